@@ -0,0 +1,18 @@
+/* C interface to the is-terminal crate, enabled with its `capi` feature. */
+
+#ifndef IS_TERMINAL_H
+#define IS_TERMINAL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Returns 1 if the given standard stream (0 for stdin, 1 for stdout, or 2
+ * for stderr) is a terminal, 0 if it isn't, or -1 if `stream` is invalid. */
+int atty_is(int stream);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* IS_TERMINAL_H */
@@ -0,0 +1,745 @@
+//! Color-support detection.
+
+use crate::*;
+
+/// The number of colors a terminal supports, as reported by [`color_level`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ColorLevel {
+    /// No color.
+    None,
+    /// The 16 basic ANSI colors.
+    Ansi16,
+    /// The 256-color xterm palette.
+    Ansi256,
+    /// 24-bit RGB color.
+    TrueColor,
+}
+
+/// Returns `true` if color output on `stream` is appropriate.
+///
+/// This is `color_level(stream) != ColorLevel::None`; see [`color_level`]
+/// for how it's decided.
+pub fn supports_color(stream: Stream) -> bool {
+    supports_color_in(stream, &SystemEnv)
+}
+
+/// Like [`supports_color`], but reads the environment from `env`.
+pub fn supports_color_in(stream: Stream, env: &dyn EnvSource) -> bool {
+    color_level_in(stream, env) != ColorLevel::None
+}
+
+/// Returns the number of colors appropriate for output on `stream`.
+///
+/// The environment is consulted in this order:
+///
+/// | Environment                           | Result                          |
+/// | ------------------------------------- | ------------------------------- |
+/// | `NO_COLOR` is non-empty               | `None`, regardless of the rest  |
+/// | `FORCE_COLOR` is `0` or `false`       | `None`                          |
+/// | `FORCE_COLOR` is `1`, `2`, or `3`     | At least `Ansi16`, `Ansi256`, or `TrueColor` |
+/// | `FORCE_COLOR` is set to anything else | At least `Ansi16`               |
+/// | `CLICOLOR_FORCE` is set and not `0`   | At least `Ansi16`               |
+/// | `stream` isn't a terminal             | `None`                          |
+/// | `TERM` is `dumb` (or unset, on Unix)  | `None`                          |
+/// | `CLICOLOR` is `0`                     | `None`                          |
+///
+/// So `NO_COLOR` wins over forcing color on, and forcing color on wins over
+/// a dumb `TERM` or a stream which isn't a terminal. Otherwise, the level is
+/// `TrueColor` if `COLORTERM` is `truecolor` or `24bit`, or `TERM` names a
+/// direct-color terminfo entry, `Ansi256` if `TERM` mentions `256`, and
+/// `Ansi16` for any other terminal.
+///
+/// With the `term` feature, the terminal program is also taken into account,
+/// since not all of them set `COLORTERM`: iTerm2 3.0 and newer, kitty,
+/// WezTerm, Windows Terminal, and terminals built on VTE 0.36 and newer are
+/// `TrueColor`, and macOS's Terminal.app, which doesn't support 24-bit
+/// color, is at most `Ansi256`.
+pub fn color_level(stream: Stream) -> ColorLevel {
+    color_level_in(stream, &SystemEnv)
+}
+
+/// Like [`color_level`], but reads the environment from `env`.
+pub fn color_level_in(stream: Stream, env: &dyn EnvSource) -> ColorLevel {
+    color_level_with(env, resolve_in(stream, env))
+}
+
+/// Returns the number of colors appropriate for output on `stream`, as
+/// [`color_level`] does, or `BadDescriptor` if the stream is closed.
+///
+/// A stream which isn't a terminal isn't an error here: it gets whatever
+/// level the environment gives it, which is usually `None`.
+pub fn try_color_level(stream: Stream) -> Result<ColorLevel, TerminalError> {
+    check_descriptor(stream)?;
+    Ok(color_level(stream))
+}
+
+pub(crate) fn color_level_with(env: &dyn EnvSource, tty: bool) -> ColorLevel {
+    let preference = color_env_preference_in(env);
+    if preference == Some(false) {
+        return ColorLevel::None;
+    }
+
+    let term = env.get("TERM");
+    let name = term.as_deref().unwrap_or("");
+    let truecolor = matches!(
+        env.get("COLORTERM").as_deref(),
+        Some("truecolor") | Some("24bit")
+    );
+    let detected = if truecolor || name.ends_with("-direct") {
+        ColorLevel::TrueColor
+    } else if name.contains("256") {
+        ColorLevel::Ansi256
+    } else {
+        ColorLevel::Ansi16
+    };
+    #[cfg(feature = "term")]
+    let detected = term_program_color_level(env, detected);
+
+    if preference == Some(true) {
+        let forced = match env.get("FORCE_COLOR").as_deref() {
+            Some("2") => ColorLevel::Ansi256,
+            Some("3") => ColorLevel::TrueColor,
+            _ => ColorLevel::Ansi16,
+        };
+        forced.max(detected)
+    } else if !tty || term_is_dumb(term.as_deref()) {
+        ColorLevel::None
+    } else {
+        detected
+    }
+}
+
+/// Adjusts the color level detected from `TERM` and `COLORTERM` for
+/// terminal programs known to support more, or fewer, colors than they say.
+#[cfg(feature = "term")]
+fn term_program_color_level(env: &dyn EnvSource, detected: ColorLevel) -> ColorLevel {
+    match term_program_in(env) {
+        // Terminal.app sets `TERM=xterm-256color` but can't show RGB colors.
+        Some(TermProgram::AppleTerminal) => return detected.min(ColorLevel::Ansi256),
+        Some(TermProgram::ITerm2)
+            if version_at_least(env.get("TERM_PROGRAM_VERSION").as_deref(), (3, 0)) =>
+        {
+            return ColorLevel::TrueColor
+        }
+        Some(TermProgram::Kitty)
+        | Some(TermProgram::WezTerm)
+        | Some(TermProgram::WindowsTerminal) => return ColorLevel::TrueColor,
+        _ => {}
+    }
+
+    // VTE encodes its version as a number, such as 3600 for 0.36.0.
+    if env
+        .get("VTE_VERSION")
+        .and_then(|version| version.parse::<u32>().ok())
+        .map_or(false, |version| version >= 3600)
+    {
+        return ColorLevel::TrueColor;
+    }
+    detected
+}
+
+/// Returns the number of colors to use on `stream`, which is [`color_level`]
+/// adjusted for what [`size`] says about the terminal.
+///
+/// When the two disagree, the size wins. On a terminal narrower than 10
+/// columns, which is more likely an embedded or degenerate display than a
+/// terminal emulator, this is `None`. On a terminal whose size is unknown,
+/// which usually means it isn't a full terminal emulator either, this is at
+/// most `Ansi256`. Otherwise, and for streams which aren't terminals, where
+/// the size means nothing, this is the same as `color_level`.
+pub fn effective_color_level(stream: Stream) -> ColorLevel {
+    let tty = is(stream);
+    effective_color_level_with(color_level_with(&SystemEnv, tty), tty, size(stream))
+}
+
+pub(crate) fn effective_color_level_with(
+    level: ColorLevel,
+    tty: bool,
+    size: Option<(u16, u16)>,
+) -> ColorLevel {
+    match size {
+        _ if !tty => level,
+        Some((columns, _)) if columns < 10 => ColorLevel::None,
+        Some(_) => level,
+        None => level.min(ColorLevel::Ansi256),
+    }
+}
+
+/// Returns the preference for color expressed by the environment, if any.
+///
+/// This is `Some(false)` if `NO_COLOR` is non-empty, `FORCE_COLOR` is `0` or
+/// `false`, or `CLICOLOR` is `0`, and `Some(true)` if `FORCE_COLOR` is set to
+/// anything else or `CLICOLOR_FORCE` is set and not `0`. These are checked in
+/// the order shown in the table on [`color_level`], so `NO_COLOR` wins over
+/// `CLICOLOR_FORCE`, for example. When the environment expresses no
+/// preference, this returns `None`, and the caller should fall back to
+/// checking whether the stream is a terminal.
+pub fn color_env_preference() -> Option<bool> {
+    color_env_preference_in(&SystemEnv)
+}
+
+/// Like [`color_env_preference`], but reads the environment from `env`.
+pub fn color_env_preference_in(env: &dyn EnvSource) -> Option<bool> {
+    if env.get("NO_COLOR").map_or(false, |value| !value.is_empty()) {
+        return Some(false);
+    }
+    match env.get("FORCE_COLOR").as_deref() {
+        Some("0") | Some("false") => return Some(false),
+        Some(_) => return Some(true),
+        None => {}
+    }
+    match env.get("CLICOLOR_FORCE").as_deref() {
+        Some("0") | None => {}
+        Some(_) => return Some(true),
+    }
+    if env.get("CLICOLOR").as_deref() == Some("0") {
+        return Some(false);
+    }
+    None
+}
+
+/// Returns the output stream which should get color, for tools which write
+/// their main output to stdout and diagnostics to stderr: `Stdout` if it's a
+/// terminal, or else `Stderr` if it is, or else `None`.
+///
+/// This returns `None` whenever the environment disables color, as
+/// described on [`color_env_preference`], such as when `NO_COLOR` is set.
+pub fn color_target() -> Option<Stream> {
+    color_target_with(&SystemEnv, is(Stream::Stdout), is(Stream::Stderr))
+}
+
+pub(crate) fn color_target_with(
+    env: &dyn EnvSource,
+    stdout_tty: bool,
+    stderr_tty: bool,
+) -> Option<Stream> {
+    if color_env_preference_in(env) == Some(false) {
+        None
+    } else if stdout_tty {
+        Some(Stream::Stdout)
+    } else if stderr_tty {
+        Some(Stream::Stderr)
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if color is appropriate for output written directly to
+/// `/dev/tty`, the process's controlling terminal, regardless of where the
+/// standard streams go.
+///
+/// Tools which talk to the user around redirection, such as by writing
+/// prompts or diagnostics to `/dev/tty` while their output goes to a file,
+/// can use this instead of [`color_level`] on a standard stream. This opens
+/// `/dev/tty` without making it the controlling terminal, and returns
+/// `false` if that fails, as it does when the process has no controlling
+/// terminal. Otherwise, the environment decides, as it does for a terminal
+/// in `color_level`. This is only available on Unix-family platforms.
+#[cfg(unix)]
+pub fn color_via_controlling_tty() -> bool {
+    use rustix::fs::{cwd, openat, Mode, OFlags};
+
+    let tty = openat(
+        cwd(),
+        "/dev/tty",
+        OFlags::RDWR | OFlags::NOCTTY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )
+    .map_or(false, |tty| rustix::termios::isatty(&tty));
+    tty && color_level_with(&SystemEnv, true) != ColorLevel::None
+}
+
+/// How a logger should format output to stderr, as decided by
+/// [`stderr_is_tty_for_logging`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum LogTarget {
+    /// Stderr is a terminal and color is permitted.
+    TtyColor,
+    /// Stderr is a terminal, but color is disabled, such as by `NO_COLOR` or
+    /// `TERM=dumb`.
+    TtyPlain,
+    /// Stderr is a file, pipe, or something else which isn't a terminal, so
+    /// log lines should be plain and self-contained, with timestamps.
+    FileOrPipe,
+}
+
+/// Decides how a logger writing to stderr should format its output.
+///
+/// This is `FileOrPipe` if stderr isn't a terminal, even if the environment
+/// forces color on, since log files and collectors rarely want escape
+/// sequences. Otherwise, it's `TtyColor` if [`color_level`] for stderr isn't
+/// `ColorLevel::None`, and `TtyPlain` if it is.
+pub fn stderr_is_tty_for_logging() -> LogTarget {
+    log_target_with(&SystemEnv, is(Stream::Stderr))
+}
+
+pub(crate) fn log_target_with(env: &dyn EnvSource, tty: bool) -> LogTarget {
+    if !tty {
+        LogTarget::FileOrPipe
+    } else if color_level_with(env, true) == ColorLevel::None {
+        LogTarget::TtyPlain
+    } else {
+        LogTarget::TtyColor
+    }
+}
+
+/// How a progress indicator should update, as decided by
+/// [`progress_style`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ProgressStyle {
+    /// Redraw the line in place, by starting each update with `\r`.
+    CarriageReturn,
+    /// Write each update on a line of its own, so that logs of the output
+    /// stay readable.
+    NewLine,
+    /// Don't write progress at all, since it would be discarded.
+    None,
+}
+
+/// Decides how a progress indicator written to `stream` should update.
+///
+/// This is `None` if `stream` is the null device, as [`is_null`] reports,
+/// `CarriageReturn` if it's a terminal and [`is_ci`] doesn't report a CI
+/// service, whose log viewers show every redrawn line, and `NewLine`
+/// otherwise, such as for pipes and files.
+pub fn progress_style(stream: Stream) -> ProgressStyle {
+    progress_style_with(&SystemEnv, is(stream), is_null(stream))
+}
+
+pub(crate) fn progress_style_with(env: &dyn EnvSource, tty: bool, null: bool) -> ProgressStyle {
+    if null {
+        ProgressStyle::None
+    } else if tty && !is_ci_with(env) {
+        ProgressStyle::CarriageReturn
+    } else {
+        ProgressStyle::NewLine
+    }
+}
+
+/// The decisions made by [`interactive_caps`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct InteractiveCaps {
+    /// Whether the user can be prompted, that is, whether `prompt_stream`
+    /// is `Some`.
+    pub can_prompt: bool,
+    /// Whether standard output should be colored, as [`supports_color`]
+    /// decides.
+    pub can_color: bool,
+    /// Whether a progress bar makes sense, that is, whether
+    /// `progress_stream` is `Some`.
+    pub can_progress: bool,
+    /// The stream to write prompts to: `Stdout`, or `Stderr` if stdout is
+    /// redirected, as decided by [`prompt_should_go_to_stderr`]. This is
+    /// `None` if standard input isn't a terminal, or neither output is.
+    pub prompt_stream: Option<Stream>,
+    /// The stream to draw progress bars on: `Stderr`, so they stay out of
+    /// the program's output, or `Stdout` if only it is a terminal. This is
+    /// `None` if neither is a terminal, or if [`is_ci`] says the process is
+    /// running under CI, where logs are kept and redrawn lines pile up.
+    pub progress_stream: Option<Stream>,
+}
+
+/// Makes the decisions a command-line program typically needs about how to
+/// interact with its user: whether and where to prompt, whether to color its
+/// output, and whether and where to show progress.
+///
+/// See the fields of [`InteractiveCaps`] for the policies used.
+pub fn interactive_caps() -> InteractiveCaps {
+    interactive_caps_with(
+        &SystemEnv,
+        is(Stream::Stdin),
+        is(Stream::Stdout),
+        is(Stream::Stderr),
+    )
+}
+
+pub(crate) fn interactive_caps_with(
+    env: &dyn EnvSource,
+    stdin_tty: bool,
+    stdout_tty: bool,
+    stderr_tty: bool,
+) -> InteractiveCaps {
+    let prompt_stream = if !stdin_tty {
+        None
+    } else if stdout_tty {
+        Some(Stream::Stdout)
+    } else if prompt_should_go_to_stderr_with(stdin_tty, stdout_tty, stderr_tty) {
+        Some(Stream::Stderr)
+    } else {
+        None
+    };
+    let progress_stream = if is_ci_with(env) {
+        None
+    } else if stderr_tty {
+        Some(Stream::Stderr)
+    } else if stdout_tty {
+        Some(Stream::Stdout)
+    } else {
+        None
+    };
+    InteractiveCaps {
+        can_prompt: prompt_stream.is_some(),
+        can_color: color_level_with(env, stdout_tty) != ColorLevel::None,
+        can_progress: progress_stream.is_some(),
+        prompt_stream,
+        progress_stream,
+    }
+}
+
+/// Decides whether to use fancy output on `stream`, such as color, progress
+/// bars, and redrawn status lines, as the crate recommends for programs
+/// which want one answer rather than asking [`is`] and the other checks
+/// themselves.
+///
+/// The checks are made in this order, and the first which applies decides:
+///
+/// | Situation                                                    | Result  |
+/// | ------------------------------------------------------------ | ------- |
+/// | [`color_env_preference`] disables color, as `NO_COLOR` does  | `false` |
+/// | [`color_env_preference`] forces color, as `FORCE_COLOR` does | `true`  |
+/// | `stream` isn't a terminal                                    | `false` |
+/// | [`is_ci`] reports a CI service                               | `false` |
+/// | `TERM` is `dumb`, or unset outside Windows                   | `false` |
+/// | [`screen_reader_active`] says a screen reader is in use      | `false` |
+/// | Output goes to the systemd journal, on Linux                 | `false` |
+/// | Otherwise                                                    | `true`  |
+pub fn fancy_output(stream: Stream) -> bool {
+    #[cfg(target_os = "linux")]
+    let journald = piped_to_journald();
+    #[cfg(not(target_os = "linux"))]
+    let journald = false;
+
+    fancy_output_with(&SystemEnv, is(stream), screen_reader_active(), journald)
+}
+
+pub(crate) fn fancy_output_with(
+    env: &dyn EnvSource,
+    tty: bool,
+    screen_reader: bool,
+    journald: bool,
+) -> bool {
+    if let Some(preference) = color_env_preference_in(env) {
+        return preference;
+    }
+    tty && !is_ci_with(env)
+        && !term_is_dumb(env.get("TERM").as_deref())
+        && !screen_reader
+        && !journald
+}
+
+/// Explains why the session isn't interactive, for a `--why` flag or other
+/// debugging output, or returns `None` if it is.
+///
+/// The session is interactive if standard input and output are terminals,
+/// [`is_ci`] doesn't report a CI service, and color isn't disabled on
+/// standard output by `NO_COLOR` or `TERM=dumb`. Otherwise, this returns
+/// each reason it isn't, separated by `; `, such as
+/// `stdin is a pipe; running under CI (GITHUB_ACTIONS)`.
+pub fn why_not_interactive() -> Option<String> {
+    why_not_interactive_with(
+        &SystemEnv,
+        (is(Stream::Stdin), inspect(Stream::Stdin).file_kind),
+        (is(Stream::Stdout), inspect(Stream::Stdout).file_kind),
+    )
+}
+
+pub(crate) fn why_not_interactive_with(
+    env: &dyn EnvSource,
+    stdin: (bool, FileKind),
+    stdout: (bool, FileKind),
+) -> Option<String> {
+    let mut reasons = Vec::new();
+    for &(name, (tty, kind)) in &[("stdin", stdin), ("stdout", stdout)] {
+        if tty {
+            continue;
+        }
+        let what = match kind {
+            FileKind::Pipe => "is a pipe",
+            FileKind::File if name == "stdin" => "is read from a file",
+            FileKind::File => "is redirected to a file",
+            FileKind::CharDevice => "is a device which isn't a terminal, such as /dev/null",
+            FileKind::Socket => "is a socket",
+            FileKind::Unknown => "is closed",
+            FileKind::Tty | FileKind::Other => "isn't a terminal",
+        };
+        reasons.push(format!("{} {}", name, what));
+    }
+    if let Some(variable) = ci_variable_with(env) {
+        reasons.push(format!("running under CI ({})", variable));
+    }
+    if env.get("NO_COLOR").map_or(false, |value| !value.is_empty()) {
+        reasons.push("NO_COLOR is set".to_owned());
+    } else if term_is_dumb(env.get("TERM").as_deref()) && stdout.0 {
+        reasons.push("TERM is dumb".to_owned());
+    }
+
+    if reasons.is_empty() {
+        None
+    } else {
+        Some(reasons.join("; "))
+    }
+}
+
+/// Returns `true` if escape sequences written to `stream` would be recorded
+/// verbatim rather than interpreted, so that output should be plain text.
+///
+/// | Situation                               | Result  |
+/// | --------------------------------------- | ------- |
+/// | `NO_COLOR` is non-empty                 | `true`  |
+/// | `stream` is a terminal                  | `false` |
+/// | `stream` is a pipe read by a pager      | `false` |
+/// | `stream` is a file or any other pipe    | `true`  |
+/// | [`is_ci`] reports a CI service          | `true`  |
+/// | Otherwise, such as for `/dev/null`      | `false` |
+///
+/// Pagers are detected as `piped_to_pager` does, for stdout on Linux.
+pub fn escapes_would_be_literal(stream: Stream) -> bool {
+    #[cfg(unix)]
+    let pager = stream == Stream::Stdout && piped_to_pager();
+    #[cfg(not(unix))]
+    let pager = false;
+
+    escapes_would_be_literal_with(
+        &SystemEnv,
+        is(stream),
+        pager,
+        is_file(stream) || is_pipe(stream),
+    )
+}
+
+/// Decides [`escapes_would_be_literal`], where `recorded` is whether the
+/// stream is a file or a pipe, including one read by a pager.
+pub(crate) fn escapes_would_be_literal_with(
+    env: &dyn EnvSource,
+    tty: bool,
+    pager: bool,
+    recorded: bool,
+) -> bool {
+    if env.get("NO_COLOR").map_or(false, |value| !value.is_empty()) {
+        true
+    } else if tty || pager {
+        false
+    } else {
+        recorded || is_ci_with(env)
+    }
+}
+
+/// Returns `true` if trailing whitespace written to `stream` is likely to
+/// reach its reader, so that formatters can tell whether padding the last
+/// column of a table with spaces is worth the bytes.
+///
+/// | Situation                                 | Result  |
+/// | ----------------------------------------- | ------- |
+/// | `stream` is a terminal                    | `true`  |
+/// | `stream` is a file                        | `true`  |
+/// | `stream` is a pipe and [`is_ci`] is false | `true`  |
+/// | `stream` is a pipe and [`is_ci`] is true  | `false` |
+/// | Otherwise, such as for `/dev/null`        | `false` |
+///
+/// A terminal keeps trailing spaces, though they're invisible there. Under
+/// CI, output piped to the service is usually collected by log processors,
+/// some of which trim each line, and a pager reading a pipe may do the
+/// same, which this can't tell.
+pub fn preserves_trailing_whitespace(stream: Stream) -> bool {
+    preserves_trailing_whitespace_with(&SystemEnv, is(stream), is_file(stream), is_pipe(stream))
+}
+
+pub(crate) fn preserves_trailing_whitespace_with(
+    env: &dyn EnvSource,
+    tty: bool,
+    file: bool,
+    pipe: bool,
+) -> bool {
+    tty || file || (pipe && !is_ci_with(env))
+}
+
+/// Installs a panic hook which strips escape sequences from panic messages
+/// when stderr won't display them, so that crash output in CI logs and
+/// redirected files stays readable.
+///
+/// The hook wraps whichever hook was installed before. Messages are passed
+/// to it unchanged when [`color_level`] permits color on stderr, or when they
+/// contain no escape sequences. Otherwise, this prints the stripped message
+/// and its location itself, in the format of Rust's default hook, and the
+/// previous hook isn't called, so no backtrace is printed for such panics.
+pub fn install_panic_color_filter() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+        match message {
+            Some(message)
+                if message.contains('\x1b') && color_level(Stream::Stderr) == ColorLevel::None =>
+            {
+                use std::io::Write;
+
+                let thread = std::thread::current();
+                let name = thread.name().unwrap_or("<unnamed>");
+                let _ = match info.location() {
+                    Some(location) => writeln!(
+                        std::io::stderr(),
+                        "thread '{}' panicked at {}:\n{}",
+                        name,
+                        location,
+                        strip_escapes(message)
+                    ),
+                    None => writeln!(
+                        std::io::stderr(),
+                        "thread '{}' panicked:\n{}",
+                        name,
+                        strip_escapes(message)
+                    ),
+                };
+            }
+            _ => previous(info),
+        }
+    }));
+}
+
+/// Removes the escape sequences from `text`: control sequences such as
+/// `\x1b[31m`, operating system commands such as OSC 8 hyperlinks, and other
+/// strings and two- and three-byte escapes.
+pub(crate) fn strip_escapes(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // A control sequence ends with a byte from `@` to `~`.
+            Some('[') => {
+                for c in &mut chars {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Strings end with BEL or ST, `\x1b\\`.
+            Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Other escapes, such as `\x1b(0`, have intermediate bytes from
+            // space to `/` before their final byte.
+            Some(' '..='/') => {
+                for c in &mut chars {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// Returns `true` if escape sequences written to `stream` will be
+/// interpreted rather than displayed, so that cursor movement, styling, and
+/// other ANSI sequences can be used on it.
+///
+/// This is whether `stream` is a terminal and `TERM` isn't `dumb`. On
+/// Windows, a console qualifies only if virtual-terminal processing is
+/// enabled in its mode; see [`enable_ansi`]. Unlike [`color_level`], this
+/// ignores `NO_COLOR` and friends, which concern color only.
+pub fn supports_ansi(stream: Stream) -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+
+        if let Some(mode) = console_mode(stream.as_raw_handle()) {
+            return mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0;
+        }
+    }
+
+    is(stream) && !term_is_dumb(env_var("TERM").as_deref())
+}
+
+/// Turns on interpretation of escape sequences on `stream` where that's
+/// needed and possible, and returns whether they'll be interpreted, as
+/// [`supports_ansi`] reports it afterwards.
+///
+/// On Windows, this sets `ENABLE_VIRTUAL_TERMINAL_PROCESSING` in the mode of
+/// a console which lacks it, which fails on consoles which predate Windows
+/// 10. Elsewhere, there's nothing to turn on, so this is `supports_ansi`.
+pub fn enable_ansi(stream: Stream) -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{
+            SetConsoleMode, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+        };
+
+        let handle = stream.as_raw_handle();
+        if let Some(mode) = console_mode(handle) {
+            return mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+                || unsafe { SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) }
+                    != 0;
+        }
+    }
+
+    supports_ansi(stream)
+}
+
+/// Writes the escape sequence `seq` to `w` if it would be interpreted on
+/// `stream`, and returns whether it was written.
+///
+/// `w` should be the writer for `stream`, such as `std::io::stdout()` for
+/// `Stream::Stdout`; this only checks `stream`. The check is
+/// [`enable_ansi`], so on Windows consoles which support virtual-terminal
+/// processing but don't have it enabled, it's enabled first.
+pub fn write_ansi_if_supported(
+    stream: Stream,
+    w: &mut impl std::io::Write,
+    seq: &str,
+) -> std::io::Result<bool> {
+    if !enable_ansi(stream) {
+        return Ok(false);
+    }
+    w.write_all(seq.as_bytes())?;
+    Ok(true)
+}
+
+/// Returns the color choice for an `anstream` `AutoStream` writing to
+/// `stream`, from this crate's color detection, so that programs styling
+/// their output with the `anstyle` crates can use that detection in place
+/// of `anstream`'s.
+///
+/// This is `ColorChoice::Never` if [`color_level`] is `ColorLevel::None`
+/// for `stream`, and `ColorChoice::Always` otherwise, with which `anstream`
+/// emits ANSI colors, or uses the console API on Windows consoles which
+/// don't accept them. `ColorChoice::Auto`, which would have `anstream`
+/// decide for itself, and `ColorChoice::AlwaysAnsi` aren't returned.
+///
+/// # Example
+///
+/// ```
+/// use is_terminal::{auto_stream_choice, Stream};
+///
+/// // Make `anstream::stdout()` and the like follow this crate's detection.
+/// auto_stream_choice(Stream::Stdout).write_global();
+/// ```
+#[cfg(feature = "anstyle")]
+pub fn auto_stream_choice(stream: Stream) -> colorchoice::ColorChoice {
+    color_choice_for(color_level(stream))
+}
+
+#[cfg(feature = "anstyle")]
+pub(crate) fn color_choice_for(level: ColorLevel) -> colorchoice::ColorChoice {
+    match level {
+        ColorLevel::None => colorchoice::ColorChoice::Never,
+        _ => colorchoice::ColorChoice::Always,
+    }
+}
@@ -0,0 +1,279 @@
+//! Detecting many terminal properties at once.
+
+use crate::*;
+
+/// A builder for querying several facts about a stream at once, computing
+/// only the ones asked for.
+///
+/// Some queries, such as [`size`], make system calls, and others scan the
+/// environment, so callers which only want a few of them can select just
+/// those.
+///
+/// # Example
+///
+/// ```
+/// use is_terminal::{Probe, Stream};
+///
+/// let info = Probe::new(Stream::Stdout).tty().size().run();
+/// if info.tty == Some(true) {
+///     println!("stdout is a terminal of size {:?}", info.size);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Probe {
+    stream: Stream,
+    tty: bool,
+    size: bool,
+    #[cfg(feature = "color")]
+    color: bool,
+}
+
+impl Probe {
+    /// Returns a `Probe` for `stream` which doesn't query anything yet.
+    pub fn new(stream: Stream) -> Self {
+        Self {
+            stream,
+            tty: false,
+            size: false,
+            #[cfg(feature = "color")]
+            color: false,
+        }
+    }
+
+    /// Queries whether the stream is a terminal, as [`is`] does.
+    pub fn tty(mut self) -> Self {
+        self.tty = true;
+        self
+    }
+
+    /// Queries the terminal size, as [`size`] does.
+    pub fn size(mut self) -> Self {
+        self.size = true;
+        self
+    }
+
+    /// Queries the color level, as [`color_level`] does.
+    #[cfg(feature = "color")]
+    pub fn color(mut self) -> Self {
+        self.color = true;
+        self
+    }
+
+    /// Runs the selected queries.
+    pub fn run(self) -> TerminalInfo {
+        self.run_with(&size)
+    }
+
+    pub(crate) fn run_with(self, size: &dyn Fn(Stream) -> Option<(u16, u16)>) -> TerminalInfo {
+        let mut info = TerminalInfo::default();
+        #[cfg(feature = "color")]
+        let need_tty = self.tty || self.color;
+        #[cfg(not(feature = "color"))]
+        let need_tty = self.tty;
+        let tty = if need_tty {
+            Some(is(self.stream))
+        } else {
+            None
+        };
+        if self.tty {
+            info.tty = tty;
+        }
+        if self.size {
+            info.size = size(self.stream);
+        }
+        #[cfg(feature = "color")]
+        if self.color {
+            info.color = tty.map(|tty| color_level_with(&SystemEnv, tty));
+        }
+        info
+    }
+}
+
+/// The facts gathered by [`Probe::run`].
+///
+/// Fields for queries which weren't selected are `None`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct TerminalInfo {
+    /// Whether the stream is a terminal.
+    pub tty: Option<bool>,
+    /// The terminal size, as `(columns, rows)`. This is also `None` if the
+    /// size was selected but is unknown.
+    pub size: Option<(u16, u16)>,
+    /// The color level.
+    #[cfg(feature = "color")]
+    pub color: Option<ColorLevel>,
+}
+
+/// Queries everything this crate can cheaply tell about `stream` at once,
+/// returning an immutable snapshot of the results.
+///
+/// Where a [`Probe`] runs only the queries it's asked for, this runs them
+/// all. The snapshot owns its results, so it can be shared with other
+/// threads, such as the workers of a thread pool, which then don't need to
+/// repeat the system calls themselves. It isn't updated if the terminal
+/// changes afterwards, such as when it's resized, or if the stream is
+/// redirected, which [`verify_consistency`] can check for on Unix-family
+/// platforms.
+pub fn detect(stream: Stream) -> TerminalSnapshot {
+    #[cfg(unix)]
+    record_identity(stream);
+    let tty = is(stream);
+    TerminalSnapshot {
+        stream,
+        tty,
+        pipe: is_pipe(stream),
+        size: size(stream),
+        #[cfg(feature = "color")]
+        color: color_level_with(&SystemEnv, tty),
+        #[cfg(feature = "term")]
+        term_program: term_program(),
+    }
+}
+
+/// The facts gathered by [`detect`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TerminalSnapshot {
+    stream: Stream,
+    tty: bool,
+    pipe: bool,
+    size: Option<(u16, u16)>,
+    #[cfg(feature = "color")]
+    color: ColorLevel,
+    #[cfg(feature = "term")]
+    term_program: Option<TermProgram>,
+}
+
+impl TerminalSnapshot {
+    /// Returns the stream this snapshot describes.
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+
+    /// Returns whether the stream was a terminal, as [`is`] reported.
+    pub fn is_tty(&self) -> bool {
+        self.tty
+    }
+
+    /// Returns whether the stream was a pipe, as [`is_pipe`] reported.
+    pub fn is_pipe(&self) -> bool {
+        self.pipe
+    }
+
+    /// Returns the terminal size, as `(columns, rows)`, as [`size`]
+    /// reported.
+    pub fn size(&self) -> Option<(u16, u16)> {
+        self.size
+    }
+
+    /// Returns the color level, as [`color_level`] reported.
+    #[cfg(feature = "color")]
+    pub fn color_level(&self) -> ColorLevel {
+        self.color
+    }
+
+    /// Returns the terminal program, as [`term_program`] reported.
+    #[cfg(feature = "term")]
+    pub fn term_program(&self) -> Option<&TermProgram> {
+        self.term_program.as_ref()
+    }
+}
+
+/// Returns `true` if `stream` still refers to the same file as it did when
+/// [`detect`] or [`snapshot`] last took a snapshot of it, comparing the
+/// device and inode numbers `fstat` reports.
+///
+/// Long-running programs whose standard streams may be reopened, such as
+/// servers which reopen their logs when `logrotate` sends them `SIGHUP`,
+/// can use this to tell when to call [`detect`] or [`is`] again and to
+/// invalidate anything they cached from them. This returns `true` if
+/// neither has been called for `stream` yet, or couldn't `fstat` it, and
+/// `false` if the stream can no longer be `fstat`ed.
+///
+/// This isn't async-signal-safe: the identities are kept behind a spin
+/// lock, which a signal handler calling this could wait on forever if the
+/// signal interrupted `detect` on the same thread. Handlers should set a
+/// flag for the program to check and call this from outside them.
+#[cfg(unix)]
+pub fn verify_consistency(stream: Stream) -> bool {
+    match identity_slot(stream).with(|identity| *identity) {
+        Some(snapshotted) => file_identity(stream) == Some(snapshotted),
+        None => true,
+    }
+}
+
+/// Remembers what `stream` refers to, for [`verify_consistency`].
+#[cfg(unix)]
+pub(crate) fn record_identity(stream: Stream) {
+    // `fstat` first, so that only the copy happens under the lock.
+    let current = file_identity(stream);
+    identity_slot(stream).with(|identity| *identity = current);
+}
+
+/// Returns the device and inode numbers of the file `stream` refers to.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn file_identity(stream: Stream) -> Option<(u64, u64)> {
+    rustix::fs::fstat(stream.as_fd())
+        .ok()
+        .map(|stat| (stat.st_dev as u64, stat.st_ino as u64))
+}
+
+/// The identities [`detect`] recorded, one for each standard stream.
+#[cfg(unix)]
+static IDENTITIES: [IdentitySlot; 3] = [
+    IdentitySlot::new(),
+    IdentitySlot::new(),
+    IdentitySlot::new(),
+];
+
+#[cfg(unix)]
+fn identity_slot(stream: Stream) -> &'static IdentitySlot {
+    match stream {
+        Stream::Stdin => &IDENTITIES[0],
+        Stream::Stdout => &IDENTITIES[1],
+        Stream::Stderr => &IDENTITIES[2],
+    }
+}
+
+/// A file identity guarded by a spin lock, since a device and inode number
+/// pair is too wide for an atomic on every platform, and `Mutex::new`
+/// isn't `const` on this crate's minimum Rust version.
+#[cfg(unix)]
+struct IdentitySlot {
+    locked: core::sync::atomic::AtomicBool,
+    identity: core::cell::UnsafeCell<Option<(u64, u64)>>,
+}
+
+// SAFETY: `identity` is only accessed in `with`, while holding `locked`.
+#[cfg(unix)]
+unsafe impl Sync for IdentitySlot {}
+
+#[cfg(unix)]
+impl IdentitySlot {
+    const fn new() -> Self {
+        Self {
+            locked: core::sync::atomic::AtomicBool::new(false),
+            identity: core::cell::UnsafeCell::new(None),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Option<(u64, u64)>) -> R) -> R {
+        use core::sync::atomic::Ordering;
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // `core::hint::spin_loop` is newer than this crate's minimum Rust
+            // version.
+            #[allow(deprecated)]
+            core::sync::atomic::spin_loop_hint();
+        }
+        // SAFETY: Holding `locked` gives exclusive access to `identity`.
+        let result = f(unsafe { &mut *self.identity.get() });
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
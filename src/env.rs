@@ -0,0 +1,610 @@
+//! Reading the environment, and environment-based checks.
+
+use crate::*;
+
+/// Returns `true` if `term`, the value of `TERM`, describes a terminal which
+/// doesn't understand escape sequences.
+///
+/// Windows consoles don't usually set `TERM`, so it's only required to be
+/// set on other platforms.
+#[cfg(any(feature = "term", feature = "color"))]
+pub(crate) fn term_is_dumb(term: Option<&str>) -> bool {
+    match term {
+        Some(term) => term == "dumb",
+        None => cfg!(not(windows)),
+    }
+}
+
+/// Returns what `ATTY_FORCE` forces [`is`] to report, or `None` if it's
+/// unset or not a recognized value.
+pub(crate) fn forced_tty(env: &dyn EnvSource) -> Option<bool> {
+    match env.get("ATTY_FORCE")?.as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the process is a Linux program running under the
+/// Windows Subsystem for Linux.
+pub fn is_wsl() -> bool {
+    wsl_marker() == Some(true)
+}
+
+/// Looks for signs of WSL, returning `None` if they can't be checked.
+fn wsl_marker() -> Option<bool> {
+    if env_var("WSL_DISTRO_NAME").is_some() || env_var("WSL_INTEROP").is_some() {
+        return Some(true);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        // WSL kernels identify themselves in their release string, as
+        // "...-microsoft-standard-WSL2" or "...-Microsoft" for WSL 1.
+        std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|release| {
+                let release = release.to_ascii_lowercase();
+                release.contains("microsoft") || release.contains("wsl")
+            })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        Some(false)
+    }
+}
+
+/// Returns `true` if a debugger is attached to the process.
+///
+/// Debugger consoles, such as those of gdb, lldb, and Visual Studio, are
+/// often ptys or consoles which don't render escape sequences well, so
+/// tools may want plain output under one. This checks `TracerPid` in
+/// `/proc/self/status` on Linux, the `P_TRACED` flag from `sysctl` on macOS,
+/// and `IsDebuggerPresent` on Windows, and is `false` elsewhere.
+///
+/// The answer is remembered after the first call, since debuggers are
+/// rarely attached or detached while a program runs.
+pub fn under_debugger() -> bool {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    // 0 until checked, then 1 for no debugger and 2 for one.
+    static UNDER_DEBUGGER: AtomicU8 = AtomicU8::new(0);
+
+    match UNDER_DEBUGGER.load(Ordering::Relaxed) {
+        0 => {
+            let traced = debugger_attached();
+            UNDER_DEBUGGER.store(if traced { 2 } else { 1 }, Ordering::Relaxed);
+            traced
+        }
+        state => state == 2,
+    }
+}
+
+fn debugger_attached() -> bool {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        traced_in_status_file(std::path::Path::new("/proc/self/status"))
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    unsafe {
+        // `kinfo_proc` isn't in libc, so read `kp_proc.p_flag` from its
+        // start: it follows a 16-byte union and two pointers in
+        // `extern_proc`.
+        const P_TRACED: i32 = 0x800;
+        const P_FLAG_OFFSET: usize = 32;
+
+        let mut mib = [
+            libc::CTL_KERN,
+            libc::KERN_PROC,
+            libc::KERN_PROC_PID,
+            libc::getpid(),
+        ];
+        let mut info = [0_u64; 128];
+        let mut size = core::mem::size_of_val(&info);
+        if libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as _,
+            info.as_mut_ptr().cast(),
+            &mut size,
+            core::ptr::null_mut(),
+            0,
+        ) != 0
+            || size < P_FLAG_OFFSET + 4
+        {
+            return false;
+        }
+        let flags = info
+            .as_ptr()
+            .cast::<u8>()
+            .add(P_FLAG_OFFSET)
+            .cast::<i32>()
+            .read();
+        flags & P_TRACED != 0
+    }
+
+    #[cfg(windows)]
+    {
+        unsafe { windows_sys::Win32::System::Diagnostics::Debug::IsDebuggerPresent() != 0 }
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        windows
+    )))]
+    {
+        false
+    }
+}
+
+/// Returns `true` if the file at `path`, in the format of
+/// `/proc/self/status`, has a non-zero `TracerPid`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn traced_in_status_file(path: &std::path::Path) -> bool {
+    std::fs::read_to_string(path).map_or(false, |status| {
+        status
+            .lines()
+            .filter_map(|line| line.strip_prefix("TracerPid:"))
+            .any(|pid| pid.trim().parse::<u32>().map_or(false, |pid| pid != 0))
+    })
+}
+
+/// Returns `true` if the process appears to be running in a mosh session.
+///
+/// mosh predicts local echo and synchronizes the screen over UDP, so it
+/// adds latency which programs may want to allow for, such as by
+/// throttling redraws or avoiding queries which wait for a reply, and it
+/// doesn't pass through every escape sequence. This checks the environment
+/// with [`is_mosh_in`], and on Linux also looks for a `mosh-server` among
+/// the process's ancestors, since `mosh-server` doesn't always leave
+/// anything in the environment of the shell it starts.
+pub fn is_mosh() -> bool {
+    is_mosh_in(&SystemEnv) || has_mosh_server_ancestor()
+}
+
+/// Like [`is_mosh`], but only checks `env`, for `MOSH_KEY` or
+/// `MOSH_CONNECTION`.
+pub fn is_mosh_in(env: &dyn EnvSource) -> bool {
+    env.get("MOSH_KEY").is_some() || env.get("MOSH_CONNECTION").is_some()
+}
+
+fn has_mosh_server_ancestor() -> bool {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut stat = std::fs::read_to_string("/proc/self/stat").ok();
+        // Stop eventually even if `/proc` is somehow cyclic.
+        for _ in 0..64 {
+            let parent = match stat.as_deref().and_then(proc_stat_command_and_parent) {
+                Some((_, parent)) if parent > 1 => parent,
+                _ => return false,
+            };
+            stat = std::fs::read_to_string(format!("/proc/{}/stat", parent)).ok();
+            if stat
+                .as_deref()
+                .and_then(proc_stat_command_and_parent)
+                .map_or(false, |(command, _)| command == "mosh-server")
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        false
+    }
+}
+
+/// Parses the command name and parent process ID from `stat`, the contents
+/// of a `/proc/<pid>/stat` file.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn proc_stat_command_and_parent(stat: &str) -> Option<(&str, u32)> {
+    // The command is in parentheses, and may itself contain parentheses
+    // and spaces, so find its end from the right.
+    let start = stat.find('(')? + 1;
+    let end = stat.rfind(')')?;
+    let mut fields = stat.get(end + 1..)?.split_whitespace();
+    let _state = fields.next()?;
+    let parent = fields.next()?.parse().ok()?;
+    Some((stat.get(start..end)?, parent))
+}
+
+/// Returns a best-effort hint at the user's keyboard layout, as a
+/// lowercase XKB layout name such as `us` or `de`, or `None` if there's
+/// none.
+///
+/// TUIs can use this to choose default keybindings which are easy to type,
+/// such as avoiding keys which need AltGr on the layout. The terminal
+/// doesn't report the layout, so this comes from the environment:
+/// `XKB_DEFAULT_LAYOUT`, the first layout if it lists several, or else the
+/// territory of `LANG`, such as `de` for `de_DE.UTF-8`, which is usually
+/// the name of that country's layout. See [`keyboard_hint_in`].
+pub fn keyboard_hint() -> Option<String> {
+    keyboard_hint_in(&SystemEnv)
+}
+
+/// Like [`keyboard_hint`], but reads the environment from `env`.
+pub fn keyboard_hint_in(env: &dyn EnvSource) -> Option<String> {
+    if let Some(layouts) = env.get("XKB_DEFAULT_LAYOUT") {
+        let layout = layouts.split(',').next().unwrap_or("").trim();
+        if !layout.is_empty() {
+            return Some(layout.to_ascii_lowercase());
+        }
+    }
+
+    // `language_TERRITORY.codeset@modifier`; `C` and `POSIX` have no
+    // territory.
+    let lang = env.get("LANG")?;
+    let territory = lang
+        .split(|c| c == '.' || c == '@')
+        .next()?
+        .split('_')
+        .nth(1)?;
+    if territory.len() == 2 && territory.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(territory.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// The kind of environment hosting the process, as classified by
+/// [`outer_host`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum OuterHost {
+    /// Not running under WSL or Windows Terminal.
+    Native,
+    /// A Linux program under WSL, outside of Windows Terminal.
+    Wsl,
+    /// Running in Windows Terminal, whose tabs are backed by ConPTY, either
+    /// natively or as a Linux program under WSL.
+    ConPtyUnderWt,
+    /// The environment couldn't be determined.
+    Unknown,
+}
+
+/// Classifies the environment hosting the process, for tools which mix
+/// Windows and WSL programs and need to know whether the outer host is
+/// ConPTY.
+///
+/// Windows Terminal sets `WT_SESSION`, and forwards it into WSL
+/// distributions, so it's recognized in both.
+pub fn outer_host() -> OuterHost {
+    outer_host_with(wsl_marker(), env_var("WT_SESSION").is_some())
+}
+
+pub(crate) fn outer_host_with(wsl: Option<bool>, wt_session: bool) -> OuterHost {
+    match (wsl, wt_session) {
+        (_, true) => OuterHost::ConPtyUnderWt,
+        (Some(true), false) => OuterHost::Wsl,
+        (Some(false), false) => OuterHost::Native,
+        (None, false) => OuterHost::Unknown,
+    }
+}
+
+/// A command shell, as classified by [`parent_shell`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Shell {
+    /// The Windows command prompt, `cmd.exe`.
+    Cmd,
+    /// Windows PowerShell, `powershell.exe`, up to version 5.1.
+    PowerShell,
+    /// PowerShell 6 and later, `pwsh`.
+    Pwsh,
+    /// bash.
+    Bash,
+    /// zsh.
+    Zsh,
+    /// fish.
+    Fish,
+    /// Nushell, `nu`.
+    Nushell,
+    /// Some other shell, or the shell couldn't be determined.
+    Unknown,
+}
+
+/// Classifies the shell the process was most likely started from, for tools
+/// which print commands for the user to copy and paste, whose quoting
+/// depends on the shell.
+///
+/// This is a heuristic. If the parent process is a shell this recognizes,
+/// that's the answer; the parent's name is read from `/proc` on Linux, with
+/// `proc_name` on macOS, and from a process snapshot on Windows. Otherwise,
+/// if `NU_VERSION` is set, as Nushell sets it for the commands it runs, this
+/// is Nushell, and if `PSModulePath` includes a user's own module directory,
+/// which PowerShell adds when it starts, this is PowerShell, and otherwise
+/// `SHELL`, the user's login shell, is classified. Programs started by
+/// others, such as build tools, may see `Unknown` or the login shell.
+pub fn parent_shell() -> Shell {
+    shell_with(parent_process_name().as_deref(), &SystemEnv)
+}
+
+pub(crate) fn shell_with(parent: Option<&str>, env: &dyn EnvSource) -> Shell {
+    if let Some(shell) = parent.and_then(shell_named) {
+        return shell;
+    }
+    if env.get("NU_VERSION").is_some() {
+        return Shell::Nushell;
+    }
+
+    if let Some(paths) = env.get("PSModulePath") {
+        // Entries are separated by `;` on Windows and `:` elsewhere.
+        // Splitting on both also splits off Windows drive letters, which
+        // doesn't matter for matching the ends of the entries.
+        let paths = paths.to_ascii_lowercase().replace('\\', "/");
+        for entry in paths.split(|c| c == ';' || c == ':') {
+            let entry = entry.trim_end_matches('/');
+            if entry.ends_with("/documents/windowspowershell/modules") {
+                return Shell::PowerShell;
+            }
+            if entry.ends_with("/documents/powershell/modules")
+                || entry.ends_with("/.local/share/powershell/modules")
+            {
+                return Shell::Pwsh;
+            }
+        }
+    }
+
+    env.get("SHELL")
+        .and_then(|shell| shell_named(&shell))
+        .unwrap_or(Shell::Unknown)
+}
+
+/// Returns `true` if standard output is likely to be parsed as data by a
+/// shell with structured pipelines, so that a tool can default to a
+/// machine-readable format, such as JSON, rather than a table for people.
+///
+/// This is `true` when [`parent_shell`] is Nushell, which is decided from
+/// the parent process or `NU_VERSION`, and standard output isn't a
+/// terminal; output to a terminal is displayed whatever the shell.
+/// `STRUCTURED_OUTPUT` overrides this: `1`, `true`, or `yes` means yes, and
+/// `0`, `false`, or `no` means no.
+pub fn prefers_structured_output() -> bool {
+    prefers_structured_output_with(
+        &SystemEnv,
+        parent_process_name().as_deref(),
+        is(Stream::Stdout),
+    )
+}
+
+pub(crate) fn prefers_structured_output_with(
+    env: &dyn EnvSource,
+    parent: Option<&str>,
+    stdout_tty: bool,
+) -> bool {
+    match env.get("STRUCTURED_OUTPUT").as_deref() {
+        Some("1") | Some("true") | Some("yes") => return true,
+        Some("0") | Some("false") | Some("no") => return false,
+        _ => {}
+    }
+    !stdout_tty && shell_with(parent, env) == Shell::Nushell
+}
+
+/// Classifies the shell whose executable has the name or path `path`.
+fn shell_named(path: &str) -> Option<Shell> {
+    let name = path.rsplit(|c| c == '/' || c == '\\').next()?;
+    // Login shells are started with a `-` before their name.
+    let name = name.trim_start_matches('-').to_ascii_lowercase();
+    match name.strip_suffix(".exe").unwrap_or(&name) {
+        "cmd" => Some(Shell::Cmd),
+        "powershell" => Some(Shell::PowerShell),
+        "pwsh" => Some(Shell::Pwsh),
+        "bash" => Some(Shell::Bash),
+        "zsh" => Some(Shell::Zsh),
+        "fish" => Some(Shell::Fish),
+        "nu" => Some(Shell::Nushell),
+        _ => None,
+    }
+}
+
+/// Returns the name of the parent process's executable, or `None` if it
+/// can't be determined.
+fn parent_process_name() -> Option<String> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+        let (_, parent) = proc_stat_command_and_parent(&stat)?;
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", parent)).ok()?;
+        proc_stat_command_and_parent(&stat).map(|(command, _)| command.to_owned())
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        let mut name = [0_u8; 256];
+        let len = unsafe {
+            libc::proc_name(libc::getppid(), name.as_mut_ptr().cast(), name.len() as u32)
+        };
+        if len <= 0 {
+            return None;
+        }
+        String::from_utf8(name[..len as usize].to_vec()).ok()
+    }
+
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+        use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
+        };
+        use windows_sys::Win32::System::Threading::GetCurrentProcessId;
+
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut processes = Vec::new();
+        let mut entry: PROCESSENTRY32W = core::mem::zeroed();
+        entry.dwSize = core::mem::size_of::<PROCESSENTRY32W>() as u32;
+        let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+        while more {
+            processes.push(entry);
+            more = Process32NextW(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+
+        let pid = GetCurrentProcessId();
+        let parent = processes.iter().find(|entry| entry.th32ProcessID == pid)?;
+        let parent = processes
+            .iter()
+            .find(|entry| entry.th32ProcessID == parent.th32ParentProcessID)?;
+        let len = parent
+            .szExeFile
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(parent.szExeFile.len());
+        String::from_utf16(&parent.szExeFile[..len]).ok()
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        windows
+    )))]
+    {
+        None
+    }
+}
+
+/// Returns `true` if the process appears to be running under a continuous
+/// integration service.
+///
+/// This is the case if `CI` is set to anything other than an empty value,
+/// `0`, or `false`, as most services do, or if a variable set by a service
+/// which doesn't set `CI`, such as `TF_BUILD` for Azure Pipelines,
+/// `JENKINS_URL`, or `TEAMCITY_VERSION`, is present.
+pub fn is_ci() -> bool {
+    is_ci_with(&SystemEnv)
+}
+
+pub(crate) fn is_ci_with(env: &dyn EnvSource) -> bool {
+    ci_variable_with(env).is_some()
+}
+
+/// Returns the name of the variable which [`is_ci`] decided by, preferring
+/// one which names the service, such as `GITHUB_ACTIONS`, over `CI`.
+pub(crate) fn ci_variable_with(env: &dyn EnvSource) -> Option<&'static str> {
+    let service = [
+        "TF_BUILD",
+        "JENKINS_URL",
+        "TEAMCITY_VERSION",
+        "BUILDKITE",
+        "GITHUB_ACTIONS",
+    ]
+    .iter()
+    .copied()
+    .find(|name| env.get(name).is_some());
+    match env.get("CI") {
+        Some(ci) if matches!(ci.as_str(), "" | "0" | "false") => None,
+        Some(_) => service.or(Some("CI")),
+        None => service,
+    }
+}
+
+/// Returns `true` if a screen reader appears to be in use, so that output
+/// should be kept plain and linear even on a capable terminal.
+///
+/// This is necessarily heuristic. An explicit `ACCESSIBLE` or `A11Y`
+/// variable decides it either way: `0`, `false`, or an empty value means no,
+/// and anything else means yes. Otherwise, environment variables left by
+/// screen readers are looked for: any with a name beginning with `NVDA`,
+/// `JAWS`, or `ORCA_`. If none of these are present, this returns `false`.
+pub fn screen_reader_active() -> bool {
+    screen_reader_active_with(
+        &SystemEnv,
+        std::env::vars_os().filter_map(|(name, _)| name.into_string().ok()),
+    )
+}
+
+pub(crate) fn screen_reader_active_with(
+    env: &dyn EnvSource,
+    names: impl IntoIterator<Item = String>,
+) -> bool {
+    if let Some(value) = env.get("ACCESSIBLE").or_else(|| env.get("A11Y")) {
+        return !matches!(value.as_str(), "" | "0" | "false");
+    }
+
+    names.into_iter().any(|name| {
+        name.starts_with("NVDA") || name.starts_with("JAWS") || name.starts_with("ORCA_")
+    })
+}
+
+/// Like [`is`], but also returns how long the check took.
+///
+/// On Windows, the MSYS fallback can be noticeably slow, so this is useful
+/// for measuring it against a startup-time budget. [`is`] itself doesn't pay
+/// for any timing.
+pub fn is_timed(stream: Stream) -> (bool, std::time::Duration) {
+    let start = std::time::Instant::now();
+    let is = is(stream);
+    (is, start.elapsed())
+}
+
+/// A source of environment variables, for the `*_in` variants of the
+/// functions which consult the environment.
+///
+/// This lets callers supply an environment other than the process's, such
+/// as a snapshot taken earlier, or a fixed one in tests.
+pub trait EnvSource {
+    /// Returns the value of the variable `key`, or `None` if it isn't set or
+    /// isn't valid Unicode.
+    fn get(&self, key: &str) -> Option<String>;
+}
+
+/// The process's environment, as read by [`std::env::var`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SystemEnv;
+
+impl EnvSource for SystemEnv {
+    fn get(&self, key: &str) -> Option<String> {
+        #[cfg(feature = "testutil")]
+        {
+            if let Some(value) = testutil::fake_env(key) {
+                return value;
+            }
+        }
+
+        std::env::var(key).ok()
+    }
+}
+
+impl<S: std::hash::BuildHasher> EnvSource for std::collections::HashMap<String, String, S> {
+    fn get(&self, key: &str) -> Option<String> {
+        std::collections::HashMap::get(self, key).cloned()
+    }
+}
+
+/// Returns the foreground and background colors named by `COLORFGBG`, as
+/// ANSI color indices.
+///
+/// rxvt, Konsole, and some other terminals set this to `fg;bg`, such as
+/// `15;0` for white on black, which is a cheap hint at whether the
+/// background is dark or light. rxvt may add a middle field, as in
+/// `15;default;0`, which is ignored. This returns `None` if the variable is
+/// unset or malformed.
+pub fn colorfgbg() -> Option<(u8, u8)> {
+    colorfgbg_in(&SystemEnv)
+}
+
+/// Like [`colorfgbg`], but reads the environment from `env`.
+pub fn colorfgbg_in(env: &dyn EnvSource) -> Option<(u8, u8)> {
+    let value = env.get("COLORFGBG")?;
+    let mut fields = value.split(';');
+    let fg = fields.next()?;
+    let bg = match (fields.next(), fields.next(), fields.next()) {
+        (Some(bg), None, None) | (Some(_), Some(bg), None) => bg,
+        _ => return None,
+    };
+    Some((fg.parse().ok()?, bg.parse().ok()?))
+}
+
+pub(crate) fn env_var(name: &str) -> Option<String> {
+    SystemEnv.get(name)
+}
@@ -0,0 +1,56 @@
+//! The error type of the `try_` functions.
+
+/// Why a `try_` function, such as [`try_size`](crate::try_size),
+/// couldn't answer.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TerminalError {
+    /// The stream is closed, or its handle is invalid or unset.
+    BadDescriptor,
+    /// The stream isn't a terminal.
+    NotATerminal,
+    /// The answer isn't available on this platform, or from this terminal.
+    Unsupported,
+    /// A system call failed.
+    Io(std::io::Error),
+    /// The terminal didn't reply to a query in time.
+    Timeout,
+}
+
+impl std::fmt::Display for TerminalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TerminalError::BadDescriptor => f.write_str("bad file descriptor or handle"),
+            TerminalError::NotATerminal => f.write_str("not a terminal"),
+            TerminalError::Unsupported => f.write_str("not supported"),
+            TerminalError::Io(error) => error.fmt(f),
+            TerminalError::Timeout => f.write_str("the terminal didn't reply in time"),
+        }
+    }
+}
+
+impl std::error::Error for TerminalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TerminalError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TerminalError {
+    fn from(error: std::io::Error) -> Self {
+        TerminalError::Io(error)
+    }
+}
+
+#[cfg(unix)]
+impl From<rustix::io::Errno> for TerminalError {
+    fn from(errno: rustix::io::Errno) -> Self {
+        match errno {
+            rustix::io::Errno::BADF => TerminalError::BadDescriptor,
+            rustix::io::Errno::NOTTY => TerminalError::NotATerminal,
+            errno => TerminalError::Io(errno.into()),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! What kind of file a standard stream refers to.
+
+use crate::*;
+
+/// Returns `true` if `stream` is a pipe, such as in `producer | cmd`, or a
+/// FIFO.
+///
+/// On Windows, MSYS ptys are implemented with pipes, but they're reported as
+/// terminals rather than pipes.
+pub fn is_pipe(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        unix_file_type(stream) == Some(rustix::fs::FileType::Fifo)
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Storage::FileSystem::FILE_TYPE_PIPE;
+
+        file_type(stream) == Some(FILE_TYPE_PIPE) && unsafe { !msys_tty_on(stream.as_raw_handle()) }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+/// Returns `true` if `stream` is a regular file, as when output is
+/// redirected with `cmd > out.txt`.
+///
+/// On Windows, this is whether `GetFileType` reports a disk file. It's
+/// always `false` on platforms where this can't be determined.
+pub fn is_file(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        unix_file_type(stream) == Some(rustix::fs::FileType::RegularFile)
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Storage::FileSystem::FILE_TYPE_DISK;
+
+        file_type(stream) == Some(FILE_TYPE_DISK)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+/// Returns `true` if `stream` is the null device, as when output is
+/// discarded with `cmd > /dev/null`, or `cmd > NUL` on Windows.
+///
+/// On Unix-family platforms, this compares the stream's device number with
+/// that of `/dev/null`. On Windows, it's whether `GetFileType` reports a
+/// character device which isn't a console, which also matches the rarely
+/// redirected-to serial ports. It's always `false` on platforms where this
+/// can't be determined.
+pub fn is_null(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        use rustix::fs::{cwd, fstat, statat, AtFlags, FileType};
+
+        let null = match statat(cwd(), rustix::cstr!("/dev/null"), AtFlags::empty()) {
+            Ok(null) => null,
+            Err(_) => return false,
+        };
+        fstat(stream.as_fd()).map_or(false, |stat| {
+            FileType::from_raw_mode(stat.st_mode) == FileType::CharacterDevice
+                && stat.st_rdev == null.st_rdev
+        })
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Storage::FileSystem::FILE_TYPE_CHAR;
+
+        file_type(stream) == Some(FILE_TYPE_CHAR) && console_mode(stream.as_raw_handle()).is_none()
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+/// Returns `true` if `stream` is a Unix-domain socket, as when a service
+/// manager such as inetd or systemd connects the standard streams to one,
+/// or a launcher connects them to a desktop session's log.
+///
+/// Such a stream is neither a terminal, a pipe, nor a file. Sockets of
+/// other families, such as TCP connections, don't count.
+#[cfg(unix)]
+pub fn is_unix_socket(stream: Stream) -> bool {
+    use rustix::net::{getsockname, AddressFamily};
+
+    unix_file_type(stream) == Some(rustix::fs::FileType::Socket)
+        && getsockname(stream.as_fd())
+            .map_or(false, |addr| addr.address_family() == AddressFamily::UNIX)
+}
+
+/// Returns the type of the file `stream` refers to, from `fstat`.
+#[cfg(unix)]
+pub(crate) fn unix_file_type(stream: Stream) -> Option<rustix::fs::FileType> {
+    rustix::fs::fstat(stream.as_fd())
+        .ok()
+        .map(|stat| rustix::fs::FileType::from_raw_mode(stat.st_mode))
+}
+
+/// What kind of file a stream refers to, as reported by [`inspect`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum FileKind {
+    /// A terminal, including MSYS ptys on Windows.
+    Tty,
+    /// A pipe or FIFO. On Windows, this includes sockets.
+    Pipe,
+    /// A regular file.
+    File,
+    /// A character device which isn't a terminal, such as `/dev/null` or
+    /// `NUL`.
+    CharDevice,
+    /// A socket.
+    Socket,
+    /// Something else, such as a directory or a block device.
+    Other,
+    /// The kind couldn't be determined, as when the stream is closed or on
+    /// platforms which can't tell.
+    Unknown,
+}
+
+/// The facts gathered by [`inspect`].
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct Inspection {
+    /// Whether the stream is a terminal, as [`is`] reports.
+    pub is_tty: bool,
+    /// What kind of file the stream refers to.
+    pub file_kind: FileKind,
+    /// The name of the terminal device or pipe, where available, such as
+    /// `/dev/pts/3` for a terminal, `pipe:[12345]` for a pipe on Linux, or
+    /// `\msys-1888ae32e00d56aa-pty0-to-master` for a pipe on Windows.
+    pub name: Option<String>,
+}
+
+/// Returns whether `stream` is a terminal, what kind of file it refers to,
+/// and its name.
+///
+/// This makes the same terminal check as [`is`], so a stream reported as a
+/// terminal through `ATTY_FORCE` or an override is listed as
+/// [`FileKind::Tty`] here too. Beyond that it makes a single `fstat` or
+/// `GetFileType`, and looks up a name only for the kinds of file which have
+/// one, which saves calling [`is_pipe`], [`is_file`], and `tty_name`
+/// separately, each of which would ask the kernel again.
+#[cfg(feature = "std")]
+pub fn inspect(stream: Stream) -> Inspection {
+    #[cfg(unix)]
+    {
+        use rustix::fs::FileType;
+
+        let is_tty = is(stream);
+        let file_kind = match unix_file_type(stream) {
+            _ if is_tty => FileKind::Tty,
+            Some(FileType::CharacterDevice) => FileKind::CharDevice,
+            Some(FileType::Fifo) => FileKind::Pipe,
+            Some(FileType::Socket) => FileKind::Socket,
+            Some(FileType::RegularFile) => FileKind::File,
+            Some(_) => FileKind::Other,
+            None => FileKind::Unknown,
+        };
+        Inspection {
+            is_tty,
+            file_kind,
+            name: inspect_name(stream, file_kind),
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        inspect_handle(stream.as_raw_handle(), is(stream))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let is_tty = is(stream);
+        Inspection {
+            is_tty,
+            file_kind: if is_tty {
+                FileKind::Tty
+            } else {
+                FileKind::Unknown
+            },
+            name: None,
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "std"))]
+fn inspect_name(stream: Stream, file_kind: FileKind) -> Option<String> {
+    match file_kind {
+        #[cfg(not(target_os = "fuchsia"))]
+        FileKind::Tty => tty_name(stream).map(|name| name.to_string_lossy().into_owned()),
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        FileKind::Pipe => proc_fd_target(stream),
+        _ => {
+            let _ = stream;
+            None
+        }
+    }
+}
+
+#[cfg(all(windows, feature = "std"))]
+pub(crate) fn inspect_handle(handle: HANDLE, is_tty: bool) -> Inspection {
+    use windows_sys::Win32::Storage::FileSystem::{FILE_TYPE_CHAR, FILE_TYPE_DISK, FILE_TYPE_PIPE};
+
+    let file_type = file_type_of(handle);
+    let file_kind = match file_type {
+        _ if is_tty => FileKind::Tty,
+        Some(FILE_TYPE_CHAR) => FileKind::CharDevice,
+        Some(FILE_TYPE_DISK) => FileKind::File,
+        Some(FILE_TYPE_PIPE) => FileKind::Pipe,
+        Some(_) => FileKind::Other,
+        None => FileKind::Unknown,
+    };
+    let name = if file_type == Some(FILE_TYPE_PIPE) {
+        unsafe { pipe_name_of(handle) }
+    } else {
+        None
+    };
+    Inspection {
+        is_tty,
+        file_kind,
+        name,
+    }
+}
+
+/// Returns `true` if standard input is a pipe and standard output is a
+/// terminal, as when a filter is run interactively on piped data, such as in
+/// `producer | cmd` or `cmd < data.fifo`.
+///
+/// This is [`is_pipe`]`(Stream::Stdin) && `[`is`]`(Stream::Stdout)`. Note
+/// that redirecting from a regular file, as in `cmd < data.txt`, doesn't
+/// count, since that isn't a pipe. Programs can use this to decide to show a
+/// progress bar while consuming their input.
+pub fn reading_from_pipe_writing_to_tty() -> bool {
+    reading_from_pipe_writing_to_tty_with(is_pipe(Stream::Stdin), is(Stream::Stdout))
+}
+
+pub(crate) fn reading_from_pipe_writing_to_tty_with(
+    stdin_is_pipe: bool,
+    stdout_is_tty: bool,
+) -> bool {
+    stdin_is_pipe && stdout_is_tty
+}
+
+/// Returns `true` if `a` and `b` are both terminals, and the same one.
+///
+/// On Unix-family platforms, this compares the devices the two streams
+/// refer to, so separate ptys are told apart. On Windows, a process has at
+/// most one console, so any two streams which are terminals are taken to be
+/// the same one.
+pub fn same_terminal(a: Stream, b: Stream) -> bool {
+    if !is(a) || !is(b) {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use rustix::fs::fstat;
+
+        match (fstat(a.as_fd()), fstat(b.as_fd())) {
+            (Ok(a), Ok(b)) => a.st_rdev == b.st_rdev,
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+/// Returns `true` if writing to stderr won't scribble over a full-screen
+/// program drawing on stdout, because stderr isn't the same terminal as
+/// stdout.
+///
+/// While a TUI owns the terminal on stdout, anything written to the same
+/// terminal through stderr, such as a log line, lands wherever the cursor
+/// happens to be and corrupts the display until the next full redraw. When
+/// this returns `false`, TUI programs should send their logs elsewhere
+/// while they're running, such as to a file or to a pane of their own, or
+/// buffer them until they exit. It's `true` if stderr is redirected, or is
+/// a different terminal, and when stdout isn't a terminal at all.
+pub fn stderr_safe_for_logging_during_tui() -> bool {
+    !same_terminal(Stream::Stdout, Stream::Stderr)
+}
+
+/// Returns `true` if standard input is known to be at end-of-file, without
+/// consuming any of it.
+///
+/// This is the case for a pipe whose writers have all exited without
+/// leaving anything unread, and for a regular file positioned at its end.
+/// Interactive tools can use this to skip prompting when no more input can
+/// arrive. There's inherent ambiguity: a terminal, or a pipe whose writer is
+/// still running, may or may not produce more input later, so this returns
+/// `false` for them, and a regular file may grow after this returns `true`.
+#[cfg(feature = "std")]
+pub fn stdin_at_eof() -> bool {
+    #[cfg(unix)]
+    {
+        use rustix::fs::{fstat, tell, FileType};
+        use rustix::io::{ioctl_fionread, poll, PollFd, PollFlags};
+
+        let fd = Stream::Stdin.as_fd();
+        let stat = match fstat(fd) {
+            Ok(stat) => stat,
+            Err(_) => return false,
+        };
+        match FileType::from_raw_mode(stat.st_mode) {
+            FileType::RegularFile => {
+                tell(fd).map_or(false, |position| position >= stat.st_size as u64)
+            }
+            FileType::Fifo | FileType::Socket => {
+                let mut fds = [PollFd::new(&fd, PollFlags::IN)];
+                retry_on_eintr(|| poll(&mut fds, 0)).map_or(false, |_| {
+                    fds[0].revents().contains(PollFlags::HUP) && ioctl_fionread(fd) == Ok(0)
+                })
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::{GetLastError, ERROR_BROKEN_PIPE};
+        use windows_sys::Win32::Storage::FileSystem::{
+            GetFileSizeEx, GetFileType, SetFilePointerEx, FILE_CURRENT, FILE_TYPE_DISK,
+            FILE_TYPE_PIPE,
+        };
+        use windows_sys::Win32::System::Pipes::PeekNamedPipe;
+
+        let handle = Stream::Stdin.as_raw_handle();
+        unsafe {
+            match GetFileType(handle) {
+                FILE_TYPE_DISK => {
+                    let mut size = 0;
+                    let mut position = 0;
+                    GetFileSizeEx(handle, &mut size) != 0
+                        && SetFilePointerEx(handle, 0, &mut position, FILE_CURRENT) != 0
+                        && position >= size
+                }
+                FILE_TYPE_PIPE => {
+                    let mut available = 0;
+                    let peeked = PeekNamedPipe(
+                        handle,
+                        std::ptr::null_mut(),
+                        0,
+                        std::ptr::null_mut(),
+                        &mut available,
+                        std::ptr::null_mut(),
+                    );
+                    peeked == 0 && GetLastError() == ERROR_BROKEN_PIPE
+                }
+                _ => false,
+            }
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        false
+    }
+}
@@ -13,6 +13,44 @@
 //! On Windows, it uses a variety of techniques to determine whether the
 //! given stream is a terminal.
 //!
+//! Some restricted Windows targets, such as some app partitions, lack the
+//! console API set those techniques depend on. For them, the `win-minimal`
+//! feature makes [`IsTerminal`] and [`is`] rely on `GetFileType` alone,
+//! which reports consoles as character devices. That's less accurate:
+//! other character devices, such as `NUL`, are reported as terminals, and
+//! MSYS ptys aren't. Other functions in this crate still use the console
+//! APIs.
+//!
+//! Everything in this crate may be called from any thread. The little
+//! process-wide state it keeps, such as the sizes remembered by
+//! `size_cached`, is kept in atomics, or behind short spin locks where
+//! it's too wide for one.
+//!
+//! # Features
+//!
+//! With no features enabled, this crate is a thin layer over system calls:
+//! it depends only on `io-lifetimes` and the platform's system-call crate,
+//! and on Unix-family platforms it's `no_std`. Everything else is opt-in:
+//!
+//!  - `std` enables the APIs which need the standard library, such as the
+//!    ones which read the environment.
+//!  - `term` adds checks based on `TERM` and the terminfo database.
+//!  - `color` adds color-support detection.
+//!  - `query` adds checks which ask the terminal itself, with escape
+//!    sequences, and the [`reply`] module, for parsing terminals' replies.
+//!  - `raw`, `cache`, `custom-fd`, `capi`, `testutil`, and
+//!    `is-terminal-compat` add the APIs they're named for.
+//!  - `anstyle` adds `auto_stream_choice`, for passing the color
+//!    detection on to the `anstream` styling crates, and depends on
+//!    `colorchoice`.
+//!  - `tokio` and `async-std` add `readable` and `readable_async_std`, for
+//!    awaiting input from those runtimes on Unix-family platforms.
+//!  - `win-minimal` changes how Windows consoles are recognized, as
+//!    described above.
+//!
+//! Each feature compiles on its own, and CI checks every one of them
+//! separately.
+//!
 //! # Example
 //!
 //! ```rust
@@ -25,18 +63,86 @@
 //!
 //! [`isatty`]: https://man7.org/linux/man-pages/man3/isatty.3.html
 
-#![cfg_attr(unix, no_std)]
+#![cfg_attr(all(unix, not(feature = "std")), no_std)]
+// io-lifetimes provides its own I/O-safety types on Rust versions which
+// predate the ones in std, so they're usable at our MSRV.
+#![allow(clippy::incompatible_msrv)]
+
+// Optional dependencies can't enable our `std` feature without the `dep:`
+// syntax, which is newer than our MSRV, so request std for them here.
+#[cfg(all(
+    unix,
+    any(feature = "tokio", feature = "async-std"),
+    not(feature = "std")
+))]
+extern crate std;
 
 #[cfg(not(target_os = "unknown"))]
 use io_lifetimes::AsFilelike;
+#[cfg(any(unix, target_os = "wasi"))]
+use io_lifetimes::BorrowedFd;
 #[cfg(windows)]
 use io_lifetimes::BorrowedHandle;
+#[cfg(unix)]
+use io_lifetimes::OwnedFd;
 #[cfg(target_os = "hermit")]
 use std::os::hermit::io::AsRawFd;
 #[cfg(windows)]
 use std::os::windows::io::AsRawHandle;
 #[cfg(windows)]
 use windows_sys::Win32::Foundation::HANDLE;
+#[cfg(windows)]
+use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+#[cfg(all(windows, feature = "custom-fd"))]
+use windows_sys::Win32::System::Console::STD_HANDLE;
+
+#[cfg(feature = "color")]
+mod color;
+mod detect;
+#[cfg(feature = "std")]
+mod env;
+#[cfg(feature = "std")]
+mod error;
+mod file;
+#[cfg(feature = "query")]
+mod query;
+#[cfg(feature = "raw")]
+mod raw;
+#[cfg(feature = "query")]
+pub mod reply;
+mod size;
+mod stream;
+#[cfg(feature = "term")]
+mod term;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(feature = "color")]
+pub use color::*;
+pub use detect::*;
+#[cfg(feature = "std")]
+pub use env::*;
+#[cfg(feature = "std")]
+pub use error::*;
+pub use file::*;
+#[cfg(feature = "query")]
+pub use query::*;
+#[cfg(feature = "raw")]
+pub use raw::*;
+pub use size::*;
+pub use stream::*;
+#[cfg(feature = "term")]
+pub use term::*;
+#[cfg(unix)]
+pub use unix::*;
+#[cfg(windows)]
+pub use windows::*;
 
 /// Extension trait to check whether something is a terminal.
 pub trait IsTerminal {
@@ -71,129 +177,427 @@ pub fn is_terminal<T: IsTerminal>(this: &T) -> bool {
     this.is_terminal()
 }
 
-#[cfg(not(target_os = "unknown"))]
-impl<Stream: AsFilelike> IsTerminal for Stream {
-    #[inline]
-    fn is_terminal(&self) -> bool {
-        #[cfg(any(unix, target_os = "wasi"))]
-        {
-            rustix::termios::isatty(self)
-        }
+/// The API of the upstream `is-terminal` crate, for projects migrating from
+/// it.
+///
+/// Upstream's [`IsTerminal`] trait is the same as this crate's, so it's
+/// re-exported unchanged. Upstream's `is_terminal` function takes its
+/// argument by value rather than by reference, so that's provided here.
+/// Projects can switch by replacing `use is_terminal::{...}` with
+/// `use is_terminal::compat::{...}`; everything is routed to this crate's
+/// detection.
+#[cfg(feature = "is-terminal-compat")]
+pub mod compat {
+    pub use crate::IsTerminal;
+
+    /// Returns `true` if `this` is a terminal.
+    ///
+    /// This is equivalent to calling `this.is_terminal()`, as upstream's
+    /// function is.
+    pub fn is_terminal<T: IsTerminal>(this: T) -> bool {
+        this.is_terminal()
+    }
+}
 
-        #[cfg(target_os = "hermit")]
-        {
-            hermit_abi::isatty(self.as_filelike().as_raw_fd())
-        }
+/// Returns `true` if the given standard stream is a terminal.
+///
+/// This checks whatever the stream's file descriptor or handle currently
+/// refers to. If the stream has been closed, this returns `false`; if its
+/// descriptor number has since been reused by another `open`, this reports on
+/// the newly opened file.
+///
+/// With the `std` feature, setting `ATTY_FORCE` to `1`, `true`, or `yes`
+/// makes this report every stream as a terminal, and setting it to `0`,
+/// `false`, or `no` makes it report none of them as one, so that users can
+/// override the detection. Other values are ignored.
+///
+/// # Example
+///
+/// ```
+/// use is_terminal::Stream;
+///
+/// if is_terminal::is(Stream::Stdout) {
+///     println!("stdout is a terminal")
+/// }
+/// ```
+#[inline]
+pub fn is(stream: Stream) -> bool {
+    stream.is_terminal()
+}
 
-        #[cfg(windows)]
-        {
-            handle_is_console(self.as_filelike())
-        }
+/// Returns whether `stream` is a terminal, as [`is`] does, but remembers
+/// what the OS reported and reuses it on later calls, until
+/// [`invalidate_tty_cache`] is called.
+///
+/// This is for programs which check the same stream very often. The cached
+/// answer is stale if the stream is redirected, which
+/// [`verify_consistency`] can detect on Unix-family platforms. Overrides,
+/// from `ATTY_FORCE` and the `testutil` fixtures, still apply, since only
+/// the OS's answer is cached; with the `std` feature, this means each call
+/// still reads `ATTY_FORCE` from the environment. `Stream::Custom` handles
+/// aren't cached.
+#[cfg(feature = "cache")]
+pub fn is_cached(stream: Stream) -> bool {
+    resolve(stream, true)
+}
+
+/// Forgets the answers remembered by [`is_cached`], so that its next calls
+/// ask the OS again.
+#[cfg(feature = "cache")]
+pub fn invalidate_tty_cache() {
+    use core::sync::atomic::Ordering;
+
+    for slot in TTY_CACHE.iter() {
+        let _ = slot.fetch_update(Ordering::Release, Ordering::Relaxed, |state| {
+            Some(match state {
+                TTY_QUERYING | TTY_QUERY_INVALIDATED => TTY_QUERY_INVALIDATED,
+                _ => TTY_UNCACHED,
+            })
+        });
     }
 }
 
-// The Windows implementation here is copied from `handle_is_console` in
-// std/src/sys/windows/io.rs in Rust at revision
-// d7b0bcb20f2f7d5f3ea3489d56ece630147e98f5.
+/// Returns `true` if the given standard stream isn't a terminal.
+///
+/// This is `!is(stream)`, for readability where the negative is what's
+/// being tested.
+#[inline]
+#[must_use]
+pub fn isnt(stream: Stream) -> bool {
+    !is(stream)
+}
 
-#[cfg(windows)]
-fn handle_is_console(handle: BorrowedHandle<'_>) -> bool {
-    use windows_sys::Win32::System::Console::{
-        GetConsoleMode, GetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
-    };
+/// An alias for [`isnt`].
+#[inline]
+#[must_use]
+pub fn isnt_tty(stream: Stream) -> bool {
+    isnt(stream)
+}
+
+/// Returns `true` if every stream in `streams` is a terminal, as [`is`]
+/// reports. This is `true` if `streams` is empty.
+#[must_use]
+pub fn is_all(streams: &[Stream]) -> bool {
+    streams.iter().all(|&stream| is(stream))
+}
+
+/// Returns `true` if any stream in `streams` is a terminal, as [`is`]
+/// reports. This is `false` if `streams` is empty.
+#[must_use]
+pub fn is_any(streams: &[Stream]) -> bool {
+    streams.iter().any(|&stream| is(stream))
+}
 
-    let handle = handle.as_raw_handle();
+/// Returns `true` if both standard output and standard error are terminals.
+///
+/// This is the check for output which must not end up in a file or pipe,
+/// such as a full-screen display drawn on one stream while the other is
+/// written to. Use [`any_output_is_tty`] to ask whether the user is watching
+/// either stream, as for deciding whether to show progress at all.
+#[must_use]
+pub fn output_is_tty() -> bool {
+    is_all(&[Stream::Stdout, Stream::Stderr])
+}
+
+/// Returns `true` if standard output or standard error is a terminal, so
+/// that the user is watching at least some of the program's output, even if
+/// the other stream is redirected, as in `cmd > out.txt` or `cmd 2> log.txt`.
+///
+/// See [`output_is_tty`], which requires both.
+#[must_use]
+pub fn any_output_is_tty() -> bool {
+    is_any(&[Stream::Stdout, Stream::Stderr])
+}
 
-    unsafe {
-        // A null handle means the process has no console.
-        if handle.is_null() {
-            return false;
+/// Whether `stream` is a terminal, as [`is`] reports it, and how sure of
+/// that this crate is, as returned by [`is_confident`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Confidence {
+    /// The answer comes from the operating system, so it's reliable.
+    Certain(bool),
+    /// The answer is a guess, made by a heuristic.
+    Uncertain(bool),
+}
+
+impl Confidence {
+    /// Returns the answer, regardless of how certain it is.
+    pub fn value(self) -> bool {
+        match self {
+            Confidence::Certain(value) | Confidence::Uncertain(value) => value,
         }
+    }
+
+    /// Returns `true` if the answer is [`Certain`](Confidence::Certain).
+    pub fn is_certain(self) -> bool {
+        matches!(self, Confidence::Certain(_))
+    }
+}
 
-        let mut out = 0;
-        if GetConsoleMode(handle as HANDLE, &mut out) != 0 {
-            // False positives aren't possible. If we got a console then we definitely have a console.
-            return true;
+/// Returns whether `stream` is a terminal, as [`is`] does, along with
+/// whether that answer is reliable.
+///
+/// On Unix-family platforms, `isatty` is authoritative, so the answer is
+/// always `Certain`. On Windows, a handle which isn't a console is only
+/// known not to be a terminal if another standard handle is a console;
+/// otherwise, it may be an MSYS pty, which is recognized by the name of its
+/// pipe, and the answer is `Uncertain`. With the `win-minimal` feature,
+/// character devices and pipes are `Uncertain`. Careful callers can treat
+/// an uncertain answer conservatively, such as by not prompting.
+pub fn is_confident(stream: Stream) -> Confidence {
+    #[cfg(windows)]
+    {
+        let handle = stream.as_raw_handle();
+        if handle == INVALID_HANDLE_VALUE {
+            return Confidence::Certain(false);
         }
+        handle_console_confidence(unsafe { BorrowedHandle::borrow_raw(handle as _) })
+    }
+
+    #[cfg(not(windows))]
+    {
+        Confidence::Certain(is(stream))
+    }
+}
+
+/// Returns whether `writer` is a terminal, if it's one of the handle types
+/// this crate knows, or `None` otherwise.
+///
+/// This is for code which holds its output as a type-erased value, such as a
+/// logger configured with an arbitrary writer, and can't say which stream it
+/// writes to. The types recognized are `std::io::Stdout`, `std::io::Stderr`,
+/// `std::io::StdoutLock<'static>`, `std::io::StderrLock<'static>`,
+/// `std::fs::File`, and `std::process::ChildStdin`. Anything else, such as a
+/// `Vec<u8>` or a wrapper like `BufWriter<Stdout>`, gives `None`.
+#[cfg(feature = "std")]
+pub fn is_terminal_writer(writer: &dyn std::any::Any) -> Option<bool> {
+    fn check<T: IsTerminal + 'static>(writer: &dyn std::any::Any) -> Option<bool> {
+        writer.downcast_ref::<T>().map(T::is_terminal)
+    }
 
-        // At this point, we *could* have a false negative. We can determine that this is a true
-        // negative if we can detect the presence of a console on any of the standard I/O streams. If
-        // another stream has a console, then we know we're in a Windows console and can therefore
-        // trust the negative.
-        for std_handle in [STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
-            let std_handle = GetStdHandle(std_handle);
-            if std_handle != 0
-                && std_handle != handle as HANDLE
-                && GetConsoleMode(std_handle, &mut out) != 0
-            {
-                return false;
-            }
+    check::<std::io::Stdout>(writer)
+        .or_else(|| check::<std::io::Stderr>(writer))
+        .or_else(|| check::<std::io::StdoutLock<'static>>(writer))
+        .or_else(|| check::<std::io::StderrLock<'static>>(writer))
+        .or_else(|| check::<std::fs::File>(writer))
+        .or_else(|| check::<std::process::ChildStdin>(writer))
+}
+
+/// Returns `true` if the process isn't being used interactively, that is,
+/// if standard input or standard output isn't a terminal.
+///
+/// Prefer checking capabilities positively, such as with [`is`], where a
+/// specific stream is what matters.
+#[must_use]
+pub fn not_interactive() -> bool {
+    isnt(Stream::Stdin) || isnt(Stream::Stdout)
+}
+
+/// Returns `true` if the C library would line-buffer `stream` by default.
+///
+/// glibc, musl, and other Unix-family C libraries line-buffer standard input
+/// and output when they're terminals and fully buffer them otherwise, and
+/// never buffer standard error. On Windows, the CRT treats line buffering as
+/// full buffering, and leaves console streams unbuffered, so this is always
+/// `false` there.
+///
+/// This is useful for programs which shell out to C programs and want to
+/// predict when their output will be flushed.
+pub fn is_line_buffered(stream: Stream) -> bool {
+    stream != Stream::Stderr && cfg!(not(windows)) && is(stream)
+}
+
+/// Returns a buffered writer for `stream`, which must be stdout or stderr,
+/// which flushes at the end of every line if `stream` is a terminal, so
+/// that someone watching sees each line as soon as it's complete, and
+/// otherwise buffers 64 KiB at a time, for throughput when writing to a
+/// file or pipe.
+///
+/// Unlike [`is_line_buffered`], which reports what the C library would do,
+/// this applies the policy to both streams, including stderr. Like any
+/// buffered writer, anything still buffered is flushed when the writer is
+/// dropped, and errors from that final flush are ignored, so call `flush`
+/// to see them.
+///
+/// # Panics
+///
+/// Panics if `stream` is `Stream::Stdin`, which can't be written to.
+#[cfg(feature = "std")]
+pub fn line_buffered_writer(stream: Stream) -> Box<dyn std::io::Write> {
+    fn buffered<W: std::io::Write + 'static>(inner: W, tty: bool) -> Box<dyn std::io::Write> {
+        if tty {
+            Box::new(std::io::LineWriter::new(inner))
+        } else {
+            Box::new(std::io::BufWriter::with_capacity(64 * 1024, inner))
         }
+    }
 
-        // Otherwise, we fall back to an msys hack to see if we can detect the presence of a pty.
-        msys_tty_on(handle as HANDLE)
+    let tty = is(stream);
+    match stream {
+        Stream::Stdin => panic!("line_buffered_writer called on stdin, which isn't writable"),
+        Stream::Stdout => buffered(std::io::stdout(), tty),
+        Stream::Stderr => buffered(std::io::stderr(), tty),
+        #[cfg(all(windows, feature = "custom-fd"))]
+        Stream::Custom(_) => panic!("line_buffered_writer called on a custom handle"),
     }
 }
 
-/// Returns true if there is an MSYS tty on the given handle.
+/// Returns `true` if an interactive prompt should be written to stderr
+/// rather than stdout: standard input is a terminal, so there's someone to
+/// answer it, but standard output isn't, as in `cmd > out.txt` or
+/// `cmd | consumer`, and standard error is a terminal, so they'll see it.
 ///
-/// This incoproates d7b0bcb20f2f7d5f3ea3489d56ece630147e98f5
-#[cfg(windows)]
-unsafe fn msys_tty_on(handle: HANDLE) -> bool {
-    use std::ffi::c_void;
-    use windows_sys::Win32::{
-        Foundation::MAX_PATH,
-        Storage::FileSystem::{
-            FileNameInfo, GetFileInformationByHandleEx, GetFileType, FILE_TYPE_PIPE,
-        },
+/// Writing the prompt to stdout in that case would mix it into the output.
+/// When stdout is a terminal, prompting there is fine, and when neither
+/// stdout nor stderr is, nobody would see the prompt, so this is `false`.
+/// This is the policy of shell commands such as `read -p`.
+pub fn prompt_should_go_to_stderr() -> bool {
+    prompt_should_go_to_stderr_with(is(Stream::Stdin), is(Stream::Stdout), is(Stream::Stderr))
+}
+
+fn prompt_should_go_to_stderr_with(
+    stdin_is_tty: bool,
+    stdout_is_tty: bool,
+    stderr_is_tty: bool,
+) -> bool {
+    stdin_is_tty && !stdout_is_tty && stderr_is_tty
+}
+
+/// Returns `1` if the standard stream numbered `stream`, `0` for stdin, `1`
+/// for stdout, or `2` for stderr, is a terminal, `0` if it isn't, or `-1` if
+/// `stream` is none of those.
+///
+/// This is for use from other languages; `include/is_terminal.h` declares
+/// it for C.
+#[cfg(feature = "capi")]
+#[no_mangle]
+pub extern "C" fn atty_is(stream: std::os::raw::c_int) -> std::os::raw::c_int {
+    let stream = match stream {
+        0 => Stream::Stdin,
+        1 => Stream::Stdout,
+        2 => Stream::Stderr,
+        _ => return -1,
     };
+    is(stream).into()
+}
 
-    // Early return if the handle is not a pipe.
-    if GetFileType(handle) != FILE_TYPE_PIPE {
-        return false;
+/// Returns `true` if writing `\n` to `stream` wouldn't return the cursor to
+/// the start of the line, so that programs writing directly to it need to
+/// write `\r\n` to avoid output staircasing.
+///
+/// This is the case for Windows consoles whose mode lacks
+/// `ENABLE_PROCESSED_OUTPUT`, or has `DISABLE_NEWLINE_AUTO_RETURN`, which
+/// programs set along with `ENABLE_VIRTUAL_TERMINAL_PROCESSING` to get
+/// Unix-style line feeds. Unix-family terminals in raw mode behave this way
+/// too, but that's the program's own doing, so this always returns `false`
+/// elsewhere, as well as for MSYS ptys and streams which aren't consoles.
+pub fn needs_crlf(stream: Stream) -> bool {
+    #[cfg(windows)]
+    {
+        needs_crlf_with(console_mode(stream.as_raw_handle()))
     }
 
-    /// Mirrors windows_sys::Win32::Storage::FileSystem::FILE_NAME_INFO, giving
-    /// it a fixed length that we can stack allocate
-    #[repr(C)]
-    #[allow(non_snake_case)]
-    struct FILE_NAME_INFO {
-        FileNameLength: u32,
-        FileName: [u16; MAX_PATH as usize],
+    #[cfg(not(windows))]
+    {
+        let _ = stream;
+        false
     }
-    let mut name_info = FILE_NAME_INFO {
-        FileNameLength: 0,
-        FileName: [0; MAX_PATH as usize],
-    };
-    // Safety: buffer length is fixed.
-    let res = GetFileInformationByHandleEx(
-        handle,
-        FileNameInfo,
-        &mut name_info as *mut _ as *mut c_void,
-        std::mem::size_of::<FILE_NAME_INFO>() as u32,
-    );
-    if res == 0 {
-        return false;
+}
+
+/// Returns whether `ENABLE_PROCESSED_OUTPUT` is set in the console mode of
+/// `stream`, which controls whether the console interprets BEL, backspace,
+/// carriage return, and line feed rather than printing them as glyphs.
+///
+/// Programs doing their own cursor control sometimes turn this off, and can
+/// use this to save the current setting so they can restore it. This returns
+/// `None` if `stream` isn't a Windows console, including on other platforms.
+pub fn processed_output_enabled(stream: Stream) -> Option<bool> {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::ENABLE_PROCESSED_OUTPUT;
+
+        console_mode(stream.as_raw_handle()).map(|mode| mode & ENABLE_PROCESSED_OUTPUT != 0)
     }
 
-    // Use `get` because `FileNameLength` can be out of range.
-    let s = match name_info
-        .FileName
-        .get(..name_info.FileNameLength as usize / 2)
+    #[cfg(not(windows))]
     {
-        None => return false,
-        Some(s) => s,
-    };
-    let name = String::from_utf16_lossy(s);
-    // Get the file name only.
-    let name = name.rsplit('\\').next().unwrap_or(&name);
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(not(target_os = "unknown"))]
+impl<Stream: AsFilelike> IsTerminal for Stream {
+    #[inline]
+    fn is_terminal(&self) -> bool {
+        #[cfg(any(unix, target_os = "wasi"))]
+        {
+            rustix::termios::isatty(self)
+        }
+
+        #[cfg(target_os = "hermit")]
+        {
+            hermit_abi::isatty(self.as_filelike().as_raw_fd())
+        }
+
+        #[cfg(windows)]
+        {
+            handle_is_console(self.as_filelike())
+        }
+    }
+}
+
+/// The kind of pty emulation a pipe is used for, as identified by
+/// [`classify_pipe_name`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum PtyFlavor {
+    /// An MSYS2 pty, as used by Git Bash and by MSYS2's mintty.
+    Msys,
+    /// A Cygwin pty, as used by Cygwin's mintty.
+    Cygwin,
+}
+
+/// Returns the kind of pty emulation the Windows pipe named `name` is used
+/// for, or `None` if it isn't an MSYS or Cygwin pty pipe.
+///
+/// `name` is a pipe name as returned by `GetFileInformationByHandleEx`, such
+/// as `\msys-1888ae32e00d56aa-pty0-to-master`, decoded from UTF-16. The
+/// name may also be given with a `\\?\pipe\`, `\\.\pipe\`, or
+/// `\Device\NamedPipe\` prefix. These are the rules [`is`] uses to
+/// recognize MSYS ptys on Windows, but this makes no system calls, so it's
+/// available on all platforms.
+pub fn classify_pipe_name(name: &str) -> Option<PtyFlavor> {
+    // Strip the prefixes the pipe's name may be reported with, leaving just
+    // the name within the pipe namespace, which must not have any further
+    // path components.
+    let mut name = name;
+    for prefix in &[
+        "\\\\?\\",
+        "\\\\.\\",
+        "\\Device\\NamedPipe\\",
+        "pipe\\",
+        "\\",
+    ] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            name = rest;
+        }
+    }
+    if name.contains('\\') {
+        return None;
+    }
     // This checks whether 'pty' exists in the file name, which indicates that
     // a pseudo-terminal is attached. To mitigate against false positives
     // (e.g., an actual file name that contains 'pty'), we also require that
     // the file name begins with either the strings 'msys-' or 'cygwin-'.)
-    let is_msys = name.starts_with("msys-") || name.starts_with("cygwin-");
-    let is_pty = name.contains("-pty");
-    is_msys && is_pty
+    if !name.contains("-pty") {
+        None
+    } else if name.starts_with("msys-") {
+        Some(PtyFlavor::Msys)
+    } else if name.starts_with("cygwin-") {
+        Some(PtyFlavor::Cygwin)
+    } else {
+        None
+    }
 }
 
 #[cfg(target_os = "unknown")]
@@ -275,112 +679,3 @@ impl IsTerminal for std::process::ChildStderr {
         false
     }
 }
-
-#[cfg(test)]
-mod tests {
-    #[cfg(not(target_os = "unknown"))]
-    use super::IsTerminal;
-
-    #[test]
-    #[cfg(windows)]
-    fn stdin() {
-        assert_eq!(
-            atty::is(atty::Stream::Stdin),
-            std::io::stdin().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(windows)]
-    fn stdout() {
-        assert_eq!(
-            atty::is(atty::Stream::Stdout),
-            std::io::stdout().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(windows)]
-    fn stderr() {
-        assert_eq!(
-            atty::is(atty::Stream::Stderr),
-            std::io::stderr().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stdin() {
-        assert_eq!(
-            atty::is(atty::Stream::Stdin),
-            rustix::io::stdin().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stdout() {
-        assert_eq!(
-            atty::is(atty::Stream::Stdout),
-            rustix::io::stdout().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stderr() {
-        assert_eq!(
-            atty::is(atty::Stream::Stderr),
-            rustix::io::stderr().is_terminal()
-        )
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stdin_vs_libc() {
-        unsafe {
-            assert_eq!(
-                libc::isatty(libc::STDIN_FILENO) != 0,
-                rustix::io::stdin().is_terminal()
-            )
-        }
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stdout_vs_libc() {
-        unsafe {
-            assert_eq!(
-                libc::isatty(libc::STDOUT_FILENO) != 0,
-                rustix::io::stdout().is_terminal()
-            )
-        }
-    }
-
-    #[test]
-    #[cfg(any(unix, target_os = "wasi"))]
-    fn stderr_vs_libc() {
-        unsafe {
-            assert_eq!(
-                libc::isatty(libc::STDERR_FILENO) != 0,
-                rustix::io::stderr().is_terminal()
-            )
-        }
-    }
-
-    // Verify that the msys_tty_on function works with long path.
-    #[test]
-    #[cfg(windows)]
-    fn msys_tty_on_path_length() {
-        use std::{fs::File, os::windows::io::AsRawHandle};
-        use windows_sys::Win32::Foundation::MAX_PATH;
-
-        let dir = tempfile::tempdir().expect("Unable to create temporary directory");
-        let file_path = dir.path().join("ten_chars_".repeat(25));
-        // Ensure that the path is longer than MAX_PATH.
-        assert!(file_path.to_string_lossy().len() > MAX_PATH as usize);
-        let file = File::create(file_path).expect("Unable to create file");
-
-        assert!(!unsafe { crate::msys_tty_on(file.as_raw_handle() as isize) });
-    }
-}
@@ -0,0 +1,1269 @@
+//! Checks which query the terminal with escape sequences and wait for its
+//! reply.
+//!
+//! A terminal which doesn't understand a query usually ignores it rather
+//! than saying so, so most queries here are followed by a Primary Device
+//! Attributes request, `\x1b[c`, which every terminal answers. Terminals
+//! answer requests in the order they're sent, so once the answer to that
+//! one arrives, any answer to the query has arrived before it, and a query
+//! which went unanswered is given up on then, rather than after the
+//! timeout. Each query still gives up if the terminal doesn't reply within
+//! a short timeout, and on Windows, where there's nothing to query, they
+//! all return `None` or `false`.
+
+#[cfg(unix)]
+use crate::reply::{Reply, ReplyParser};
+use crate::*;
+#[cfg(unix)]
+use core::convert::TryFrom;
+
+/// Returns `true` if the terminal on `stream` can be sent a query, such as
+/// a Device Status Report, and its reply read without risk of hanging.
+///
+/// Every query in this crate checks this first, and gives up, returning
+/// `None` or `false`, if it's `false`. That's the case unless `stream` and
+/// standard input are the same terminal, so that the reply arrives where
+/// the user's input does, and the process is in the terminal's foreground
+/// process group, since reading from the terminal in the background stops
+/// the process with `SIGTTIN`. A terminal which isn't the process's
+/// controlling terminal has no foreground process group of its own, and
+/// the latter doesn't apply. On Windows, this is always `false`, since
+/// there's nothing to query.
+pub fn query_is_safe(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        same_terminal(Stream::Stdin, stream) && in_foreground(stream)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+/// How long to wait for a terminal to reply to a query before deciding that
+/// it doesn't understand it.
+///
+/// Waiting continues when a signal handler interrupts it, as for `SIGWINCH`
+/// when the terminal is resized, until this much time has passed in all.
+#[cfg(unix)]
+const QUERY_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Writes `request` to `output` and reads the terminal's reply from `input`
+/// until `complete` accepts it, returning `None` if it doesn't arrive within
+/// `timeout`.
+///
+/// Echo and line buffering are turned off on `input` while waiting, so that
+/// the reply isn't displayed and can be read as soon as it arrives, and are
+/// restored afterwards. Any input the user types while waiting is consumed
+/// along with the reply.
+#[cfg(unix)]
+fn query_raw(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    request: &[u8],
+    timeout: std::time::Duration,
+    complete: &dyn Fn(&[u8]) -> bool,
+) -> Option<Vec<u8>> {
+    try_query_raw(input, output, request, timeout, complete).ok()
+}
+
+/// Sends `request` followed by a Primary Device Attributes request, as
+/// described in the module documentation, and returns everything the
+/// terminal replied up to and including its answer to the latter.
+#[cfg(unix)]
+fn query_with_da_sentinel(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    request: &[u8],
+    timeout: std::time::Duration,
+) -> Option<Vec<u8>> {
+    let mut request = request.to_vec();
+    request.extend_from_slice(b"\x1b[c");
+    query_raw(input, output, &request, timeout, &|reply| {
+        parse_device_attributes(reply).is_some()
+    })
+}
+
+/// Like `query_raw`, but says why it failed.
+///
+/// If `input` is the descriptor of the calling thread's [`QuerySession`],
+/// its mode is already set, and is left alone.
+#[cfg(unix)]
+fn try_query_raw(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    request: &[u8],
+    timeout: std::time::Duration,
+    complete: &dyn Fn(&[u8]) -> bool,
+) -> Result<Vec<u8>, TerminalError> {
+    use rustix::termios::{tcsetattr, OptionalActions};
+    use std::os::unix::io::AsRawFd;
+
+    if SESSION_FD.with(|fd| fd.get() == Some(input.as_raw_fd())) {
+        return read_reply(input, output, request, timeout, complete);
+    }
+
+    let saved = enter_query_mode(input)?;
+    let reply = read_reply(input, output, request, timeout, complete);
+    let _ = retry_on_eintr(|| tcsetattr(input, OptionalActions::Now, &saved));
+    reply
+}
+
+/// Turns off echo and line buffering on `input`, returning its previous
+/// settings.
+#[cfg(unix)]
+fn enter_query_mode(input: BorrowedFd<'_>) -> rustix::io::Result<rustix::termios::Termios> {
+    use rustix::termios::{tcgetattr, tcsetattr, OptionalActions, ECHO, ICANON, VMIN, VTIME};
+
+    let saved = retry_on_eintr(|| tcgetattr(input))?;
+    let mut raw = saved;
+    raw.c_lflag &= !(ICANON | ECHO);
+    raw.c_cc[VMIN] = 1;
+    raw.c_cc[VTIME] = 0;
+    retry_on_eintr(|| tcsetattr(input, OptionalActions::Now, &raw))?;
+    Ok(saved)
+}
+
+#[cfg(unix)]
+std::thread_local! {
+    /// The descriptor of the calling thread's current [`QuerySession`], if
+    /// it has one.
+    static SESSION_FD: core::cell::Cell<Option<std::os::unix::io::RawFd>> =
+        core::cell::Cell::new(None);
+}
+
+/// Runs several queries on the terminal on `stream`, switching its mode
+/// only once for all of them, and returns what `f` returns, or `None` if
+/// [`query_is_safe`] says `stream` can't be queried or its mode can't be
+/// switched.
+///
+/// Each query function, such as [`device_attributes`], turns off echo and
+/// line buffering while it waits for a reply, and restores them afterwards.
+/// Running several in a row that way makes redundant `tcsetattr` calls, and
+/// the user's typing may be echoed between them. The methods of the
+/// [`QuerySession`] `f` is given run the same queries, but the mode is
+/// switched once before `f` is called, and restored once after it returns
+/// or panics.
+///
+/// Neither these queries nor the individual functions are cut short by
+/// signals, such as `SIGWINCH` when the terminal is resized: system calls
+/// interrupted by a signal handler are retried, and a query keeps waiting
+/// for its reply until its timeout has passed in all.
+///
+/// # Example
+///
+/// ```no_run
+/// use is_terminal::{with_query_session, Stream};
+///
+/// let (attributes, version) = with_query_session(Stream::Stdout, |session| {
+///     (session.device_attributes(), session.terminal_version())
+/// })
+/// .unwrap_or((None, None));
+/// ```
+#[cfg(unix)]
+pub fn with_query_session<R>(
+    stream: Stream,
+    f: impl FnOnce(&mut QuerySession<'_>) -> R,
+) -> Option<R> {
+    if !query_is_safe(stream) {
+        return None;
+    }
+    with_query_session_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT, f)
+}
+
+#[cfg(unix)]
+pub(crate) fn with_query_session_on<'fd, R>(
+    input: BorrowedFd<'fd>,
+    output: BorrowedFd<'fd>,
+    timeout: std::time::Duration,
+    f: impl FnOnce(&mut QuerySession<'fd>) -> R,
+) -> Option<R> {
+    use std::os::unix::io::{AsRawFd, RawFd};
+
+    struct Restore<'fd> {
+        input: BorrowedFd<'fd>,
+        saved: rustix::termios::Termios,
+        outer: Option<RawFd>,
+    }
+    impl Drop for Restore<'_> {
+        fn drop(&mut self) {
+            use rustix::termios::{tcsetattr, OptionalActions};
+
+            SESSION_FD.with(|fd| fd.set(self.outer));
+            let _ = retry_on_eintr(|| tcsetattr(self.input, OptionalActions::Now, &self.saved));
+        }
+    }
+
+    let saved = enter_query_mode(input).ok()?;
+    let outer = SESSION_FD.with(|session| session.replace(Some(input.as_raw_fd())));
+    let _restore = Restore {
+        input,
+        saved,
+        outer,
+    };
+    Some(f(&mut QuerySession {
+        input,
+        output,
+        timeout,
+    }))
+}
+
+/// A terminal whose mode has been switched for queries, given to the
+/// closure passed to [`with_query_session`].
+///
+/// Each method runs the query of the function of the same name, such as
+/// [`device_attributes`] for [`QuerySession::device_attributes`], and
+/// returns what that function would.
+#[cfg(unix)]
+pub struct QuerySession<'fd> {
+    input: BorrowedFd<'fd>,
+    output: BorrowedFd<'fd>,
+    timeout: std::time::Duration,
+}
+
+#[cfg(unix)]
+impl QuerySession<'_> {
+    /// Queries the terminal's Primary Device Attributes.
+    pub fn device_attributes(&mut self) -> Option<Vec<u16>> {
+        device_attributes_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries whether the terminal supports sixel graphics.
+    pub fn supports_sixel(&mut self) -> bool {
+        supports_sixel_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the terminal's name and version.
+    pub fn terminal_version(&mut self) -> Option<String> {
+        terminal_version_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the terminal's Secondary Device Attributes.
+    pub fn secondary_device_attributes(&mut self) -> Option<(u16, u16, u16)> {
+        secondary_device_attributes_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the kitty keyboard protocol flags currently enabled.
+    pub fn kitty_keyboard_flags(&mut self) -> Option<u8> {
+        kitty_keyboard_flags_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries xterm's modifyOtherKeys level.
+    pub fn modify_other_keys_level(&mut self) -> Option<u8> {
+        modify_other_keys_level_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the position of the terminal's window on the screen.
+    pub fn window_position(&mut self) -> Option<(i16, i16)> {
+        window_position_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the size of the terminal's text area in pixels.
+    pub fn text_area_pixels(&mut self) -> Option<(u16, u16)> {
+        let cells = rustix::termios::tcgetwinsize(self.output)
+            .ok()
+            .map(|winsize| (winsize.ws_col, winsize.ws_row));
+        text_area_pixels_on(self.input, self.output, cells, self.timeout)
+    }
+
+    /// Queries how many sixel color registers the terminal has.
+    pub fn sixel_color_registers(&mut self) -> Option<u16> {
+        sixel_color_registers_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the state of DEC private mode `mode`.
+    pub fn query_dec_mode(&mut self, mode: u16) -> Option<DecModeState> {
+        private_mode(self.input, self.output, mode, self.timeout)
+    }
+
+    /// Queries the terminal's default foreground color.
+    pub fn foreground_color(&mut self) -> Option<(u8, u8, u8)> {
+        foreground_color_on(self.input, self.output, self.timeout)
+    }
+
+    /// Queries the color of entry `index` of the terminal's palette.
+    pub fn palette_color(&mut self, index: u8) -> Option<(u8, u8, u8)> {
+        palette_color_on(self.input, self.output, index, self.timeout)
+    }
+
+    /// Queries the position of the cursor.
+    pub fn cursor_position(&mut self) -> Option<(u16, u16)> {
+        cursor_position_on(self.input, self.output, self.timeout).ok()
+    }
+}
+
+#[cfg(unix)]
+fn read_reply(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    mut request: &[u8],
+    timeout: std::time::Duration,
+    complete: &dyn Fn(&[u8]) -> bool,
+) -> Result<Vec<u8>, TerminalError> {
+    use rustix::io::{poll, read, write, Errno, PollFd, PollFlags};
+
+    while !request.is_empty() {
+        let n = retry_on_eintr(|| write(output, request))?;
+        request = &request[n..];
+    }
+
+    let deadline = std::time::Instant::now() + timeout;
+    let mut reply = Vec::new();
+    while !complete(&reply) {
+        let remaining = deadline
+            .checked_duration_since(std::time::Instant::now())
+            .ok_or(TerminalError::Timeout)?;
+        let millis = remaining.as_millis().min(i32::MAX as u128) as i32;
+        // Not `retry_on_eintr`, since the timeout has to shrink as the
+        // deadline approaches.
+        match poll(&mut [PollFd::new(&input, PollFlags::IN)], millis) {
+            Ok(0) => return Err(TerminalError::Timeout),
+            Ok(_) => {}
+            Err(Errno::INTR) => continue,
+            Err(errno) => return Err(errno.into()),
+        }
+        let mut buf = [0; 64];
+        match retry_on_eintr(|| read(input, &mut buf)) {
+            Ok(0) => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => reply.extend_from_slice(&buf[..n]),
+            Err(Errno::AGAIN) => {}
+            Err(errno) => return Err(errno.into()),
+        }
+    }
+    Ok(reply)
+}
+
+/// Returns `true` if the terminal on `stream` supports sixel graphics.
+///
+/// This sends a Primary Device Attributes request (`\x1b[c`) and checks
+/// whether the terminal's reply lists attribute `4`, sixel graphics. If the
+/// terminal doesn't reply within a short timeout, this returns `false`. On
+/// Windows, this always returns `false`.
+pub fn supports_sixel(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        query_is_safe(stream)
+            && supports_sixel_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn supports_sixel_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> bool {
+    device_attributes_on(input, output, timeout).map_or(false, |attributes| attributes.contains(&4))
+}
+
+/// Returns `true` if the terminal on `stream` supports the kitty graphics
+/// protocol.
+///
+/// kitty itself, recognized by `KITTY_WINDOW_ID` or `TERM=xterm-kitty`, is
+/// assumed to. Otherwise, this sends a graphics protocol query for a 1x1
+/// image and checks whether the terminal acknowledges it. On Windows, this
+/// always returns `false`.
+pub fn supports_kitty_graphics(stream: Stream) -> bool {
+    #[cfg(unix)]
+    {
+        query_is_safe(stream)
+            && supports_kitty_graphics_with(
+                &SystemEnv,
+                Stream::Stdin.as_fd(),
+                stream.as_fd(),
+                QUERY_TIMEOUT,
+            )
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        false
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn supports_kitty_graphics_with(
+    env: &dyn EnvSource,
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> bool {
+    if env.get("KITTY_WINDOW_ID").is_some() || env.get("TERM").as_deref() == Some("xterm-kitty") {
+        return true;
+    }
+
+    // The acknowledgment is `\x1b_Gi=31;OK\x1b\\`, or an error message in
+    // place of `OK` if the terminal understood the query but couldn't show
+    // the image; either way, it speaks the protocol.
+    query_with_da_sentinel(
+        input,
+        output,
+        b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\",
+        timeout,
+    )
+    .map_or(false, |reply| {
+        replies(&reply)
+            .any(|reply| matches!(reply, Reply::Apc(payload) if payload.starts_with(b"Gi=31")))
+    })
+}
+
+/// Returns whether tmux's `allow-passthrough` option is on for the current
+/// pane, so that sequences wrapped in tmux's passthrough escape reach the
+/// terminal tmux is running in, or `None` if the process isn't running in
+/// tmux or [`query_is_safe`] says stdout can't be queried.
+///
+/// Passthrough is needed for OSC 52 clipboard access and image protocols to
+/// work from inside tmux. Being in tmux is decided by `TMUX` being set. This
+/// then sends a Primary Device Attributes request wrapped in the passthrough
+/// escape, `\x1bPtmux;...\x1b\\`, and checks whether a reply arrives within
+/// a short timeout: tmux discards the wrapped request when passthrough is
+/// off, and otherwise the outer terminal answers it. On Windows, this always
+/// returns `None`.
+pub fn tmux_passthrough_enabled() -> Option<bool> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(Stream::Stdout) {
+            return None;
+        }
+        tmux_passthrough_enabled_with(
+            &SystemEnv,
+            Stream::Stdin.as_fd(),
+            Stream::Stdout.as_fd(),
+            QUERY_TIMEOUT,
+        )
+    }
+
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn tmux_passthrough_enabled_with(
+    env: &dyn EnvSource,
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<bool> {
+    env.get("TMUX")?;
+
+    // Escapes inside the passthrough sequence are doubled.
+    let reply = query_raw(
+        input,
+        output,
+        b"\x1bPtmux;\x1b\x1b[c\x1b\\",
+        timeout,
+        &|reply| parse_device_attributes(reply).is_some(),
+    );
+    Some(reply.is_some())
+}
+
+/// Returns whether the tmux pane the process is running in is on screen,
+/// or `None` if the process isn't running in tmux, `stream` isn't a
+/// terminal, or tmux can't be asked.
+///
+/// Long-running programs can pause expensive rendering while nobody can
+/// see it. Only tmux is supported: being in tmux is decided by `TMUX` and
+/// `TMUX_PANE` being set, and tmux is then asked about the pane with
+/// `tmux display-message`. The pane is on screen if its window is the
+/// active window of a session which a client is attached to, unless
+/// another pane of the window is zoomed. This says nothing about whether
+/// the terminal tmux runs in is itself visible.
+pub fn pane_is_visible(stream: Stream) -> Option<bool> {
+    if !is(stream) {
+        return None;
+    }
+    pane_is_visible_with(&SystemEnv, &|args| {
+        let output = std::process::Command::new("tmux")
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    })
+}
+
+pub(crate) fn pane_is_visible_with(
+    env: &dyn EnvSource,
+    tmux: &dyn Fn(&[&str]) -> Option<String>,
+) -> Option<bool> {
+    env.get("TMUX")?;
+    let pane = env.get("TMUX_PANE")?;
+
+    let reply = tmux(&[
+        "display-message",
+        "-p",
+        "-t",
+        &pane,
+        "#{window_active} #{session_attached} #{window_zoomed_flag} #{pane_active}",
+    ])?;
+    let mut fields = reply
+        .split_whitespace()
+        .map(|field| field.parse::<u32>().ok());
+    let window_active = fields.next()?? != 0;
+    let session_attached = fields.next()?? != 0;
+    let window_zoomed = fields.next()?? != 0;
+    let pane_active = fields.next()?? != 0;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(window_active && session_attached && (pane_active || !window_zoomed))
+}
+
+/// Returns whether the terminal on `stream` wraps lines which are too long
+/// to fit (DECAWM, private mode 7), or `None` if it can't be determined.
+///
+/// This is [`query_dec_mode`] for mode 7. On Windows, this always returns
+/// `None`.
+pub fn autowrap_enabled(stream: Stream) -> Option<bool> {
+    query_dec_mode(stream, 7)?.is_set()
+}
+
+/// Returns whether the terminal on `stream` is showing its alternate screen
+/// buffer (private mode 1049), as full-screen programs do while they run, or
+/// `None` if it can't be determined.
+///
+/// Programs which run after a full-screen program, or restore the terminal
+/// after one crashes, can use this to tell whether it left the alternate
+/// screen active. This is [`query_dec_mode`] for mode 1049. On Windows,
+/// this always returns `None`.
+pub fn in_alternate_screen(stream: Stream) -> Option<bool> {
+    query_dec_mode(stream, 1049)?.is_set()
+}
+
+/// The state of a DEC private mode, as reported by [`query_dec_mode`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum DecModeState {
+    /// The mode is set.
+    Set,
+    /// The mode is reset.
+    Reset,
+    /// The mode is set, and can't be changed.
+    PermanentlySet,
+    /// The mode is reset, and can't be changed.
+    PermanentlyReset,
+    /// The terminal doesn't recognize the mode.
+    NotRecognized,
+}
+
+impl DecModeState {
+    /// Returns whether the mode is set, or `None` if it isn't recognized.
+    pub fn is_set(self) -> Option<bool> {
+        match self {
+            DecModeState::Set | DecModeState::PermanentlySet => Some(true),
+            DecModeState::Reset | DecModeState::PermanentlyReset => Some(false),
+            DecModeState::NotRecognized => None,
+        }
+    }
+
+    /// Converts a DECRPM status value.
+    #[cfg(unix)]
+    fn from_status(status: u32) -> Option<Self> {
+        match status {
+            0 => Some(DecModeState::NotRecognized),
+            1 => Some(DecModeState::Set),
+            2 => Some(DecModeState::Reset),
+            3 => Some(DecModeState::PermanentlySet),
+            4 => Some(DecModeState::PermanentlyReset),
+            _ => None,
+        }
+    }
+}
+
+/// Queries the state of DEC private mode `mode` on the terminal on `stream`,
+/// such as `7` for autowrap or `2004` for bracketed paste.
+///
+/// This sends a DECRQM request, `\x1b[?7$p` for mode 7, and returns `None`
+/// if the terminal doesn't answer it. On Windows, this always returns
+/// `None`.
+pub fn query_dec_mode(stream: Stream, mode: u16) -> Option<DecModeState> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        private_mode(Stream::Stdin.as_fd(), stream.as_fd(), mode, QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (stream, mode);
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn private_mode(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    mode: u16,
+    timeout: std::time::Duration,
+) -> Option<DecModeState> {
+    let request = format!("\x1b[?{}$p", mode);
+    let reply = query_with_da_sentinel(input, output, request.as_bytes(), timeout)?;
+    mode_report(&reply, mode)
+}
+
+/// Finds a DECRPM report for private mode `mode`, `\x1b[?7;1$y`, in `reply`
+/// and returns the state it reports.
+#[cfg(unix)]
+pub(crate) fn mode_report(reply: &[u8], mode: u16) -> Option<DecModeState> {
+    csi_params(reply, Some(b'?'), b"$", b'y').find_map(|params| match params[..] {
+        [reported, status] if reported == u32::from(mode) => DecModeState::from_status(status),
+        _ => None,
+    })
+}
+
+/// Returns the terminal's Primary Device Attributes, which it reports in
+/// reply to `\x1b[c`, such as `[62, 4, 22]` for a VT220-compatible terminal
+/// with sixel graphics (`4`) and color (`22`).
+///
+/// This is useful for identifying a terminal's capabilities beyond what the
+/// environment says. If the terminal doesn't reply within a short timeout,
+/// this returns `None`. On Windows, this always returns `None`.
+pub fn device_attributes(stream: Stream) -> Option<Vec<u16>> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        device_attributes_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn device_attributes_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<Vec<u16>> {
+    let reply = query_raw(input, output, b"\x1b[c", timeout, &|reply| {
+        parse_device_attributes(reply).is_some()
+    })?;
+    parse_device_attributes(&reply)
+}
+
+/// Returns the terminal's name and version, such as `WezTerm 20240203`, as
+/// it reports them in reply to an XTVERSION request (`\x1b[>0q`).
+///
+/// Unlike `TERM_PROGRAM`, this identifies the terminal itself even across
+/// `ssh` and inside multiplexers which forward the request. If the terminal
+/// doesn't report a version, this returns `None`, as it always does on
+/// Windows.
+pub fn terminal_version(stream: Stream) -> Option<String> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        terminal_version_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn terminal_version_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<String> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[>0q", timeout)?;
+    parse_xtversion(&reply)
+}
+
+/// Finds an XTVERSION reply, `\x1bP>|WezTerm 20240203\x1b\\`, in `reply` and
+/// returns the text it carries.
+#[cfg(unix)]
+pub(crate) fn parse_xtversion(reply: &[u8]) -> Option<String> {
+    replies(reply).find_map(|reply| match reply {
+        Reply::Dcs {
+            private: Some(b'>'),
+            params,
+            intermediates,
+            final_byte: b'|',
+            data,
+        } if params.is_empty() && intermediates.is_empty() => {
+            let version = String::from_utf8(data).ok()?;
+            if version.is_empty() || version.chars().any(char::is_control) {
+                return None;
+            }
+            Some(version)
+        }
+        _ => None,
+    })
+}
+
+/// Returns the terminal's Secondary Device Attributes, which it reports in
+/// reply to `\x1b[>c`, as its terminal type, firmware version, and ROM
+/// cartridge number, such as `(41, 390, 0)` for xterm patch 390.
+///
+/// This identifies the terminal more finely than [`device_attributes`]. If
+/// the terminal doesn't report all three numbers, this returns `None`, as it
+/// always does on Windows.
+pub fn secondary_device_attributes(stream: Stream) -> Option<(u16, u16, u16)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        secondary_device_attributes_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn secondary_device_attributes_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<(u16, u16, u16)> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[>c", timeout)?;
+    parse_secondary_device_attributes(&reply)
+}
+
+/// Finds a Secondary Device Attributes reply, `\x1b[>41;390;0c`, in `reply`
+/// and returns its three numbers.
+#[cfg(unix)]
+pub(crate) fn parse_secondary_device_attributes(reply: &[u8]) -> Option<(u16, u16, u16)> {
+    csi_params(reply, Some(b'>'), b"", b'c').find_map(|params| match params[..] {
+        [terminal, firmware, cartridge] => Some((terminal, firmware, cartridge)),
+        _ => None,
+    })
+}
+
+/// Returns the kitty keyboard protocol flags currently enabled on the
+/// terminal on `stream`, as reported in reply to `\x1b[?u`, or `None` if it
+/// doesn't support the protocol.
+///
+/// The flags are the protocol's progressive enhancements: `1` to
+/// disambiguate escape codes, `2` to report event types, `4` to report
+/// alternate keys, `8` to report all keys as escape codes, and `16` to
+/// report associated text. `0` means the protocol is supported but nothing
+/// is enabled. Line editors can use this to find out which enhancements are
+/// already active before pushing their own. On Windows, this always returns
+/// `None`.
+pub fn kitty_keyboard_flags(stream: Stream) -> Option<u8> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        kitty_keyboard_flags_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn kitty_keyboard_flags_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<u8> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[?u", timeout)?;
+    parse_kitty_keyboard_flags(&reply)
+}
+
+/// Finds a kitty keyboard protocol flags report, `\x1b[?15u`, in `reply`
+/// and returns the flags.
+#[cfg(unix)]
+pub(crate) fn parse_kitty_keyboard_flags(reply: &[u8]) -> Option<u8> {
+    csi_params(reply, Some(b'?'), b"", b'u').find_map(|params| match params[..] {
+        [flags] => Some(flags),
+        _ => None,
+    })
+}
+
+/// Returns xterm's modifyOtherKeys level on the terminal on `stream`, as
+/// reported in reply to XTQMODKEYS (`\x1b[?4m`), or `None` if it doesn't
+/// say.
+///
+/// At level `0`, keys such as Ctrl+Tab are sent the traditional way, or not
+/// at all; at `1`, combinations without a traditional encoding are sent as
+/// `\x1b[27;...~` sequences; and at `2`, nearly all of them are. Line
+/// editors need this to decode modified keys correctly. On Windows, this
+/// always returns `None`.
+pub fn modify_other_keys_level(stream: Stream) -> Option<u8> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        modify_other_keys_level_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn modify_other_keys_level_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<u8> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[?4m", timeout)?;
+    parse_modify_other_keys_level(&reply)
+}
+
+/// Finds a modifyOtherKeys report, `\x1b[>4;2m`, in `reply` and returns the
+/// level. xterm leaves the level out when it's never been set, which means
+/// `0`.
+#[cfg(unix)]
+pub(crate) fn parse_modify_other_keys_level(reply: &[u8]) -> Option<u8> {
+    csi_params(reply, Some(b'>'), b"", b'm').find_map(|params| match params[..] {
+        [4] => Some(0),
+        [4, level] => Some(level),
+        _ => None,
+    })
+}
+
+/// Returns the position of the terminal's window on the screen, in pixels
+/// from the top-left corner, as `(x, y)`, as reported in reply to the
+/// XTWINOPS request `\x1b[13t`, or `None` if it doesn't say.
+///
+/// Programs which place popups or overlays alongside the terminal can use
+/// this. Positions can be negative on screens left of or above the primary
+/// one. Many terminals refuse to report this, since it leaks information
+/// about the desktop, and some, such as xterm, only do when configured to.
+/// On Windows, this always returns `None`.
+pub fn window_position(stream: Stream) -> Option<(i16, i16)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        window_position_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn window_position_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<(i16, i16)> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[13t", timeout)?;
+    parse_window_report(&reply, 3)
+}
+
+/// Returns the size of the terminal's text area in pixels, as
+/// `(width, height)`, or `None` if it doesn't say.
+///
+/// This sends the XTWINOPS requests `\x1b[14t`, whose reply,
+/// `\x1b[4;height;widtht`, gives the size of the text area, and
+/// `\x1b[16t`, whose reply, `\x1b[6;height;widtht`, gives the size of a
+/// cell. The former is used if the terminal gives it; otherwise, the cell
+/// size is multiplied by the terminal's [`size`] in cells. Image viewers
+/// can use this to scale images to the terminal, and it's more reliable
+/// than the pixel sizes in `TIOCGWINSZ`, which many terminals leave as
+/// zero. On Windows, this always returns `None`.
+pub fn text_area_pixels(stream: Stream) -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        text_area_pixels_on(
+            Stream::Stdin.as_fd(),
+            stream.as_fd(),
+            size(stream),
+            QUERY_TIMEOUT,
+        )
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn text_area_pixels_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    cells: Option<(u16, u16)>,
+    timeout: std::time::Duration,
+) -> Option<(u16, u16)> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[14t\x1b[16t", timeout)?;
+    if let Some((height, width)) = parse_window_report(&reply, 4) {
+        return Some((width, height));
+    }
+    let (cell_height, cell_width) = parse_window_report(&reply, 6)?;
+    let (columns, rows) = cells?;
+    Some((
+        columns.checked_mul(cell_width)?,
+        rows.checked_mul(cell_height)?,
+    ))
+}
+
+/// Finds an XTWINOPS report with two parameters, `\x1b[{kind};a;bt`, in
+/// `reply` and returns them.
+#[cfg(unix)]
+pub(crate) fn parse_window_report<T: TryFrom<i32>>(reply: &[u8], kind: i32) -> Option<(T, T)> {
+    csi_params(reply, None, b"", b't').find_map(|params: Vec<i32>| match params[..] {
+        [reported, a, b] if reported == kind => Some((T::try_from(a).ok()?, T::try_from(b).ok()?)),
+        _ => None,
+    })
+}
+
+/// Returns the number of color registers the terminal on `stream` has for
+/// sixel graphics, as reported by XTSMGRAPHICS, or `None` if it doesn't
+/// say.
+///
+/// Image encoders need this to quantize sixel images to a palette that
+/// fits. This sends `\x1b[?1;1;0S`, which asks for the current number of
+/// registers. Terminals without sixel support, or which don't implement
+/// XTSMGRAPHICS, give `None`, as does Windows.
+pub fn sixel_color_registers(stream: Stream) -> Option<u16> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        sixel_color_registers_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn sixel_color_registers_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<u16> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b[?1;1;0S", timeout)?;
+    parse_sixel_color_registers(&reply)
+}
+
+/// Finds a successful XTSMGRAPHICS color-register report, `\x1b[?1;0;256S`,
+/// in `reply` and returns the number of registers.
+#[cfg(unix)]
+pub(crate) fn parse_sixel_color_registers(reply: &[u8]) -> Option<u16> {
+    csi_params(reply, Some(b'?'), b"", b'S').find_map(|params| match params[..] {
+        // The item, `1` for color registers, and the status, `0` for
+        // success.
+        [1, 0, registers] => Some(registers),
+        _ => None,
+    })
+}
+
+/// Returns the terminal's default foreground color, as `(red, green, blue)`,
+/// as it reports it in reply to an OSC 10 request (`\x1b]10;?\x07`).
+///
+/// Programs which choose their own colors can use this to make sure their
+/// text contrasts with the terminal's. Terminals report each component with
+/// one to four hex digits, typically four, as in `rgb:ffff/ffff/ffff`; these
+/// are scaled to 8 bits. On Windows, this always returns `None`.
+pub fn foreground_color(stream: Stream) -> Option<(u8, u8, u8)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        foreground_color_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn foreground_color_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<(u8, u8, u8)> {
+    let reply = query_with_da_sentinel(input, output, b"\x1b]10;?\x07", timeout)?;
+    parse_osc_color(&reply, "10")
+}
+
+/// Returns the color the terminal on `stream` uses for entry `index` of its
+/// palette, as `(red, green, blue)`, as it reports it in reply to an OSC 4
+/// request (`\x1b]4;1;?\x07` for entry 1).
+///
+/// Entries 0 to 15 are the colors selected by the 16-color escape
+/// sequences, such as `\x1b[31m` for entry 1, red, so themes can use this
+/// to adapt to the user's color scheme rather than assuming the standard
+/// values. Components are scaled to 8 bits, as [`foreground_color`] does.
+/// On Windows, this always returns `None`.
+pub fn palette_color(stream: Stream, index: u8) -> Option<(u8, u8, u8)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        palette_color_on(Stream::Stdin.as_fd(), stream.as_fd(), index, QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (stream, index);
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn palette_color_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    index: u8,
+    timeout: std::time::Duration,
+) -> Option<(u8, u8, u8)> {
+    let request = format!("\x1b]4;{};?\x07", index);
+    let reply = query_with_da_sentinel(input, output, request.as_bytes(), timeout)?;
+    parse_osc_color(&reply, &format!("4;{}", index))
+}
+
+/// Finds a reply to the OSC color request `selector`, such as
+/// `\x1b]10;rgb:ffff/ffff/ffff\x07` for `10`, in `reply` and returns the
+/// color it reports, scaled to 8 bits per component. The reply may also end
+/// with ST, `\x1b\\`.
+#[cfg(unix)]
+pub(crate) fn parse_osc_color(reply: &[u8], selector: &str) -> Option<(u8, u8, u8)> {
+    let prefix = format!("{};rgb:", selector);
+    replies(reply).find_map(|reply| match reply {
+        Reply::Osc(payload) if payload.starts_with(prefix.as_bytes()) => {
+            parse_rgb(&payload[prefix.len()..])
+        }
+        _ => None,
+    })
+}
+
+/// Parses the color specification `ffff/8080/0000` from an OSC color reply,
+/// scaling each component to 8 bits.
+#[cfg(unix)]
+fn parse_rgb(spec: &[u8]) -> Option<(u8, u8, u8)> {
+    let mut components = std::str::from_utf8(spec).ok()?.split('/').map(|component| {
+        if component.is_empty() || component.len() > 4 {
+            return None;
+        }
+        let value = u32::from_str_radix(component, 16).ok()?;
+        let max = (1 << (4 * component.len())) - 1;
+        Some(((value * 255 + max / 2) / max) as u8)
+    });
+    let color = (
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+    if components.next().is_some() {
+        return None;
+    }
+    Some(color)
+}
+
+/// Returns the position of the cursor on the terminal on `stream`, as
+/// `(column, row)` counting from `(0, 0)` at the top left, or `None` if it
+/// can't be determined.
+///
+/// This is [`try_cursor_position`], with any error mapped to `None`.
+pub fn cursor_position(stream: Stream) -> Option<(u16, u16)> {
+    try_cursor_position(stream).ok()
+}
+
+/// Returns the position of the cursor on the terminal on `stream`, as
+/// [`cursor_position`] does, or why it can't be determined.
+///
+/// This sends a Device Status Report request (`\x1b[6n`), which every
+/// VT100-compatible terminal answers, and waits a short time for the reply.
+/// It's `NotATerminal` if `stream` isn't a terminal, `Timeout` if the
+/// terminal doesn't reply in time, and `Unsupported` if [`query_is_safe`]
+/// says the terminal can't be queried, and on Windows.
+pub fn try_cursor_position(stream: Stream) -> Result<(u16, u16), TerminalError> {
+    #[cfg(unix)]
+    {
+        check_descriptor(stream)?;
+        if !is(stream) {
+            return Err(TerminalError::NotATerminal);
+        }
+        if !query_is_safe(stream) {
+            return Err(TerminalError::Unsupported);
+        }
+        cursor_position_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        Err(TerminalError::Unsupported)
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn cursor_position_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Result<(u16, u16), TerminalError> {
+    let reply = try_query_raw(input, output, b"\x1b[6n", timeout, &|reply| {
+        parse_cursor_position(reply).is_some()
+    })?;
+    parse_cursor_position(&reply).ok_or(TerminalError::Unsupported)
+}
+
+/// Returns the size of the terminal on `stream`, found by moving the cursor
+/// to the bottom right and asking where it is, or `None` if it isn't a
+/// terminal or doesn't reply in time.
+pub(crate) fn probed_size(stream: Stream) -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    {
+        if !query_is_safe(stream) {
+            return None;
+        }
+        probed_size_on(Stream::Stdin.as_fd(), stream.as_fd(), QUERY_TIMEOUT)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn probed_size_on(
+    input: BorrowedFd<'_>,
+    output: BorrowedFd<'_>,
+    timeout: std::time::Duration,
+) -> Option<(u16, u16)> {
+    // Save the cursor, move it as far as the terminal clamps it to, ask for
+    // its position, and restore it.
+    let reply = query_raw(
+        input,
+        output,
+        b"\x1b7\x1b[9999;9999H\x1b[6n\x1b8",
+        timeout,
+        &|reply| parse_cursor_position(reply).is_some(),
+    )?;
+    let (column, row) = parse_cursor_position(&reply)?;
+    Some((column + 1, row + 1))
+}
+
+/// Finds a Cursor Position Report, `\x1b[5;10R`, in `reply` and returns the
+/// position it reports, as a zero-based `(column, row)`.
+#[cfg(unix)]
+pub(crate) fn parse_cursor_position(reply: &[u8]) -> Option<(u16, u16)> {
+    csi_params(reply, None, b"", b'R').find_map(|params: Vec<u16>| match params[..] {
+        [row, column] => Some((column.checked_sub(1)?, row.checked_sub(1)?)),
+        _ => None,
+    })
+}
+
+/// Parses a Primary Device Attributes reply, `\x1b[?62;4;22c`, into its
+/// list of numbers, `[62, 4, 22]`, ignoring anything around it. Some
+/// terminals omit the `?`.
+#[cfg(unix)]
+pub(crate) fn parse_device_attributes(reply: &[u8]) -> Option<Vec<u16>> {
+    replies(reply).find_map(|reply| match reply {
+        Reply::Csi {
+            private,
+            params,
+            intermediates,
+            final_byte: b'c',
+        } if private.map_or(true, |private| private == b'?')
+            && intermediates.is_empty()
+            && !params.is_empty() =>
+        {
+            convert_params(params)
+        }
+        _ => None,
+    })
+}
+
+/// Returns the replies in `input`, skipping anything else in it.
+#[cfg(unix)]
+fn replies(input: &[u8]) -> impl Iterator<Item = Reply> + '_ {
+    let mut parser = ReplyParser::new();
+    let mut input = Some(input);
+    core::iter::from_fn(move || parser.feed(input.take().unwrap_or(&[])))
+}
+
+/// Returns the parameters of each control sequence in `input` which has
+/// the private-parameter marker `private`, the intermediate bytes
+/// `intermediates`, and the final byte `final_byte`, skipping any with a
+/// parameter which is empty or doesn't fit in `T`.
+#[cfg(unix)]
+fn csi_params<'a, T: TryFrom<i32> + 'a>(
+    input: &'a [u8],
+    private: Option<u8>,
+    intermediates: &'a [u8],
+    final_byte: u8,
+) -> impl Iterator<Item = Vec<T>> + 'a {
+    replies(input).filter_map(move |reply| match reply {
+        Reply::Csi {
+            private: reply_private,
+            params,
+            intermediates: reply_intermediates,
+            final_byte: reply_final_byte,
+        } if reply_private == private
+            && reply_intermediates == intermediates
+            && reply_final_byte == final_byte =>
+        {
+            convert_params(params)
+        }
+        _ => None,
+    })
+}
+
+/// Converts the parameters of a reply to `T`, or returns `None` if any is
+/// empty or doesn't fit.
+#[cfg(unix)]
+fn convert_params<T: TryFrom<i32>>(params: Vec<Option<i32>>) -> Option<Vec<T>> {
+    params
+        .into_iter()
+        .map(|param| T::try_from(param?).ok())
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! Raw mode, for reading keypresses one at a time.
+
+use crate::*;
+
+/// Puts the terminal on `stream` into raw mode, in which input is available
+/// a keypress at a time, without echo or line editing, until the returned
+/// guard is dropped.
+///
+/// `stream` is normally `Stream::Stdin`. On Unix-family platforms, this
+/// applies `cfmakeraw` to the terminal's attributes; on Windows, it clears
+/// `ENABLE_LINE_INPUT`, `ENABLE_ECHO_INPUT`, and `ENABLE_PROCESSED_INPUT`
+/// from the console mode. This fails if `stream` isn't a terminal. Calls
+/// interrupted by a signal, which `tcsetattr` can be when the process is in
+/// the background, are retried, both here and when the guard is dropped.
+pub fn enter_raw_mode(stream: Stream) -> std::io::Result<RawModeGuard> {
+    #[cfg(unix)]
+    {
+        use rustix::termios::{cfmakeraw, tcgetattr, tcsetattr, OptionalActions};
+
+        let fd = stream.as_fd();
+        let original = retry_on_eintr(|| tcgetattr(fd))?;
+        let mut raw = original;
+        cfmakeraw(&mut raw);
+        retry_on_eintr(|| tcsetattr(fd, OptionalActions::Now, &raw))?;
+        Ok(RawModeGuard { stream, original })
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{
+            SetConsoleMode, ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT, ENABLE_PROCESSED_INPUT,
+        };
+
+        let handle = stream.as_raw_handle();
+        let original = console_mode(handle).ok_or_else(std::io::Error::last_os_error)?;
+        let raw = original & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT | ENABLE_PROCESSED_INPUT);
+        if unsafe { SetConsoleMode(handle, raw) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(RawModeGuard { stream, original })
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "raw mode isn't supported on this platform",
+        ))
+    }
+}
+
+/// Restores a terminal's original mode when dropped. See
+/// [`enter_raw_mode`].
+pub struct RawModeGuard {
+    #[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+    stream: Stream,
+    #[cfg(unix)]
+    original: rustix::termios::Termios,
+    #[cfg(windows)]
+    original: u32,
+}
+
+impl core::fmt::Debug for RawModeGuard {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RawModeGuard")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            let _ = retry_on_eintr(|| {
+                rustix::termios::tcsetattr(
+                    self.stream.as_fd(),
+                    rustix::termios::OptionalActions::Now,
+                    &self.original,
+                )
+            });
+        }
+
+        #[cfg(windows)]
+        unsafe {
+            windows_sys::Win32::System::Console::SetConsoleMode(
+                self.stream.as_raw_handle(),
+                self.original,
+            );
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! An incremental parser for the escape sequences terminals send in reply
+//! to queries.
+//!
+//! The query functions in this crate parse their replies with
+//! [`ReplyParser`], and it's public so that programs sending queries of
+//! their own can do the same. A terminal's reply may arrive across any
+//! number of reads, mixed in with whatever the user happens to be typing,
+//! so the parser is fed bytes as they're read and hands back each reply
+//! once it's complete:
+//!
+//! ```rust
+//! use is_terminal::reply::{Reply, ReplyParser};
+//!
+//! let mut parser = ReplyParser::new();
+//! assert_eq!(parser.feed(b"typed\x1b[?62;"), None);
+//! assert_eq!(
+//!     parser.feed(b"4c"),
+//!     Some(Reply::Csi {
+//!         private: Some(b'?'),
+//!         params: vec![Some(62), Some(4)],
+//!         intermediates: vec![],
+//!         final_byte: b'c',
+//!     })
+//! );
+//! ```
+//!
+//! Only the 7-bit forms of the sequences are recognized, since the 8-bit
+//! C1 controls are also continuation bytes in UTF-8 text.
+
+/// The most bytes of a single sequence which are kept; longer sequences are
+/// dropped, so that a terminal which never finishes one can't use up
+/// memory.
+const MAX_LENGTH: usize = 1 << 16;
+
+/// A complete escape sequence recognized by [`ReplyParser`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Reply {
+    /// A control sequence, `\x1b[` followed by parameters and a final byte,
+    /// as in the Primary Device Attributes reply `\x1b[?62;4c` or the
+    /// Cursor Position Report `\x1b[5;10R`.
+    ///
+    /// Keys such as the arrow keys are also sent as control sequences, so
+    /// callers should check that `final_byte` and `private` are the ones
+    /// they're expecting.
+    Csi {
+        /// The private-parameter marker, `<`, `=`, `>`, or `?`, which
+        /// begins the parameters of many replies, if there is one.
+        private: Option<u8>,
+        /// The parameters, separated by `;`, with `None` for any left
+        /// empty, whose meaning is up to the sequence. A sequence with no
+        /// parameters at all has none here. A `-` at the start of a
+        /// parameter is taken as a sign, since xterm reports negative
+        /// window positions that way.
+        params: Vec<Option<i32>>,
+        /// The intermediate bytes between the parameters and the final
+        /// byte, such as the `$` of a DECRPM report, `\x1b[?7;1$y`.
+        intermediates: Vec<u8>,
+        /// The byte which ends the sequence and says what it is.
+        final_byte: u8,
+    },
+    /// An Operating System Command, `\x1b]` followed by its payload and
+    /// ended by BEL, `\x07`, or ST, `\x1b\\`, as in the OSC 11 reply
+    /// `\x1b]11;rgb:ffff/ffff/ffff\x07`. This holds the payload,
+    /// `11;rgb:ffff/ffff/ffff`.
+    Osc(Vec<u8>),
+    /// A Device Control String, `\x1bP` followed by a header in the form of
+    /// a control sequence's, data, and ST, as in the XTVERSION reply
+    /// `\x1bP>|WezTerm 20240203\x1b\\`.
+    Dcs {
+        /// The private-parameter marker, as for [`Reply::Csi`].
+        private: Option<u8>,
+        /// The parameters, as for [`Reply::Csi`].
+        params: Vec<Option<i32>>,
+        /// The intermediate bytes, as for [`Reply::Csi`].
+        intermediates: Vec<u8>,
+        /// The byte which ends the header, `|` in the example above.
+        final_byte: u8,
+        /// Everything after the header, `WezTerm 20240203` in the example
+        /// above.
+        data: Vec<u8>,
+    },
+    /// An Application Program Command, `\x1b_` followed by its payload and
+    /// ST, as in a kitty graphics protocol reply, `\x1b_Gi=31;OK\x1b\\`.
+    /// This holds the payload, `Gi=31;OK`.
+    Apc(Vec<u8>),
+}
+
+/// Where a [`ReplyParser`] is in the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    /// Outside of any sequence.
+    Ground,
+    /// After an `\x1b`.
+    Escape,
+    /// After `\x1bO`, which some keys are sent with, followed by one more
+    /// byte.
+    Ss3,
+    /// In a control sequence.
+    Csi,
+    /// In a string sequence of the given kind.
+    String(StringKind),
+    /// After an `\x1b` in a string sequence, which ends it if it's followed
+    /// by `\\`.
+    StringEscape(StringKind),
+}
+
+/// The kinds of string sequences, which run until ST.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum StringKind {
+    Osc,
+    Dcs,
+    Apc,
+    /// Start of String and Privacy Message, which are skipped.
+    Ignored,
+}
+
+/// An incremental parser for terminal replies; see the [module
+/// documentation](crate::reply).
+///
+/// Anything which isn't an escape sequence, such as keystrokes, is skipped,
+/// as are the short escape sequences some keys are sent as, such as
+/// `\x1bOA`, and any sequence which is malformed, cut short by another, or
+/// longer than 64 KiB.
+#[derive(Clone, Debug)]
+pub struct ReplyParser {
+    state: State,
+    /// The bytes of the current sequence after its introducer.
+    sequence: Vec<u8>,
+    /// Input which has been fed but not yet parsed, because a reply was
+    /// returned before it.
+    pending: Vec<u8>,
+}
+
+impl ReplyParser {
+    /// Returns a new parser, which hasn't seen any input.
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            sequence: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Parses `input`, following on from everything fed so far, and
+    /// returns the first reply which it completes, or `None` if it doesn't
+    /// complete one.
+    ///
+    /// Anything in `input` after that reply is kept for the next call, so
+    /// when `input` may hold several replies, call this again with an empty
+    /// slice until it returns `None` to get the rest.
+    pub fn feed(&mut self, input: &[u8]) -> Option<Reply> {
+        let mut pending = core::mem::take(&mut self.pending);
+        pending.extend_from_slice(input);
+        for (index, &byte) in pending.iter().enumerate() {
+            if let Some(reply) = self.step(byte) {
+                pending.drain(..=index);
+                self.pending = pending;
+                return Some(reply);
+            }
+        }
+        None
+    }
+
+    /// Moves on by one byte of input, returning the reply it completes, if
+    /// any.
+    fn step(&mut self, byte: u8) -> Option<Reply> {
+        const ESC: u8 = 0x1b;
+        const BEL: u8 = 0x07;
+        // CAN and SUB cancel any sequence in progress.
+        const CAN: u8 = 0x18;
+        const SUB: u8 = 0x1a;
+
+        match self.state {
+            State::Ground => {
+                if byte == ESC {
+                    self.state = State::Escape;
+                }
+            }
+            State::Escape => {
+                self.sequence.clear();
+                self.state = match byte {
+                    ESC => State::Escape,
+                    b'[' => State::Csi,
+                    b']' => State::String(StringKind::Osc),
+                    b'P' => State::String(StringKind::Dcs),
+                    b'_' => State::String(StringKind::Apc),
+                    b'X' | b'^' => State::String(StringKind::Ignored),
+                    b'O' => State::Ss3,
+                    _ => State::Ground,
+                };
+            }
+            State::Ss3 => self.state = State::Ground,
+            State::Csi => match byte {
+                ESC => self.state = State::Escape,
+                CAN | SUB => self.state = State::Ground,
+                0x20..=0x3f => self.push(byte),
+                0x40..=0x7e => {
+                    self.state = State::Ground;
+                    let (private, params, intermediates) = parse_header(&self.sequence)?;
+                    return Some(Reply::Csi {
+                        private,
+                        params,
+                        intermediates,
+                        final_byte: byte,
+                    });
+                }
+                // Other controls are carried out by terminals as though
+                // they weren't in the sequence, and are skipped here.
+                0x00..=0x1f | 0x7f => {}
+                _ => self.state = State::Ground,
+            },
+            State::String(kind) => match byte {
+                ESC => self.state = State::StringEscape(kind),
+                BEL if kind == StringKind::Osc => {
+                    self.state = State::Ground;
+                    return self.finish_string(kind);
+                }
+                CAN | SUB => self.state = State::Ground,
+                _ => self.push(byte),
+            },
+            State::StringEscape(kind) => {
+                if byte == b'\\' {
+                    self.state = State::Ground;
+                    return self.finish_string(kind);
+                }
+                // Any other escape sequence cuts the string short.
+                self.state = State::Escape;
+                return self.step(byte);
+            }
+        }
+        None
+    }
+
+    /// Appends `byte` to the current sequence, or drops the sequence if
+    /// it's grown too long.
+    fn push(&mut self, byte: u8) {
+        if self.sequence.len() < MAX_LENGTH {
+            self.sequence.push(byte);
+        } else {
+            self.state = State::Ground;
+        }
+    }
+
+    /// Returns the reply for the string sequence of kind `kind` which has
+    /// just ended.
+    fn finish_string(&mut self, kind: StringKind) -> Option<Reply> {
+        let sequence = core::mem::take(&mut self.sequence);
+        match kind {
+            StringKind::Osc => Some(Reply::Osc(sequence)),
+            StringKind::Apc => Some(Reply::Apc(sequence)),
+            StringKind::Dcs => {
+                let end = sequence.iter().position(|b| (0x40..=0x7e).contains(b))?;
+                let (private, params, intermediates) = parse_header(&sequence[..end])?;
+                Some(Reply::Dcs {
+                    private,
+                    params,
+                    intermediates,
+                    final_byte: sequence[end],
+                    data: sequence[end + 1..].to_vec(),
+                })
+            }
+            StringKind::Ignored => None,
+        }
+    }
+}
+
+impl Default for ReplyParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The private-parameter marker, parameters, and intermediate bytes of a
+/// control sequence or device control string.
+type Header = (Option<u8>, Vec<Option<i32>>, Vec<u8>);
+
+/// Splits the header of a control sequence or device control string,
+/// everything before its final byte, into its private-parameter marker,
+/// parameters, and intermediate bytes, or returns `None` if it's malformed,
+/// as when it has sub-parameters separated by `:`.
+fn parse_header(header: &[u8]) -> Option<Header> {
+    let (private, header) = match header.first() {
+        Some(&marker @ b'<'..=b'?') => (Some(marker), &header[1..]),
+        _ => (None, header),
+    };
+    let split = header
+        .iter()
+        .rposition(|b| !(0x20..=0x2f).contains(b))
+        .map_or(0, |last| last + 1);
+    let (params, intermediates) = header.split_at(split);
+    let params = if params.is_empty() {
+        Vec::new()
+    } else {
+        params
+            .split(|&b| b == b';')
+            .map(parse_param)
+            .collect::<Option<_>>()?
+    };
+    Some((private, params, intermediates.to_vec()))
+}
+
+/// Parses a single parameter, which may be empty, saturating any which
+/// overflow.
+fn parse_param(param: &[u8]) -> Option<Option<i32>> {
+    if param.is_empty() {
+        return Some(None);
+    }
+    let (negative, digits) = match param.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        _ => (false, param),
+    };
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let value = digits.iter().fold(0i32, |value, digit| {
+        value
+            .saturating_mul(10)
+            .saturating_add(i32::from(digit - b'0'))
+    });
+    Some(Some(if negative { -value } else { value }))
+}
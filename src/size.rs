@@ -0,0 +1,245 @@
+//! Terminal sizes.
+
+use crate::*;
+
+/// Returns the size of the terminal `stream` refers to, as `(columns, rows)`,
+/// or `None` if it isn't a terminal or its size is unknown.
+///
+/// On Unix-family platforms this uses the `TIOCGWINSZ` ioctl, and a size of
+/// zero, which ptys report until someone sets it, is treated as unknown. On
+/// Windows this is the size of the console's visible window. Elsewhere this
+/// always returns `None`.
+pub fn size(stream: Stream) -> Option<(u16, u16)> {
+    #[cfg(unix)]
+    {
+        let winsize = rustix::termios::tcgetwinsize(stream.as_fd()).ok()?;
+        if winsize.ws_col == 0 && winsize.ws_row == 0 {
+            return None;
+        }
+        Some((winsize.ws_col, winsize.ws_row))
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::{
+            GetConsoleScreenBufferInfo, CONSOLE_SCREEN_BUFFER_INFO,
+        };
+
+        let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { std::mem::zeroed() };
+        if unsafe { GetConsoleScreenBufferInfo(stream.as_raw_handle(), &mut info) } == 0 {
+            return None;
+        }
+        let window = info.srWindow;
+        Some((
+            (window.Right - window.Left + 1) as u16,
+            (window.Bottom - window.Top + 1) as u16,
+        ))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        None
+    }
+}
+
+/// Returns the size of the terminal `stream` refers to, as [`size`] does,
+/// or why it can't be determined.
+///
+/// This is `NotATerminal` if `stream` isn't a terminal, `BadDescriptor` if
+/// it's closed, and `Unsupported` if the terminal's size is unknown or the
+/// platform can't tell.
+#[cfg(feature = "std")]
+pub fn try_size(stream: Stream) -> Result<(u16, u16), TerminalError> {
+    #[cfg(unix)]
+    {
+        let winsize = rustix::termios::tcgetwinsize(stream.as_fd())?;
+        if winsize.ws_col == 0 && winsize.ws_row == 0 {
+            return Err(TerminalError::Unsupported);
+        }
+        Ok((winsize.ws_col, winsize.ws_row))
+    }
+
+    #[cfg(windows)]
+    {
+        check_descriptor(stream)?;
+        if console_mode(stream.as_raw_handle()).is_none() {
+            return Err(TerminalError::NotATerminal);
+        }
+        size(stream).ok_or_else(|| std::io::Error::last_os_error().into())
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+        Err(TerminalError::Unsupported)
+    }
+}
+
+/// The sizes reported for a terminal by each of the ways of asking, as
+/// gathered by [`size_sources`], each as `(columns, rows)`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub struct SizeSources {
+    /// The size from the OS, as [`size`] reports it: the `TIOCGWINSZ`
+    /// ioctl on Unix-family platforms, and the console window on Windows.
+    pub ioctl: Option<(u16, u16)>,
+    /// The size from `COLUMNS` and `LINES`, if both are set to positive
+    /// numbers.
+    pub env: Option<(u16, u16)>,
+    /// The size found by moving the cursor as far to the bottom right as
+    /// the terminal allows and asking where it ended up.
+    #[cfg(feature = "query")]
+    pub cursor_probe: Option<(u16, u16)>,
+}
+
+/// Gathers the size of the terminal `stream` refers to from each of the
+/// places it can be found, so that callers can see when they disagree.
+///
+/// This is a diagnostic, for bug reports about output laid out for the
+/// wrong width: a stale `COLUMNS` exported by a shell, or a pty whose size
+/// was never set, shows up as a disagreement. With the `query` feature,
+/// the terminal itself is asked too; that moves the cursor and waits a
+/// short time for the reply, and the cursor is put back afterwards.
+#[cfg(feature = "std")]
+pub fn size_sources(stream: Stream) -> SizeSources {
+    size_sources_with(stream, &SystemEnv)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn size_sources_with(stream: Stream, env: &dyn EnvSource) -> SizeSources {
+    let dimension = |name| {
+        env.get(name)
+            .and_then(|value| value.trim().parse::<u16>().ok())
+            .filter(|&value| value != 0)
+    };
+    SizeSources {
+        ioctl: size(stream),
+        env: dimension("COLUMNS").and_then(|columns| Some((columns, dimension("LINES")?))),
+        #[cfg(feature = "query")]
+        cursor_probe: probed_size(stream),
+    }
+}
+
+/// The sizes cached by [`size_cached`], one for each standard stream.
+///
+/// Each slot is `SIZE_UNCACHED` when nothing is cached, `SIZE_UNKNOWN` when
+/// [`size`] returned `None`, `SIZE_QUERYING` while a thread is querying the
+/// size to cache it, `SIZE_QUERY_INVALIDATED` if [`invalidate_size_cache`]
+/// was called during that query, and otherwise holds the columns in the high
+/// half and the rows in the low half. Only the thread which moved a slot to
+/// `SIZE_QUERYING` stores a size in it, and only if it wasn't invalidated in
+/// the meantime, so a size queried before a resize is never cached after it.
+#[cfg(all(feature = "cache", not(all(test, is_terminal_loom))))]
+static SIZE_CACHE: [core::sync::atomic::AtomicU32; 3] = [
+    core::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+    core::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+    core::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+];
+
+// Under loom, the cache is made afresh for each execution of a model.
+#[cfg(all(feature = "cache", test, is_terminal_loom))]
+loom::lazy_static! {
+    static ref SIZE_CACHE: [loom::sync::atomic::AtomicU32; 3] = [
+        loom::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+        loom::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+        loom::sync::atomic::AtomicU32::new(SIZE_UNCACHED),
+    ];
+}
+
+#[cfg(feature = "cache")]
+const SIZE_UNCACHED: u32 = 0;
+
+#[cfg(feature = "cache")]
+const SIZE_UNKNOWN: u32 = u32::MAX;
+
+#[cfg(feature = "cache")]
+const SIZE_QUERYING: u32 = u32::MAX - 1;
+
+#[cfg(feature = "cache")]
+const SIZE_QUERY_INVALIDATED: u32 = u32::MAX - 2;
+
+/// Returns the size of the terminal `stream` refers to, as [`size`] does,
+/// but remembers the result and returns it again on later calls, until
+/// [`invalidate_size_cache`] is called.
+///
+/// This avoids a system call on every redraw. The cached size is stale as
+/// soon as the terminal is resized, so programs using this should call
+/// `invalidate_size_cache` when they receive `SIGWINCH` on Unix-family
+/// platforms, or a resize event on Windows. `Stream::Custom` handles aren't
+/// cached.
+#[cfg(feature = "cache")]
+pub fn size_cached(stream: Stream) -> Option<(u16, u16)> {
+    size_cached_with(stream, &size)
+}
+
+#[cfg(feature = "cache")]
+pub(crate) fn size_cached_with(
+    stream: Stream,
+    query: &dyn Fn(Stream) -> Option<(u16, u16)>,
+) -> Option<(u16, u16)> {
+    use core::sync::atomic::Ordering;
+
+    let slot = match stream {
+        Stream::Stdin => &SIZE_CACHE[0],
+        Stream::Stdout => &SIZE_CACHE[1],
+        Stream::Stderr => &SIZE_CACHE[2],
+        #[cfg(all(windows, feature = "custom-fd"))]
+        Stream::Custom(_) => return query(stream),
+    };
+    // Acquiring pairs with the release in `invalidate_size_cache`, so a
+    // query which starts after an invalidation sees whatever changed before
+    // it.
+    match slot.compare_exchange(
+        SIZE_UNCACHED,
+        SIZE_QUERYING,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => {
+            let size = query(stream);
+            let packed = match size {
+                Some((columns, rows)) => (u32::from(columns) << 16) | u32::from(rows),
+                None => SIZE_UNKNOWN,
+            };
+            // Sizes which collide with the other states aren't cached.
+            let packed = match packed {
+                SIZE_QUERYING | SIZE_QUERY_INVALIDATED => SIZE_UNCACHED,
+                packed => packed,
+            };
+            if slot
+                .compare_exchange(SIZE_QUERYING, packed, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+            {
+                // The cache was invalidated during the query, so this size
+                // may be stale. Let the next call query it again.
+                slot.store(SIZE_UNCACHED, Ordering::Relaxed);
+            }
+            size
+        }
+        // Another thread is querying the size; query it too rather than
+        // wait for it.
+        Err(SIZE_QUERYING) | Err(SIZE_QUERY_INVALIDATED) => query(stream),
+        Err(SIZE_UNKNOWN) => None,
+        Err(packed) => Some(((packed >> 16) as u16, packed as u16)),
+    }
+}
+
+/// Forgets the sizes remembered by [`size_cached`], so that its next calls
+/// query the terminal again.
+///
+/// This is async-signal-safe, so it can be called from a `SIGWINCH` handler.
+#[cfg(feature = "cache")]
+pub fn invalidate_size_cache() {
+    use core::sync::atomic::Ordering;
+
+    for slot in SIZE_CACHE.iter() {
+        let _ = slot.fetch_update(Ordering::Release, Ordering::Relaxed, |state| {
+            Some(match state {
+                SIZE_QUERYING | SIZE_QUERY_INVALIDATED => SIZE_QUERY_INVALIDATED,
+                _ => SIZE_UNCACHED,
+            })
+        });
+    }
+}
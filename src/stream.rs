@@ -0,0 +1,309 @@
+//! The [`Stream`] type, naming the standard streams.
+
+use crate::*;
+
+/// One of the standard streams of the current process.
+///
+/// Some features add variants, so this enum is non-exhaustive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Stream {
+    /// Standard input.
+    Stdin,
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+    /// Some other standard handle, identified by the `STD_HANDLE` id that
+    /// `GetStdHandle` accepts. See [`from_std_handle`].
+    #[cfg(all(windows, feature = "custom-fd"))]
+    Custom(STD_HANDLE),
+}
+
+impl Stream {
+    /// Returns `true` if this is an input stream, that is, `Stdin`.
+    ///
+    /// `Stream::Custom` handles have no known direction, so they're neither
+    /// input nor output streams.
+    pub const fn is_input(self) -> bool {
+        matches!(self, Stream::Stdin)
+    }
+
+    /// Returns `true` if this is an output stream, that is, `Stdout` or
+    /// `Stderr`.
+    pub const fn is_output(self) -> bool {
+        matches!(self, Stream::Stdout | Stream::Stderr)
+    }
+
+    #[cfg(any(unix, target_os = "wasi"))]
+    #[inline]
+    pub(crate) fn as_fd(self) -> BorrowedFd<'static> {
+        match self {
+            Stream::Stdin => rustix::io::stdin(),
+            Stream::Stdout => rustix::io::stdout(),
+            Stream::Stderr => rustix::io::stderr(),
+        }
+    }
+
+    #[cfg(windows)]
+    #[inline]
+    pub(crate) fn as_raw_handle(self) -> HANDLE {
+        match self {
+            Stream::Stdin => std::io::stdin().as_raw_handle() as HANDLE,
+            Stream::Stdout => std::io::stdout().as_raw_handle() as HANDLE,
+            Stream::Stderr => std::io::stderr().as_raw_handle() as HANDLE,
+            #[cfg(feature = "custom-fd")]
+            Stream::Custom(id) => unsafe { windows_sys::Win32::System::Console::GetStdHandle(id) },
+        }
+    }
+}
+
+/// Returns the [`Stream`] for the standard handle `id`, as accepted by
+/// `GetStdHandle`.
+///
+/// `STD_INPUT_HANDLE`, `STD_OUTPUT_HANDLE`, and `STD_ERROR_HANDLE` map to
+/// `Stdin`, `Stdout`, and `Stderr`, so that all queries on them take the same
+/// path; any other id is wrapped in `Stream::Custom`.
+#[cfg(all(windows, feature = "custom-fd"))]
+pub fn from_std_handle(id: STD_HANDLE) -> Stream {
+    use windows_sys::Win32::System::Console::{
+        STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    match id {
+        STD_INPUT_HANDLE => Stream::Stdin,
+        STD_OUTPUT_HANDLE => Stream::Stdout,
+        STD_ERROR_HANDLE => Stream::Stderr,
+        id => Stream::Custom(id),
+    }
+}
+
+/// Returns the [`Stream`] whose file descriptor is `fd`: `Stdin` for 0,
+/// `Stdout` for 1, `Stderr` for 2, and `None` for anything else.
+///
+/// This lets code which is handed a raw file descriptor use the
+/// `Stream`-based functions in this crate for it.
+#[cfg(unix)]
+pub fn stream_of_fd(fd: rustix::fd::RawFd) -> Option<Stream> {
+    match fd {
+        0 => Some(Stream::Stdin),
+        1 => Some(Stream::Stdout),
+        2 => Some(Stream::Stderr),
+        _ => None,
+    }
+}
+
+/// Returns the [`Stream`] whose handle, as returned by `GetStdHandle`, is
+/// `handle`, or `None` if it isn't a standard handle.
+///
+/// Null and invalid handles never match, even if a standard handle is unset.
+/// This is the Windows counterpart of `stream_of_fd`.
+#[cfg(windows)]
+pub fn stream_of_handle(handle: std::os::windows::io::RawHandle) -> Option<Stream> {
+    let handle = handle as HANDLE;
+    if handle == 0 || handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+    [Stream::Stdin, Stream::Stdout, Stream::Stderr]
+        .iter()
+        .copied()
+        .find(|stream| stream.as_raw_handle() == handle)
+}
+
+impl IsTerminal for Stream {
+    #[inline]
+    fn is_terminal(&self) -> bool {
+        resolve(*self, false)
+    }
+}
+
+/// Decides whether `stream` is a terminal, for [`is`], `Stream`'s
+/// [`IsTerminal`] impl, `is_cached`, and everything which checks a `Stream`
+/// through them.
+///
+/// Each of these sources may decide the answer, and the first one which
+/// has one wins:
+///
+/// | Order | Source                                                   | Enabled by |
+/// |-------|----------------------------------------------------------|------------|
+/// | 1     | `testutil::with_fake_tty`, then `testutil::set_override` | `testutil` |
+/// | 2     | `ATTY_FORCE`, read through [`SystemEnv`]                 | `std`      |
+/// | 3     | the answer cached by an earlier `is_cached`, if `cached` | `cache`    |
+/// | 4     | asking the OS                                            | always     |
+///
+/// Only the OS's answer is cached, so overrides and `ATTY_FORCE` take
+/// effect, and stop taking effect, immediately, even for `is_cached`. The
+/// price is that with the `std` feature, every call looks `ATTY_FORCE` up
+/// with `std::env::var`, which takes the process's environment lock and
+/// allocates, even when `is_cached` has the OS's answer already.
+pub(crate) fn resolve(stream: Stream, cached: bool) -> bool {
+    #[cfg(feature = "std")]
+    let forced = || forced_tty(&SystemEnv);
+    #[cfg(not(feature = "std"))]
+    let forced = || None;
+
+    resolve_forced(stream, forced, cached)
+}
+
+/// Decides whether `stream` is a terminal as [`is`] does, but reads
+/// `ATTY_FORCE` from `env`, for the `_in` functions which take the
+/// environment to read.
+#[cfg(any(feature = "color", feature = "term"))]
+pub(crate) fn resolve_in(stream: Stream, env: &dyn EnvSource) -> bool {
+    resolve_forced(stream, || forced_tty(env), false)
+}
+
+/// Does the work of `resolve`, with `forced` saying what `ATTY_FORCE`
+/// forces the answer to.
+fn resolve_forced(stream: Stream, forced: impl FnOnce() -> Option<bool>, cached: bool) -> bool {
+    #[cfg(feature = "testutil")]
+    {
+        if let Some(tty) = testutil::fake_tty(stream) {
+            return tty;
+        }
+    }
+
+    if let Some(tty) = forced() {
+        return tty;
+    }
+
+    #[cfg(feature = "cache")]
+    {
+        if cached {
+            return tty_cached_with(stream, &os_is_terminal);
+        }
+    }
+    #[cfg(not(feature = "cache"))]
+    let _ = cached;
+
+    os_is_terminal(stream)
+}
+
+/// Returns what `query` says about whether `stream` is a terminal, but
+/// remembers it and returns it again on later calls, until
+/// [`invalidate_tty_cache`] is called.
+#[cfg(feature = "cache")]
+pub(crate) fn tty_cached_with(stream: Stream, query: &dyn Fn(Stream) -> bool) -> bool {
+    use core::sync::atomic::Ordering;
+
+    let slot = match stream {
+        Stream::Stdin => &TTY_CACHE[0],
+        Stream::Stdout => &TTY_CACHE[1],
+        Stream::Stderr => &TTY_CACHE[2],
+        #[cfg(all(windows, feature = "custom-fd"))]
+        Stream::Custom(_) => return query(stream),
+    };
+    // Acquiring pairs with the release in `invalidate_tty_cache`, so a
+    // query which starts after an invalidation sees whatever changed before
+    // it.
+    match slot.compare_exchange(
+        TTY_UNCACHED,
+        TTY_QUERYING,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => {
+            let tty = query(stream);
+            let answer = if tty { TTY_YES } else { TTY_NO };
+            if slot
+                .compare_exchange(TTY_QUERYING, answer, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+            {
+                // The cache was invalidated during the query, so this answer
+                // may be stale. Let the next call query it again.
+                slot.store(TTY_UNCACHED, Ordering::Relaxed);
+            }
+            tty
+        }
+        // Another thread is querying; query too rather than wait for it.
+        Err(TTY_QUERYING) | Err(TTY_QUERY_INVALIDATED) => query(stream),
+        Err(state) => state == TTY_YES,
+    }
+}
+
+/// The answers cached by `is_cached`, one for each standard stream.
+///
+/// Each slot is `TTY_UNCACHED`, `TTY_NO`, or `TTY_YES`, or, as for the
+/// size cache, `TTY_QUERYING` while a thread is asking the OS so as to cache
+/// the answer, and `TTY_QUERY_INVALIDATED` if [`invalidate_tty_cache`] was
+/// called during that query. Only the thread which moved a slot to
+/// `TTY_QUERYING` stores an answer in it, and only if it wasn't invalidated
+/// in the meantime, so an answer from before a redirection is never cached
+/// after it.
+#[cfg(all(feature = "cache", not(all(test, is_terminal_loom))))]
+pub(crate) static TTY_CACHE: [core::sync::atomic::AtomicU8; 3] = [
+    core::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+    core::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+    core::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+];
+
+// Under loom, the cache is made afresh for each execution of a model.
+#[cfg(all(feature = "cache", test, is_terminal_loom))]
+loom::lazy_static! {
+    pub(crate) static ref TTY_CACHE: [loom::sync::atomic::AtomicU8; 3] = [
+        loom::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+        loom::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+        loom::sync::atomic::AtomicU8::new(TTY_UNCACHED),
+    ];
+}
+
+#[cfg(feature = "cache")]
+pub(crate) const TTY_UNCACHED: u8 = 0;
+
+#[cfg(feature = "cache")]
+const TTY_NO: u8 = 1;
+
+#[cfg(feature = "cache")]
+const TTY_YES: u8 = 2;
+
+#[cfg(feature = "cache")]
+pub(crate) const TTY_QUERYING: u8 = 3;
+
+#[cfg(feature = "cache")]
+pub(crate) const TTY_QUERY_INVALIDATED: u8 = 4;
+
+/// Asks the OS whether `stream` is a terminal.
+fn os_is_terminal(stream: Stream) -> bool {
+    #[cfg(any(unix, target_os = "wasi"))]
+    {
+        stream.as_fd().is_terminal()
+    }
+
+    #[cfg(not(any(unix, target_os = "wasi")))]
+    match stream {
+        Stream::Stdin => std::io::stdin().is_terminal(),
+        Stream::Stdout => std::io::stdout().is_terminal(),
+        Stream::Stderr => std::io::stderr().is_terminal(),
+        #[cfg(all(windows, feature = "custom-fd"))]
+        Stream::Custom(_) => {
+            let handle = stream.as_raw_handle();
+            handle != INVALID_HANDLE_VALUE
+                && handle_is_console(unsafe { BorrowedHandle::borrow_raw(handle as _) })
+        }
+    }
+}
+
+/// Returns `BadDescriptor` if `stream`'s file descriptor or handle is
+/// closed, invalid, or unset.
+#[cfg(any(feature = "color", feature = "query", all(windows, feature = "std")))]
+pub(crate) fn check_descriptor(stream: Stream) -> Result<(), TerminalError> {
+    #[cfg(unix)]
+    {
+        rustix::io::fcntl_getfd(stream.as_fd())?;
+    }
+
+    #[cfg(windows)]
+    {
+        let handle = stream.as_raw_handle();
+        if handle == 0 || handle == INVALID_HANDLE_VALUE {
+            return Err(TerminalError::BadDescriptor);
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = stream;
+    }
+
+    Ok(())
+}
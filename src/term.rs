@@ -0,0 +1,1074 @@
+//! Terminal-capability checks based on `TERM` and the terminfo database.
+
+use crate::*;
+
+/// Returns `true` if enabling xterm mouse reporting (`\x1b[?1000h`, and the
+/// SGR encoding `\x1b[?1006h`) on `stream` is likely to work.
+///
+/// This requires `stream` to be a terminal whose `TERM` isn't `dumb`; on
+/// Unix-family platforms `TERM` must also be set. On Windows, a console must
+/// support virtual-terminal input, since that's where mouse reports arrive;
+/// MSYS ptys are driven by a Unix-style terminal emulator and always qualify.
+pub fn supports_mouse(stream: Stream) -> bool {
+    #[cfg(windows)]
+    let tty = if console_mode(stream.as_raw_handle()).is_some() {
+        console_supports_vt_input()
+    } else {
+        is(stream)
+    };
+
+    #[cfg(not(windows))]
+    let tty = is(stream);
+
+    supports_mouse_with(tty, env_var("TERM").as_deref())
+}
+
+pub(crate) fn supports_mouse_with(tty: bool, term: Option<&str>) -> bool {
+    tty && !term_is_dumb(term)
+}
+
+/// Returns `true` if enabling bracketed paste mode (`\x1b[?2004h`) on
+/// `stream` is likely to work, so that pasted text arrives between
+/// `\x1b[200~` and `\x1b[201~` markers.
+///
+/// This has the same requirements as [`supports_mouse`], and additionally
+/// excludes the Linux console (`TERM=linux`), which doesn't support it.
+pub fn supports_bracketed_paste(stream: Stream) -> bool {
+    #[cfg(windows)]
+    let tty = if console_mode(stream.as_raw_handle()).is_some() {
+        console_supports_vt_input()
+    } else {
+        is(stream)
+    };
+
+    #[cfg(not(windows))]
+    let tty = is(stream);
+
+    supports_bracketed_paste_with(tty, env_var("TERM").as_deref())
+}
+
+pub(crate) fn supports_bracketed_paste_with(tty: bool, term: Option<&str>) -> bool {
+    supports_mouse_with(tty, term) && term != Some("linux")
+}
+
+/// Returns `true` if `stream` is a terminal which likely doesn't support
+/// bracketed paste, as reported by [`supports_bracketed_paste`], so that
+/// text pasted into a prompt reading from it is indistinguishable from
+/// typing.
+///
+/// Then a multi-line paste into a REPL or shell-like prompt runs each line
+/// as soon as its newline arrives, including lines the user didn't mean to
+/// run, such as an incomplete command or one copied by mistake. Programs can
+/// warn before accepting multi-line input, or ask for confirmation, when
+/// this is `true`. When it's `false` because the terminal supports
+/// bracketed paste, programs should still enable it, with `\x1b[?2004h`,
+/// to be protected. This is normally asked of `Stream::Stdin`.
+pub fn paste_is_dangerous(stream: Stream) -> bool {
+    is(stream) && !supports_bracketed_paste(stream)
+}
+
+/// A terminal emulator or multiplexer, as identified by [`term_program`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum TermProgram {
+    /// macOS's Terminal.app.
+    AppleTerminal,
+    /// iTerm2.
+    ITerm2,
+    /// kitty.
+    Kitty,
+    /// WezTerm.
+    WezTerm,
+    /// Alacritty.
+    Alacritty,
+    /// tmux.
+    Tmux,
+    /// The integrated terminal in Visual Studio Code.
+    VsCode,
+    /// Windows Terminal.
+    WindowsTerminal,
+    /// Hyper.
+    Hyper,
+    /// mintty, as used by MSYS2, Cygwin, and Git Bash.
+    Mintty,
+    /// Some other program, as named by `TERM_PROGRAM`.
+    Other(String),
+}
+
+/// Identifies the terminal program the process is running in, from
+/// `TERM_PROGRAM` and, for programs which don't set that, their own
+/// environment variables.
+///
+/// Multiplexers such as tmux set `TERM_PROGRAM` themselves, so inside one,
+/// this names the multiplexer rather than the terminal it's running in.
+pub fn term_program() -> Option<TermProgram> {
+    term_program_in(&SystemEnv)
+}
+
+/// Like [`term_program`], but reads the environment from `env`.
+pub fn term_program_in(env: &dyn EnvSource) -> Option<TermProgram> {
+    if let Some(program) = env.get("TERM_PROGRAM") {
+        return Some(match program.as_str() {
+            "Apple_Terminal" => TermProgram::AppleTerminal,
+            "iTerm.app" => TermProgram::ITerm2,
+            "WezTerm" => TermProgram::WezTerm,
+            "tmux" => TermProgram::Tmux,
+            "vscode" => TermProgram::VsCode,
+            "Hyper" => TermProgram::Hyper,
+            "mintty" => TermProgram::Mintty,
+            _ => TermProgram::Other(program),
+        });
+    }
+
+    let term = env.get("TERM");
+    if env.get("KITTY_WINDOW_ID").is_some() || term.as_deref() == Some("xterm-kitty") {
+        Some(TermProgram::Kitty)
+    } else if env.get("ALACRITTY_WINDOW_ID").is_some() || term.as_deref() == Some("alacritty") {
+        Some(TermProgram::Alacritty)
+    } else if env.get("WEZTERM_EXECUTABLE").is_some() {
+        Some(TermProgram::WezTerm)
+    } else if env.get("WT_SESSION").is_some() {
+        Some(TermProgram::WindowsTerminal)
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if the terminal on `stream` likely supports writing to the
+/// clipboard with OSC 52 sequences.
+///
+/// Terminals don't report this, so it's decided from an allowlist of
+/// [`term_program`]s known to support it: iTerm2, kitty, WezTerm, and tmux
+/// (which forwards the sequence to the outer terminal when its
+/// `set-clipboard` option allows it).
+pub fn supports_clipboard_osc52(stream: Stream) -> bool {
+    supports_clipboard_osc52_with(is(stream), term_program().as_ref())
+}
+
+pub(crate) fn supports_clipboard_osc52_with(tty: bool, program: Option<&TermProgram>) -> bool {
+    tty && matches!(
+        program,
+        Some(TermProgram::ITerm2)
+            | Some(TermProgram::Kitty)
+            | Some(TermProgram::WezTerm)
+            | Some(TermProgram::Tmux)
+    )
+}
+
+/// Returns `true` if the terminal on `stream` likely honors the OSC 0 and
+/// OSC 2 sequences for setting the window title.
+///
+/// Known [`term_program`]s, including mintty and Windows Terminal, and
+/// terminals whose `TERM` names the xterm family or a close relative (`rxvt`,
+/// `screen`, `tmux`, `alacritty`, `kitty`, `foot`, `konsole`, `gnome`,
+/// `vte`, `putty`) qualify. `dumb` terminals and the Linux console
+/// (`TERM=linux`), which has no title, don't. On Windows, consoles can always
+/// have their title set.
+pub fn supports_title(stream: Stream) -> bool {
+    #[cfg(windows)]
+    {
+        if console_mode(stream.as_raw_handle()).is_some() {
+            return true;
+        }
+    }
+
+    supports_title_with(
+        is(stream),
+        env_var("TERM").as_deref(),
+        term_program().as_ref(),
+    )
+}
+
+pub(crate) fn supports_title_with(
+    tty: bool,
+    term: Option<&str>,
+    program: Option<&TermProgram>,
+) -> bool {
+    if !tty || term == Some("linux") || term == Some("dumb") {
+        return false;
+    }
+    match program {
+        Some(TermProgram::Other(_)) | None => {}
+        Some(_) => return true,
+    }
+
+    let term = term.unwrap_or("");
+    [
+        "xterm",
+        "rxvt",
+        "screen",
+        "tmux",
+        "alacritty",
+        "kitty",
+        "foot",
+        "konsole",
+        "gnome",
+        "vte",
+        "putty",
+    ]
+    .iter()
+    .any(|family| term.starts_with(family))
+}
+
+/// Returns `true` if the terminal on `stream` likely reports focus changes
+/// when xterm focus reporting (`\x1b[?1004h`) is enabled.
+///
+/// Terminals don't advertise this, so it's a heuristic: known
+/// [`term_program`]s other than Terminal.app qualify, as do terminals whose
+/// `TERM` names xterm, foot, kitty, alacritty, or tmux (which forwards focus
+/// changes when its `focus-events` option is on). On Windows, a console must
+/// support virtual-terminal input and be hosted by Windows Terminal; the
+/// classic console reports focus with input records instead.
+pub fn supports_focus_events(stream: Stream) -> bool {
+    #[cfg(windows)]
+    let tty = if console_mode(stream.as_raw_handle()).is_some() {
+        console_supports_vt_input()
+    } else {
+        is(stream)
+    };
+
+    #[cfg(not(windows))]
+    let tty = is(stream);
+
+    supports_focus_events_with(tty, env_var("TERM").as_deref(), term_program().as_ref())
+}
+
+pub(crate) fn supports_focus_events_with(
+    tty: bool,
+    term: Option<&str>,
+    program: Option<&TermProgram>,
+) -> bool {
+    if !tty || term == Some("linux") || term == Some("dumb") {
+        return false;
+    }
+    match program {
+        Some(TermProgram::AppleTerminal) => return false,
+        Some(TermProgram::Other(_)) | None => {}
+        Some(_) => return true,
+    }
+
+    let term = term.unwrap_or("");
+    ["xterm", "foot", "kitty", "alacritty", "tmux"]
+        .iter()
+        .any(|family| term.starts_with(family))
+}
+
+/// Sets the window title of the terminal on `stream`, if
+/// [`supports_title`] says it's supported, returning whether it did.
+///
+/// Control characters, including BEL and ESC, are removed from `title`
+/// first, so that it can't end the sequence early and inject escapes of its
+/// own. The title is set with an OSC 2 sequence, or with `SetConsoleTitleW`
+/// on Windows consoles. `Stream::Stdin` isn't an output stream, so it's
+/// never used, and neither are MSYS ptys on custom standard handles.
+pub fn set_title(stream: Stream, title: &str) -> bool {
+    if stream == Stream::Stdin || !supports_title(stream) {
+        return false;
+    }
+    let title = sanitize_title(title);
+
+    #[cfg(windows)]
+    {
+        if console_mode(stream.as_raw_handle()).is_some() {
+            use std::os::windows::ffi::OsStrExt;
+            use windows_sys::Win32::System::Console::SetConsoleTitleW;
+
+            let wide: Vec<u16> = std::ffi::OsStr::new(&title)
+                .encode_wide()
+                .chain(Some(0))
+                .collect();
+            return unsafe { SetConsoleTitleW(wide.as_ptr()) } != 0;
+        }
+    }
+
+    write_sequence(stream, format!("\x1b]2;{}\x1b\\", title).as_bytes())
+}
+
+/// Writes `sequence` to `stream`, which must be stdout or stderr, through
+/// std's handle for it, so that it's ordered with the program's other
+/// output.
+fn write_sequence(stream: Stream, sequence: &[u8]) -> bool {
+    use std::io::Write;
+
+    let written = match stream {
+        Stream::Stdout => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(sequence).and_then(|()| stdout.flush())
+        }
+        Stream::Stderr => std::io::stderr().write_all(sequence),
+        _ => return false,
+    };
+    written.is_ok()
+}
+
+/// Returns `true` if the terminal on `stream` likely supports xterm's title
+/// stack, so that [`push_title`] can save the current title before a
+/// program changes it, and [`pop_title`] can put it back.
+///
+/// Terminals don't report this, and terminfo doesn't describe it; many
+/// which can set the title ignore the stack, leaving the program's title in
+/// place after it exits. So this is decided from an allowlist: xterm itself
+/// (recognized by `XTERM_VERSION`, which xterm sets and its imitators
+/// don't), iTerm2, kitty, WezTerm, Alacritty, and mintty (from
+/// [`term_program`]), and terminals whose `TERM` names foot, kitty, or
+/// alacritty.
+pub fn supports_title_stack(stream: Stream) -> bool {
+    is(stream) && supports_title_stack_with(&SystemEnv)
+}
+
+pub(crate) fn supports_title_stack_with(env: &dyn EnvSource) -> bool {
+    let term = env.get("TERM");
+    if term_is_dumb(term.as_deref()) {
+        return false;
+    }
+    match term_program_in(env) {
+        Some(TermProgram::ITerm2)
+        | Some(TermProgram::Kitty)
+        | Some(TermProgram::WezTerm)
+        | Some(TermProgram::Alacritty)
+        | Some(TermProgram::Mintty) => return true,
+        Some(TermProgram::Other(_)) | None => {}
+        Some(_) => return false,
+    }
+
+    term.map_or(false, |term| {
+        (term.starts_with("xterm") && env.get("XTERM_VERSION").is_some())
+            || ["foot", "kitty", "alacritty"]
+                .iter()
+                .any(|family| term.starts_with(family))
+    })
+}
+
+/// Saves the window title of the terminal on `stream` on its title stack,
+/// with `\x1b[22;0t`, if [`supports_title_stack`] says it's supported,
+/// returning whether it did.
+///
+/// Programs which change the title, such as to show their progress, can
+/// call this first, and [`pop_title`] before they exit. `Stream::Stdin`
+/// isn't an output stream, so it's never used.
+pub fn push_title(stream: Stream) -> bool {
+    stream != Stream::Stdin && supports_title_stack(stream) && write_sequence(stream, b"\x1b[22;0t")
+}
+
+/// Restores the window title of the terminal on `stream` from its title
+/// stack, with `\x1b[23;0t`, if [`supports_title_stack`] says it's
+/// supported, returning whether it did.
+pub fn pop_title(stream: Stream) -> bool {
+    stream != Stream::Stdin && supports_title_stack(stream) && write_sequence(stream, b"\x1b[23;0t")
+}
+
+/// Removes the characters from `title` which could end or extend a title
+/// sequence.
+pub(crate) fn sanitize_title(title: &str) -> String {
+    title.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// Returns `true` if the terminal on `stream` likely supports OSC 8
+/// hyperlinks.
+///
+/// This is decided from an allowlist: iTerm2 3.1 and newer (from
+/// `TERM_PROGRAM_VERSION`), kitty, WezTerm, Windows Terminal, and terminals
+/// built on VTE 0.50 and newer (from `VTE_VERSION`, such as GNOME Terminal).
+pub fn supports_hyperlinks(stream: Stream) -> bool {
+    supports_hyperlinks_in(stream, &SystemEnv)
+}
+
+/// Like [`supports_hyperlinks`], but reads the environment from `env`.
+pub fn supports_hyperlinks_in(stream: Stream, env: &dyn EnvSource) -> bool {
+    resolve_in(stream, env) && supports_hyperlinks_with(env)
+}
+
+pub(crate) fn supports_hyperlinks_with(env: &dyn EnvSource) -> bool {
+    // VTE encodes its version as a number, such as 5002 for 0.50.2.
+    if env
+        .get("VTE_VERSION")
+        .and_then(|version| version.parse::<u32>().ok())
+        .map_or(false, |version| version >= 5000)
+    {
+        return true;
+    }
+
+    match term_program_in(env) {
+        Some(TermProgram::ITerm2) => {
+            version_at_least(env.get("TERM_PROGRAM_VERSION").as_deref(), (3, 1))
+        }
+        Some(TermProgram::Kitty)
+        | Some(TermProgram::WezTerm)
+        | Some(TermProgram::WindowsTerminal) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the terminal on `stream` likely supports iTerm2's
+/// inline image protocol (OSC 1337 `File=`).
+///
+/// This is decided from [`term_program`]: iTerm2 3.0 and newer (from
+/// `TERM_PROGRAM_VERSION`), and WezTerm, which implements the same protocol.
+pub fn supports_iterm_images(stream: Stream) -> bool {
+    is(stream) && supports_iterm_images_with(&SystemEnv)
+}
+
+pub(crate) fn supports_iterm_images_with(env: &dyn EnvSource) -> bool {
+    match term_program_in(env) {
+        Some(TermProgram::ITerm2) => {
+            version_at_least(env.get("TERM_PROGRAM_VERSION").as_deref(), (3, 0))
+        }
+        Some(TermProgram::WezTerm) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the terminal on `stream` likely understands extended
+/// underline styles, such as curly underlines (`\x1b[4:3m`), and underline
+/// colors (`\x1b[58;...m`), as used for highlighting diagnostics.
+///
+/// This is decided from an allowlist: kitty, WezTerm, iTerm2, and Alacritty
+/// (from [`term_program`]), foot and contour (from `TERM`), and terminals
+/// built on VTE 0.51.2 and newer (from `VTE_VERSION`). Other terminals
+/// qualify if their terminfo entry has the `Smulx` extended capability,
+/// which ncurses and tmux use to describe styled underlines.
+pub fn supports_styled_underline(stream: Stream) -> bool {
+    is(stream) && supports_styled_underline_with(&SystemEnv)
+}
+
+pub(crate) fn supports_styled_underline_with(env: &dyn EnvSource) -> bool {
+    let term = env.get("TERM");
+    if term_is_dumb(term.as_deref()) {
+        return false;
+    }
+
+    match term_program_in(env) {
+        Some(TermProgram::Kitty)
+        | Some(TermProgram::WezTerm)
+        | Some(TermProgram::ITerm2)
+        | Some(TermProgram::Alacritty) => return true,
+        _ => {}
+    }
+
+    // VTE encodes its version as a number, such as 5102 for 0.51.2.
+    if env
+        .get("VTE_VERSION")
+        .and_then(|version| version.parse::<u32>().ok())
+        .map_or(false, |version| version >= 5102)
+    {
+        return true;
+    }
+
+    let term = match term {
+        Some(term) => term,
+        None => return false,
+    };
+    if ["foot", "contour"]
+        .iter()
+        .any(|family| term.starts_with(family))
+    {
+        return true;
+    }
+    terminfo_entry(env, &term)
+        .and_then(|entry| terminfo_has_extended(&entry, "Smulx"))
+        .unwrap_or(false)
+}
+
+/// Returns `true` if the terminal on `stream` likely honors DECSCUSR
+/// sequences (`\x1b[N q`), which set the cursor's shape to a block, an
+/// underline, or a bar, blinking or steady, as editors use to show which
+/// mode they're in.
+///
+/// Terminals which don't understand DECSCUSR ignore it without replying,
+/// so support can't be reliably probed at runtime; this is a heuristic. If
+/// `TERM` has a terminfo entry with extended capabilities, this is whether
+/// it has `Ss`, the capability ncurses and tmux use to describe DECSCUSR.
+/// Otherwise, this is decided from an allowlist: kitty, WezTerm, iTerm2,
+/// Alacritty, Windows Terminal, Visual Studio Code, mintty, and tmux (from
+/// [`term_program`]), xterm, foot, and contour (from `TERM`), and terminals
+/// built on VTE 0.39 and newer (from `VTE_VERSION`).
+pub fn supports_cursor_shape(stream: Stream) -> bool {
+    is(stream) && supports_cursor_shape_with(&SystemEnv)
+}
+
+pub(crate) fn supports_cursor_shape_with(env: &dyn EnvSource) -> bool {
+    let term = env.get("TERM");
+    if term_is_dumb(term.as_deref()) {
+        return false;
+    }
+
+    if let Some(has_ss) = term
+        .as_ref()
+        .and_then(|term| terminfo_entry(env, term))
+        .and_then(|entry| terminfo_has_extended(&entry, "Ss"))
+    {
+        return has_ss;
+    }
+
+    match term_program_in(env) {
+        Some(TermProgram::Kitty)
+        | Some(TermProgram::WezTerm)
+        | Some(TermProgram::ITerm2)
+        | Some(TermProgram::Alacritty)
+        | Some(TermProgram::WindowsTerminal)
+        | Some(TermProgram::VsCode)
+        | Some(TermProgram::Mintty)
+        | Some(TermProgram::Tmux) => return true,
+        _ => {}
+    }
+
+    // VTE encodes its version as a number, such as 3900 for 0.39.0.
+    if env
+        .get("VTE_VERSION")
+        .and_then(|version| version.parse::<u32>().ok())
+        .map_or(false, |version| version >= 3900)
+    {
+        return true;
+    }
+
+    term.map_or(false, |term| {
+        ["xterm", "foot", "contour"]
+            .iter()
+            .any(|family| term.starts_with(family))
+    })
+}
+
+/// Returns `true` if the terminal on `stream` likely interprets 8-bit C1
+/// control codes, such as `\x9b` for CSI, as well as their 7-bit forms,
+/// such as `\x1b[`.
+///
+/// Most terminals don't, so this is `false` unless `TERM` names a DEC
+/// terminal from the VT220 on, the first to accept them, or has a terminfo
+/// entry with the extended capability `S8C1T`. It's always `false` under a
+/// UTF-8 locale (from `LC_ALL`, `LC_CTYPE`, or `LANG`): C1 codes aren't
+/// valid UTF-8 on their own, and encoded as UTF-8 they're two bytes, so
+/// terminals decoding UTF-8 either reject them or take them for text, and
+/// sending them is unsafe.
+pub fn supports_8bit_controls(stream: Stream) -> bool {
+    is(stream) && supports_8bit_controls_with(&SystemEnv)
+}
+
+pub(crate) fn supports_8bit_controls_with(env: &dyn EnvSource) -> bool {
+    let term = match env.get("TERM") {
+        Some(term) if !term_is_dumb(Some(&term)) => term,
+        _ => return false,
+    };
+    if locale_is_utf8(env) {
+        return false;
+    }
+
+    if terminfo_entry(env, &term)
+        .and_then(|entry| terminfo_has_extended(&entry, "S8C1T"))
+        .unwrap_or(false)
+    {
+        return true;
+    }
+    [
+        "vt220", "vt320", "vt340", "vt420", "vt510", "vt520", "vt525",
+    ]
+    .iter()
+    .any(|family| term.starts_with(family))
+}
+
+/// Returns `true` if the terminal on `stream` likely implements the DEC
+/// rectangular area operations, such as DECFRA (`\x1b[...$x`) to fill an
+/// area and DECCRA (`\x1b[...$v`) to copy one, which let full-screen
+/// programs redraw large areas with a single sequence.
+///
+/// These came with the VT420, and few terminals implement them. Terminfo
+/// doesn't describe them, and terminals which don't implement them ignore
+/// them without replying, so this is a heuristic, and it's `false` unless
+/// the terminal is on an allowlist: xterm itself (recognized by
+/// `XTERM_VERSION`, which xterm sets and its imitators don't), whose
+/// default terminal ID is a VT420, Windows Terminal (from
+/// [`term_program`]), contour and mlterm (from `TERM`), and DEC terminals
+/// from the VT420 on. A terminal's Primary Device Attributes, from
+/// `device_attributes` with the `query` feature, include `28` when it
+/// implements them, which is more reliable where it's possible to ask.
+pub fn supports_rectangular_ops(stream: Stream) -> bool {
+    is(stream) && supports_rectangular_ops_with(&SystemEnv)
+}
+
+pub(crate) fn supports_rectangular_ops_with(env: &dyn EnvSource) -> bool {
+    let term = env.get("TERM");
+    if term_is_dumb(term.as_deref()) {
+        return false;
+    }
+
+    if term_program_in(env) == Some(TermProgram::WindowsTerminal) {
+        return true;
+    }
+
+    term.map_or(false, |term| {
+        (term.starts_with("xterm") && env.get("XTERM_VERSION").is_some())
+            || ["contour", "mlterm", "vt420", "vt510", "vt520", "vt525"]
+                .iter()
+                .any(|family| term.starts_with(family))
+    })
+}
+
+/// Returns `true` if the locale's character encoding, from the first of
+/// `LC_ALL`, `LC_CTYPE`, and `LANG` which is set and non-empty, is UTF-8.
+fn locale_is_utf8(env: &dyn EnvSource) -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| env.get(name))
+        .find(|value| !value.is_empty())
+        .map_or(false, |locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+/// The characters which can safely be written to a stream, as chosen by
+/// [`safe_charset`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Charset {
+    /// Any Unicode text, including box-drawing characters, arrows, and so
+    /// on.
+    Unicode,
+    /// Only ASCII, so tables and trees should be drawn with `+`, `-`, `|`,
+    /// and the like.
+    Ascii,
+}
+
+/// Returns the characters which can be written to `stream` without being
+/// mangled, for deciding whether tables and trees are drawn with Unicode
+/// box-drawing characters or with ASCII.
+///
+/// This is [`Charset::Ascii`] unless `stream` is a terminal, since output
+/// sent to a pipe or file may be decoded with any encoding, or none. On
+/// Unix-family platforms, and for MSYS ptys on Windows, it's
+/// [`Charset::Unicode`] if the locale (from `LC_ALL`, `LC_CTYPE`, or `LANG`)
+/// is UTF-8, so the `C` and `POSIX` locales, and an unset one, give
+/// [`Charset::Ascii`]. Windows consoles give [`Charset::Unicode`] if their
+/// output code page is UTF-8 (65001), and [`Charset::Ascii`] under the
+/// legacy code pages.
+pub fn safe_charset(stream: Stream) -> Charset {
+    if !is(stream) {
+        return Charset::Ascii;
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::GetConsoleOutputCP;
+
+        // The UTF-8 code page, `CP_UTF8`.
+        const CP_UTF8: u32 = 65001;
+
+        if console_mode(stream.as_raw_handle()).is_some() {
+            return if unsafe { GetConsoleOutputCP() } == CP_UTF8 {
+                Charset::Unicode
+            } else {
+                Charset::Ascii
+            };
+        }
+    }
+
+    safe_charset_with(&SystemEnv)
+}
+
+pub(crate) fn safe_charset_with(env: &dyn EnvSource) -> Charset {
+    if locale_is_utf8(env) {
+        Charset::Unicode
+    } else {
+        Charset::Ascii
+    }
+}
+
+/// Returns `true` if the terminal on `stream` likely supports drawing lines
+/// and boxes with the DEC Special Graphics character set, selected with
+/// `\x1b(0`, as a fallback where the Unicode box-drawing characters aren't
+/// available, such as on the Linux console with some fonts.
+///
+/// If `TERM` has a terminfo entry, this is whether the entry has the `acsc`
+/// capability, which maps the line-drawing characters; callers should send
+/// its `enacs` string first, if it has one. Otherwise, this is decided from
+/// an allowlist of terminal families descended from the VT100, such as
+/// xterm, the Linux console, screen, and tmux. On Windows, consoles qualify
+/// if they have virtual-terminal processing enabled.
+pub fn supports_acs_line_drawing(stream: Stream) -> bool {
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::System::Console::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+
+        if let Some(mode) = console_mode(stream.as_raw_handle()) {
+            return mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0;
+        }
+    }
+
+    is(stream) && supports_acs_line_drawing_with(&SystemEnv)
+}
+
+pub(crate) fn supports_acs_line_drawing_with(env: &dyn EnvSource) -> bool {
+    // The index of `acsc` among the standard string capabilities.
+    const ACSC: usize = 146;
+
+    let term = match env.get("TERM") {
+        Some(term) if term != "dumb" => term,
+        _ => return false,
+    };
+    if let Some(has_acsc) =
+        terminfo_entry(env, &term).and_then(|entry| terminfo_has_string(&entry, ACSC))
+    {
+        return has_acsc;
+    }
+    [
+        "xterm",
+        "linux",
+        "vt100",
+        "vt102",
+        "vt220",
+        "vt320",
+        "screen",
+        "tmux",
+        "rxvt",
+        "putty",
+        "konsole",
+        "gnome",
+        "vte",
+        "alacritty",
+        "kitty",
+        "foot",
+    ]
+    .iter()
+    .any(|family| term.starts_with(family))
+}
+
+/// Returns `true` if sending a full reset, RIS (`\x1bc`), to the terminal on
+/// `stream` is appropriate, as opposed to a soft reset, DECSTR (`\x1b[!p`),
+/// which leaves the screen and scrollback alone.
+///
+/// This requires `stream` to be a terminal that the process has to itself:
+/// on Unix-family platforms, if it's the controlling terminal, the process
+/// must be in its foreground process group, since a background job resetting
+/// the terminal disrupts the foreground one. It's also `false` inside a
+/// multiplexer, recognized by `TMUX`, `STY` (GNU screen), `ZELLIJ`, or a
+/// `TERM` of the `screen` or `tmux` families: multiplexers handle RIS
+/// themselves, inconsistently, and some pass it on to the outer terminal,
+/// resetting it under every other pane.
+pub fn safe_to_reset(stream: Stream) -> bool {
+    #[cfg(unix)]
+    let foreground = in_foreground(stream);
+    #[cfg(not(unix))]
+    let foreground = true;
+
+    safe_to_reset_with(&SystemEnv, is(stream), foreground)
+}
+
+pub(crate) fn safe_to_reset_with(env: &dyn EnvSource, tty: bool, foreground: bool) -> bool {
+    let multiplexed = ["TMUX", "STY", "ZELLIJ"]
+        .iter()
+        .any(|name| env.get(name).is_some())
+        || env.get("TERM").map_or(false, |term| {
+            term.starts_with("screen") || term.starts_with("tmux")
+        });
+    tty && foreground && !multiplexed
+}
+
+/// Returns the distance between the tab stops of the terminal `stream`
+/// refers to, in columns, or `None` if it isn't a terminal.
+///
+/// Formatters which emit hard tabs can use this to align columns. This is
+/// the `it` (`init_tabs`) capability of the terminfo entry for `TERM`, when
+/// there is one and it has that capability, and otherwise `8`, which is
+/// where nearly every terminal starts out. Programs can move the tab stops,
+/// and this can't tell if they have.
+pub fn tab_width(stream: Stream) -> Option<u8> {
+    if !is(stream) {
+        return None;
+    }
+    Some(tab_width_with(&SystemEnv))
+}
+
+pub(crate) fn tab_width_with(env: &dyn EnvSource) -> u8 {
+    use core::convert::TryFrom;
+
+    // The index of `it` among the standard numeric capabilities.
+    const INIT_TABS: usize = 1;
+
+    env.get("TERM")
+        .and_then(|term| terminfo_entry(env, &term))
+        .and_then(|entry| terminfo_number(&entry, INIT_TABS))
+        .and_then(|width| u8::try_from(width).ok())
+        .filter(|&width| width != 0)
+        .unwrap_or(8)
+}
+
+/// Reads the compiled terminfo entry for `term`, searching the same
+/// directories as ncurses: `$TERMINFO`, `~/.terminfo`, `$TERMINFO_DIRS`, and
+/// then the usual system directories.
+fn terminfo_entry(env: &dyn EnvSource, term: &str) -> Option<Vec<u8>> {
+    use std::path::PathBuf;
+
+    // Don't let `TERM` name a path outside the terminfo directories.
+    if term.contains('/') || term.contains('\\') || term.starts_with('.') {
+        return None;
+    }
+    let first = term.chars().next()?;
+
+    let mut dirs = Vec::new();
+    dirs.extend(env.get("TERMINFO").map(PathBuf::from));
+    dirs.extend(
+        env.get("HOME")
+            .map(|home| PathBuf::from(home).join(".terminfo")),
+    );
+    if let Some(list) = env.get("TERMINFO_DIRS") {
+        dirs.extend(
+            list.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .iter()
+            .map(PathBuf::from),
+    );
+
+    // Entries live in a directory named by their first character, or on
+    // macOS, by its hexadecimal code.
+    let subdirs = [first.to_string(), format!("{:x}", first as u32)];
+    dirs.iter()
+        .flat_map(|dir| {
+            subdirs
+                .iter()
+                .map(move |subdir| dir.join(subdir).join(term))
+        })
+        .find_map(|path| std::fs::read(path).ok())
+}
+
+/// Returns whether `entry`, a compiled terminfo entry in the format described
+/// by term(5), has the standard string capability numbered `index`, or
+/// `None` if it can't be parsed.
+pub(crate) fn terminfo_has_string(entry: &[u8], index: usize) -> Option<bool> {
+    let short = |offset| terminfo_short(entry, offset);
+
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers, strings) = (short(2)?, short(4)?, short(6)?, short(8)?);
+    if index >= strings {
+        return Some(false);
+    }
+    let mut offset = 12 + names + bools;
+    offset += offset % 2;
+    offset += numbers * number_size;
+    // Absent and cancelled capabilities have offsets of -1 and -2.
+    Some(short(offset + index * 2)? < 0xfffe)
+}
+
+/// Returns the value of standard numeric capability `index` in `entry`, a
+/// compiled terminfo entry in the format described by term(5), or `None` if
+/// it's absent or the entry can't be parsed.
+pub(crate) fn terminfo_number(entry: &[u8], index: usize) -> Option<u32> {
+    use core::convert::TryFrom;
+
+    let short = |offset| terminfo_short(entry, offset);
+
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers) = (short(2)?, short(4)?, short(6)?);
+    if index >= numbers {
+        return None;
+    }
+    let mut offset = 12 + names + bools;
+    offset += offset % 2;
+    offset += index * number_size;
+    let bytes = entry.get(offset..offset + number_size)?;
+    // Absent and cancelled capabilities are negative.
+    if number_size == 2 {
+        let value = i16::from_le_bytes([bytes[0], bytes[1]]);
+        u32::try_from(value).ok()
+    } else {
+        let value = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        u32::try_from(value).ok()
+    }
+}
+
+/// Reads the little-endian 16-bit number at `offset` in `entry`.
+fn terminfo_short(entry: &[u8], offset: usize) -> Option<usize> {
+    entry
+        .get(offset..offset + 2)
+        .map(|bytes| usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+}
+
+/// Returns whether `entry`, a compiled terminfo entry in the format described
+/// by term(5), has the extended capability `name`, or `None` if it can't be
+/// parsed or has no extended capabilities.
+pub(crate) fn terminfo_has_extended(entry: &[u8], name: &str) -> Option<bool> {
+    let short = |offset| terminfo_short(entry, offset);
+
+    // The legacy format has 16-bit numbers and the extended one 32-bit ones.
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, bools, numbers, strings, table) =
+        (short(2)?, short(4)?, short(6)?, short(8)?, short(10)?);
+
+    // Skip the standard capabilities. Numbers and everything after them are
+    // aligned to even offsets.
+    let mut offset = 12 + names + bools;
+    offset += offset % 2;
+    offset += numbers * number_size + strings * 2 + table;
+    offset += offset % 2;
+
+    let (ext_bools, ext_numbers, ext_strings, ext_table) = (
+        short(offset)?,
+        short(offset + 2)?,
+        short(offset + 4)?,
+        short(offset + 8)?,
+    );
+    offset += 10 + ext_bools;
+    offset += offset % 2;
+    // The extended strings have offsets for their values, followed by
+    // offsets for the names of all the extended capabilities.
+    offset += ext_numbers * number_size + (2 * ext_strings + ext_bools + ext_numbers) * 2;
+
+    // The table holds the string values followed by the names, all
+    // NUL-terminated.
+    let table = entry.get(offset..offset + ext_table)?;
+    Some(
+        table
+            .split(|&byte| byte == 0)
+            .any(|entry| entry == name.as_bytes()),
+    )
+}
+
+/// Returns `true` if `version`, a dotted version string such as `3.4.19`,
+/// is at least `major.minor`.
+pub(crate) fn version_at_least(version: Option<&str>, (major, minor): (u32, u32)) -> bool {
+    let mut parts = match version {
+        Some(version) => version.split('.').map(|part| {
+            // Ignore suffixes, as in `3.5.0beta1`.
+            let digits = part
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(part.len());
+            part[..digits].parse::<u32>().ok()
+        }),
+        None => return false,
+    };
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(have_major), have_minor) => (have_major, have_minor.unwrap_or(0)) >= (major, minor),
+        (None, _) => false,
+    }
+}
+
+/// A set of terminal capabilities, as returned by [`capabilities`].
+///
+/// Sets can be combined with `|` and intersected with `&`, and tested with
+/// [`Capabilities::contains`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// The 16 basic ANSI colors.
+    pub const COLOR_16: Self = Self(1 << 0);
+    /// The 256-color xterm palette.
+    pub const COLOR_256: Self = Self(1 << 1);
+    /// 24-bit RGB color.
+    pub const TRUECOLOR: Self = Self(1 << 2);
+    /// ANSI escape sequences in general, such as for moving the cursor.
+    pub const ANSI: Self = Self(1 << 3);
+    /// Mouse reporting; see [`supports_mouse`].
+    pub const MOUSE: Self = Self(1 << 4);
+    /// Bracketed paste; see [`supports_bracketed_paste`].
+    pub const BRACKETED_PASTE: Self = Self(1 << 5);
+    /// OSC 8 hyperlinks; see [`supports_hyperlinks`].
+    pub const HYPERLINKS: Self = Self(1 << 6);
+    /// Setting the window title; see [`supports_title`].
+    pub const TITLE: Self = Self(1 << 7);
+    /// Sixel graphics.
+    pub const SIXEL: Self = Self(1 << 8);
+
+    /// Returns the empty set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the set's bits.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if the set has no capabilities.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if the set has all of the capabilities in `other`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Adds or removes the capabilities in `other`.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl core::ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl core::ops::BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl core::ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// Returns the capabilities of the terminal on `stream`, as reported by the
+/// individual checks such as [`supports_mouse`] and [`supports_title`].
+///
+/// The color flags are set when the `color` feature is enabled, from
+/// `color_level`; each level includes the ones below it. `ANSI` is set for
+/// terminals whose `TERM` isn't `dumb`. `SIXEL` is only set when the `query`
+/// feature is enabled, in which case this queries the terminal and may wait
+/// for a short timeout.
+pub fn capabilities(stream: Stream) -> Capabilities {
+    let tty = is(stream);
+    let mut capabilities = Capabilities::empty();
+
+    #[cfg(feature = "color")]
+    {
+        let level = color_level_with(&SystemEnv, tty);
+        capabilities.set(Capabilities::COLOR_16, level >= ColorLevel::Ansi16);
+        capabilities.set(Capabilities::COLOR_256, level >= ColorLevel::Ansi256);
+        capabilities.set(Capabilities::TRUECOLOR, level >= ColorLevel::TrueColor);
+    }
+
+    capabilities.set(
+        Capabilities::ANSI,
+        tty && !term_is_dumb(env_var("TERM").as_deref()),
+    );
+    capabilities.set(Capabilities::MOUSE, supports_mouse(stream));
+    capabilities.set(
+        Capabilities::BRACKETED_PASTE,
+        supports_bracketed_paste(stream),
+    );
+    capabilities.set(Capabilities::HYPERLINKS, supports_hyperlinks(stream));
+    capabilities.set(Capabilities::TITLE, supports_title(stream));
+
+    #[cfg(feature = "query")]
+    capabilities.set(Capabilities::SIXEL, supports_sixel(stream));
+
+    capabilities
+}
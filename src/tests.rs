@@ -0,0 +1,5230 @@
+extern crate std;
+
+#[cfg(not(target_os = "unknown"))]
+use super::IsTerminal;
+
+/// Runs `f` in a forked child process, so that it can rearrange the
+/// standard streams, and returns whether it returned `true`.
+#[cfg(unix)]
+fn in_child(f: impl FnOnce() -> bool) -> bool {
+    unsafe {
+        match libc::fork() {
+            -1 => panic!("fork failed"),
+            0 => {
+                let ok = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+                libc::_exit(if let Ok(true) = ok { 0 } else { 1 })
+            }
+            pid => {
+                let mut status = 0;
+                assert_eq!(libc::waitpid(pid, &mut status, 0), pid);
+                libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0
+            }
+        }
+    }
+}
+
+/// Returns an environment which has only `vars`.
+#[cfg(feature = "std")]
+fn env(vars: &[(&str, &str)]) -> std::collections::HashMap<String, String> {
+    vars.iter()
+        .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// Plays the terminal on the master end of a pty: waits for `request`,
+/// then writes `reply`, and returns the master so that the caller can
+/// keep it open until the reply has been read.
+#[cfg(all(unix, feature = "query"))]
+fn reply_to(master: io_lifetimes::OwnedFd, request: &[u8], reply: &[u8]) -> io_lifetimes::OwnedFd {
+    let mut received = std::vec::Vec::new();
+    while !received.ends_with(request) {
+        let mut buf = [0; 64];
+        let n = rustix::io::read(&master, &mut buf).unwrap();
+        received.extend_from_slice(&buf[..n]);
+    }
+    rustix::io::write(&master, reply).unwrap();
+    master
+}
+
+/// Opens a new pseudoterminal, returning the master and slave ends.
+#[cfg(unix)]
+fn pty() -> (io_lifetimes::OwnedFd, io_lifetimes::OwnedFd) {
+    use std::os::unix::io::FromRawFd;
+
+    let (mut master, mut slave) = (0, 0);
+    unsafe {
+        assert_eq!(
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            ),
+            0
+        );
+        (
+            io_lifetimes::OwnedFd::from_raw_fd(master),
+            io_lifetimes::OwnedFd::from_raw_fd(slave),
+        )
+    }
+}
+
+#[test]
+#[cfg(feature = "is-terminal-compat")]
+fn compat() {
+    use super::compat::{is_terminal, IsTerminal};
+
+    assert_eq!(
+        is_terminal(std::io::stdin()),
+        std::io::stdin().is_terminal()
+    );
+    assert_eq!(
+        is_terminal(std::io::stdout()),
+        std::io::stdout().is_terminal()
+    );
+    assert_eq!(
+        is_terminal(std::io::stderr().lock()),
+        std::io::stderr().is_terminal()
+    );
+    assert!(!is_terminal(std::fs::File::open("Cargo.toml").unwrap()));
+}
+
+#[test]
+#[cfg(feature = "testutil")]
+fn output_ttys() {
+    use super::testutil::set_override;
+    use super::{any_output_is_tty, is_all, is_any, output_is_tty, Stream};
+
+    for &(stdout, stderr) in &[(true, true), (true, false), (false, true), (false, false)] {
+        set_override(Stream::Stdout, Some(stdout));
+        set_override(Stream::Stderr, Some(stderr));
+        assert_eq!(output_is_tty(), stdout && stderr, "{} {}", stdout, stderr);
+        assert_eq!(
+            any_output_is_tty(),
+            stdout || stderr,
+            "{} {}",
+            stdout,
+            stderr
+        );
+    }
+    set_override(Stream::Stdout, None);
+    set_override(Stream::Stderr, None);
+
+    assert!(is_all(&[]));
+    assert!(!is_any(&[]));
+}
+
+#[test]
+#[cfg(feature = "testutil")]
+fn testutil_fixtures() {
+    use super::testutil::{with_env, with_fake_tty};
+    use super::{is, EnvSource, Stream, SystemEnv};
+
+    let stdout = is(Stream::Stdout);
+    with_fake_tty(Stream::Stdout, || {
+        assert!(is(Stream::Stdout));
+        with_fake_tty(Stream::Stdin, || assert!(is(Stream::Stdin)));
+    });
+    assert_eq!(is(Stream::Stdout), stdout);
+
+    // The override is restored even if the closure panics.
+    assert!(std::panic::catch_unwind(|| {
+        with_fake_tty(Stream::Stderr, || panic!("in with_fake_tty"))
+    })
+    .is_err());
+    assert_eq!(is(Stream::Stderr), std::io::stderr().is_terminal());
+
+    let path = SystemEnv.get("PATH");
+    with_env(&[("TERM", "xterm-kitty")], || {
+        assert_eq!(SystemEnv.get("TERM").as_deref(), Some("xterm-kitty"));
+        assert_eq!(SystemEnv.get("PATH"), None);
+        with_env(&[], || assert_eq!(SystemEnv.get("TERM"), None));
+        assert_eq!(SystemEnv.get("TERM").as_deref(), Some("xterm-kitty"));
+    });
+    assert_eq!(SystemEnv.get("PATH"), path);
+
+    // Other threads don't see the overrides.
+    with_env(&[], || {
+        with_fake_tty(Stream::Stdout, || {
+            std::thread::spawn(move || {
+                assert_eq!(SystemEnv.get("PATH"), path);
+                assert_eq!(is(Stream::Stdout), stdout);
+            })
+            .join()
+            .unwrap();
+        })
+    });
+}
+
+#[test]
+#[cfg(all(feature = "testutil", feature = "color"))]
+fn testutil_color() {
+    use super::testutil::{with_env, with_fake_tty};
+    use super::{color_level, ColorLevel, Stream};
+
+    with_fake_tty(Stream::Stdout, || {
+        with_env(&[("TERM", "xterm-256color")], || {
+            assert_eq!(color_level(Stream::Stdout), ColorLevel::Ansi256);
+        });
+        with_env(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")], || {
+            assert_eq!(color_level(Stream::Stdout), ColorLevel::None);
+        });
+    });
+
+    // Stdin and stderr are terminals, but stdout is redirected.
+    with_fake_tty(Stream::Stdin, || {
+        with_fake_tty(Stream::Stderr, || {
+            with_env(&[("TERM", "xterm")], || {
+                let caps = super::interactive_caps();
+                if !std::io::stdout().is_terminal() {
+                    assert_eq!(caps.prompt_stream, Some(Stream::Stderr));
+                }
+                assert_eq!(caps.progress_stream, Some(Stream::Stderr));
+            })
+        })
+    });
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn forced_ttys() {
+    use super::{forced_tty, is, Stream};
+
+    assert_eq!(forced_tty(&env(&[("ATTY_FORCE", "1")])), Some(true));
+    assert_eq!(forced_tty(&env(&[("ATTY_FORCE", "yes")])), Some(true));
+    assert_eq!(forced_tty(&env(&[("ATTY_FORCE", "false")])), Some(false));
+    assert_eq!(forced_tty(&env(&[("ATTY_FORCE", "sometimes")])), None);
+    assert_eq!(forced_tty(&env(&[])), None);
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        std::env::set_var("ATTY_FORCE", "1");
+        let forced = is(Stream::Stdout);
+        std::env::set_var("ATTY_FORCE", "0");
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        forced && !is(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "testutil", feature = "cache"))]
+fn tty_resolution_precedence() {
+    use super::testutil::{set_override, with_env, with_fake_tty};
+    use super::{invalidate_tty_cache, is, is_cached, Stream};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        let mut ok = true;
+        for &overridden in &[None, Some(true), Some(false)] {
+            for &force in &[None, Some("1"), Some("0")] {
+                for &cached in &[None, Some(true), Some(false)] {
+                    // Stdout ends up a pipe, but `is_cached` may have seen it
+                    // as something else first.
+                    set_override(Stream::Stdout, None);
+                    invalidate_tty_cache();
+                    if let Some(cached) = cached {
+                        with_env(&[], || {
+                            if cached {
+                                rustix::io::dup2_stdout(&slave).unwrap();
+                            }
+                            is_cached(Stream::Stdout);
+                        });
+                    }
+                    rustix::io::dup2_stdout(&writer).unwrap();
+                    set_override(Stream::Stdout, overridden);
+
+                    let vars: Vec<_> = force.iter().map(|&force| ("ATTY_FORCE", force)).collect();
+                    let forced = force.map(|force| force == "1");
+                    let expected = overridden.or(forced);
+                    with_env(&vars, || {
+                        let fresh = is(Stream::Stdout);
+                        let remembered = is_cached(Stream::Stdout);
+                        if fresh != expected.unwrap_or(false)
+                            || remembered != expected.or(cached).unwrap_or(false)
+                        {
+                            std::eprintln!(
+                                "override {:?}, ATTY_FORCE {:?}, cached {:?}: is {}, is_cached {}",
+                                overridden,
+                                force,
+                                cached,
+                                fresh,
+                                remembered
+                            );
+                            ok = false;
+                        }
+                    });
+                }
+            }
+        }
+
+        // `with_fake_tty` beats `set_override`.
+        set_override(Stream::Stdout, Some(false));
+        ok &= with_fake_tty(Stream::Stdout, || is(Stream::Stdout));
+        set_override(Stream::Stdout, None);
+
+        // Only the OS's answer was cached, not the overrides.
+        invalidate_tty_cache();
+        with_env(&[("ATTY_FORCE", "1")], || is_cached(Stream::Stdout));
+        ok && !with_env(&[], || is_cached(Stream::Stdout))
+    }));
+}
+
+#[test]
+#[cfg(all(
+    feature = "testutil",
+    any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "freebsd"
+    )
+))]
+fn testutil_pty() {
+    let (master, slave) = super::testutil::make_pty().unwrap();
+    assert!(slave.is_terminal());
+    assert!(master.is_terminal());
+    rustix::io::write(&slave, b"hello").unwrap();
+    let mut buf = [0; 5];
+    rustix::io::read(&master, &mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn terminal_writers() {
+    use super::is_terminal_writer;
+
+    assert_eq!(
+        is_terminal_writer(&std::io::stdout()),
+        Some(std::io::stdout().is_terminal())
+    );
+    let boxed: Box<dyn std::any::Any> = Box::new(std::io::stderr());
+    assert_eq!(
+        is_terminal_writer(&*boxed),
+        Some(std::io::stderr().is_terminal())
+    );
+    assert_eq!(is_terminal_writer(&Vec::<u8>::new()), None);
+    assert_eq!(
+        is_terminal_writer(&std::io::BufWriter::new(std::io::stdout())),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(windows, not(feature = "win-minimal")))]
+fn stdin() {
+    assert_eq!(
+        atty::is(atty::Stream::Stdin),
+        std::io::stdin().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(all(windows, not(feature = "win-minimal")))]
+fn stdout() {
+    assert_eq!(
+        atty::is(atty::Stream::Stdout),
+        std::io::stdout().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(all(windows, not(feature = "win-minimal")))]
+fn stderr() {
+    assert_eq!(
+        atty::is(atty::Stream::Stderr),
+        std::io::stderr().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stdin() {
+    assert_eq!(
+        atty::is(atty::Stream::Stdin),
+        rustix::io::stdin().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stdout() {
+    assert_eq!(
+        atty::is(atty::Stream::Stdout),
+        rustix::io::stdout().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stderr() {
+    assert_eq!(
+        atty::is(atty::Stream::Stderr),
+        rustix::io::stderr().is_terminal()
+    )
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stdin_vs_libc() {
+    unsafe {
+        assert_eq!(
+            libc::isatty(libc::STDIN_FILENO) != 0,
+            rustix::io::stdin().is_terminal()
+        )
+    }
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stdout_vs_libc() {
+    unsafe {
+        assert_eq!(
+            libc::isatty(libc::STDOUT_FILENO) != 0,
+            rustix::io::stdout().is_terminal()
+        )
+    }
+}
+
+#[test]
+#[cfg(any(unix, target_os = "wasi"))]
+fn stderr_vs_libc() {
+    unsafe {
+        assert_eq!(
+            libc::isatty(libc::STDERR_FILENO) != 0,
+            rustix::io::stderr().is_terminal()
+        )
+    }
+}
+
+#[test]
+#[cfg(not(any(target_os = "unknown", all(windows, feature = "win-minimal"))))]
+fn stream() {
+    use super::{is, Stream};
+
+    assert_eq!(atty::is(atty::Stream::Stdin), is(Stream::Stdin));
+    assert_eq!(atty::is(atty::Stream::Stdout), is(Stream::Stdout));
+    assert_eq!(atty::is(atty::Stream::Stderr), is(Stream::Stderr));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn mouse_needs_a_capable_terminal() {
+    use super::supports_mouse_with;
+
+    assert!(!supports_mouse_with(true, Some("dumb")));
+    assert!(!supports_mouse_with(false, Some("xterm-256color")));
+    assert!(supports_mouse_with(true, Some("xterm-256color")));
+    assert_eq!(supports_mouse_with(true, None), cfg!(windows));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn term_program_from_env() {
+    use super::{term_program_in, TermProgram};
+
+    assert_eq!(term_program_in(&env(&[])), None);
+    assert_eq!(
+        term_program_in(&env(&[("TERM_PROGRAM", "iTerm.app")])),
+        Some(TermProgram::ITerm2)
+    );
+    assert_eq!(
+        term_program_in(&env(&[("TERM_PROGRAM", "tmux"), ("KITTY_WINDOW_ID", "1")])),
+        Some(TermProgram::Tmux)
+    );
+    assert_eq!(
+        term_program_in(&env(&[("TERM", "xterm-kitty")])),
+        Some(TermProgram::Kitty)
+    );
+    assert_eq!(
+        term_program_in(&env(&[("TERM_PROGRAM", "ghostty")])),
+        Some(TermProgram::Other("ghostty".into()))
+    );
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn clipboard_osc52_allowlist() {
+    use super::{supports_clipboard_osc52_with, TermProgram};
+
+    assert!(supports_clipboard_osc52_with(
+        true,
+        Some(&TermProgram::Kitty)
+    ));
+    assert!(supports_clipboard_osc52_with(
+        true,
+        Some(&TermProgram::ITerm2)
+    ));
+    assert!(!supports_clipboard_osc52_with(
+        false,
+        Some(&TermProgram::Kitty)
+    ));
+    assert!(!supports_clipboard_osc52_with(
+        true,
+        Some(&TermProgram::AppleTerminal)
+    ));
+    assert!(!supports_clipboard_osc52_with(true, None));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn outer_host_markers() {
+    use super::{outer_host_with, OuterHost};
+
+    assert_eq!(outer_host_with(Some(true), true), OuterHost::ConPtyUnderWt);
+    assert_eq!(outer_host_with(Some(true), false), OuterHost::Wsl);
+    assert_eq!(outer_host_with(Some(false), true), OuterHost::ConPtyUnderWt);
+    assert_eq!(outer_host_with(Some(false), false), OuterHost::Native);
+    assert_eq!(outer_host_with(None, false), OuterHost::Unknown);
+}
+
+#[test]
+#[cfg(unix)]
+fn line_buffered_follows_tty() {
+    use super::{is, is_line_buffered, Stream};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&slave).unwrap();
+        is_line_buffered(Stream::Stdout) == is(Stream::Stdout)
+            && is_line_buffered(Stream::Stdout)
+            && !is_line_buffered(Stream::Stderr)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        is_line_buffered(Stream::Stdout) == is(Stream::Stdout) && !is_line_buffered(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn line_buffered_writers() {
+    use super::{line_buffered_writer, Stream};
+    use rustix::io::{poll, read, PollFd, PollFlags};
+    use std::io::Write;
+
+    // Returns whatever can be read from `fd` within `millis`.
+    fn available(fd: &io_lifetimes::OwnedFd, millis: i32) -> Vec<u8> {
+        let mut received = Vec::new();
+        while poll(&mut [PollFd::new(fd, PollFlags::IN)], millis).unwrap() != 0 {
+            let mut buf = [0; 64];
+            let n = read(fd, &mut buf).unwrap();
+            received.extend_from_slice(&buf[..n]);
+            if n == 0 {
+                break;
+            }
+        }
+        received
+    }
+
+    // Complete lines are flushed to a terminal right away.
+    assert!(in_child(|| {
+        let (master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let mut writer = line_buffered_writer(Stream::Stdout);
+        writer.write_all(b"first\nsecond").unwrap();
+        let received = available(&master, 200);
+        received.starts_with(b"first") && !received.ends_with(b"second")
+    }));
+
+    // Nothing is flushed to a pipe until the buffer is.
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        drop(writer);
+        let mut writer = line_buffered_writer(Stream::Stdout);
+        writer.write_all(b"first\nsecond").unwrap();
+        let early = available(&reader, 100);
+        writer.flush().unwrap();
+        early.is_empty() && available(&reader, 100) == b"first\nsecond"
+    }));
+
+    assert!(std::panic::catch_unwind(|| line_buffered_writer(Stream::Stdin)).is_err());
+}
+
+#[async_std::test]
+#[cfg(all(unix, feature = "async-std"))]
+async fn async_std_readable_when_pipe_has_data() {
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    let (reader, writer) = rustix::io::pipe().unwrap();
+    let write = async_std::task::spawn(async move {
+        async_std::task::sleep(Duration::from_millis(50)).await;
+        rustix::io::write(&writer, b"ready").unwrap();
+        writer
+    });
+    super::readable_async_std_fd(reader.as_raw_fd())
+        .await
+        .unwrap();
+    let _writer = write.await;
+
+    // The pipe is still open, and still has the data in it.
+    let mut buf = [0; 5];
+    assert_eq!(rustix::io::read(&reader, &mut buf).unwrap(), 5);
+    assert_eq!(&buf, b"ready");
+}
+
+#[tokio::test]
+#[cfg(all(unix, feature = "tokio"))]
+async fn readable_when_pipe_has_data() {
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    let (reader, writer) = rustix::io::pipe().unwrap();
+    let wait = super::readable_fd(reader.as_raw_fd());
+    let write = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        rustix::io::write(&writer, b"ready").unwrap();
+    };
+    let (result, ()) = tokio::join!(wait, write);
+    result.unwrap();
+
+    // The pipe is still open, and still has the data in it.
+    let mut buf = [0; 5];
+    assert_eq!(rustix::io::read(&reader, &mut buf).unwrap(), 5);
+    assert_eq!(&buf, b"ready");
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn pager_names() {
+    use super::is_pager;
+
+    assert!(is_pager("less", None));
+    assert!(is_pager("bat", Some("less -R")));
+    assert!(is_pager(
+        "moar",
+        Some("/usr/local/bin/moar --no-linenumbers")
+    ));
+    assert!(!is_pager("moar", None));
+    assert!(!is_pager("grep", Some("less")));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn pipe_read_by_less() {
+    use io_lifetimes::AsFd;
+
+    let (reader, writer) = rustix::io::pipe().unwrap();
+    let (ready_reader, ready_writer) = rustix::io::pipe().unwrap();
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0);
+    if pid == 0 {
+        // Become a process named `less` which holds the read end.
+        unsafe {
+            drop(writer);
+            libc::prctl(libc::PR_SET_NAME, b"less\0".as_ptr());
+            rustix::io::write(&ready_writer, b"x").ok();
+            loop {
+                libc::pause();
+            }
+        }
+    }
+    drop(reader);
+    drop(ready_writer);
+    let mut buf = [0];
+    rustix::io::read(&ready_reader, &mut buf).unwrap();
+
+    let readers = super::pipe_readers(writer.as_fd());
+    unsafe {
+        libc::kill(pid, libc::SIGKILL);
+        libc::waitpid(pid, std::ptr::null_mut(), 0);
+    }
+    assert!(readers.unwrap().iter().any(|name| name == "less"));
+}
+
+#[test]
+#[cfg(all(feature = "color", feature = "term"))]
+fn term_program_colors() {
+    use super::{color_level_with, ColorLevel::*};
+
+    let xterm = ("TERM", "xterm-256color");
+    #[rustfmt::skip]
+    let cases: &[(&[(&str, &str)], _)] = &[
+        (&[xterm, ("TERM_PROGRAM", "iTerm.app"), ("TERM_PROGRAM_VERSION", "3.4.19")], TrueColor),
+        (&[xterm, ("TERM_PROGRAM", "iTerm.app"), ("TERM_PROGRAM_VERSION", "2.9.2")], Ansi256),
+        (&[xterm, ("TERM_PROGRAM", "WezTerm")], TrueColor),
+        (&[("TERM", "xterm-kitty")], TrueColor),
+        (&[xterm, ("WT_SESSION", "0c4f3e1b")], TrueColor),
+        (&[xterm, ("VTE_VERSION", "3600")], TrueColor),
+        (&[xterm, ("VTE_VERSION", "3406")], Ansi256),
+        (&[xterm, ("TERM_PROGRAM", "Apple_Terminal")], Ansi256),
+        (&[xterm, ("TERM_PROGRAM", "Apple_Terminal"), ("COLORTERM", "truecolor")], Ansi256),
+        (&[("TERM", "xterm"), ("TERM_PROGRAM", "Apple_Terminal")], Ansi16),
+        (&[xterm, ("TERM_PROGRAM", "vscode")], Ansi256),
+    ];
+    for (vars, expected) in cases {
+        assert_eq!(color_level_with(&env(vars), true), *expected, "{:?}", vars);
+    }
+
+    // Programs don't override the environment's preferences.
+    let kitty = ("TERM", "xterm-kitty");
+    assert_eq!(color_level_with(&env(&[kitty]), false), None);
+    assert_eq!(
+        color_level_with(&env(&[kitty, ("NO_COLOR", "1")]), true),
+        None
+    );
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn progress_styles() {
+    use super::{progress_style_with, ProgressStyle};
+
+    let xterm = env(&[("TERM", "xterm")]);
+    let ci = env(&[("TERM", "xterm"), ("GITHUB_ACTIONS", "true")]);
+    let table = &[
+        // An interactive terminal.
+        (&xterm, true, false, ProgressStyle::CarriageReturn),
+        // A pipe or file.
+        (&xterm, false, false, ProgressStyle::NewLine),
+        // A terminal under CI.
+        (&ci, true, false, ProgressStyle::NewLine),
+        // `/dev/null`.
+        (&xterm, false, true, ProgressStyle::None),
+        (&ci, false, true, ProgressStyle::None),
+    ];
+    for &(env, tty, null, style) in table {
+        assert_eq!(
+            progress_style_with(env, tty, null),
+            style,
+            "{:?} {} {}",
+            env,
+            tty,
+            null
+        );
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn null_devices() {
+    use super::{is_null, Stream};
+
+    assert!(in_child(|| {
+        let null = std::fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .unwrap();
+        rustix::io::dup2_stdout(&null).unwrap();
+        is_null(Stream::Stdout)
+    }));
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        !is_null(Stream::Stdout)
+    }));
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        !is_null(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn interactive_capabilities() {
+    use super::{interactive_caps_with, Stream::*};
+
+    let xterm = env(&[("TERM", "xterm")]);
+    let ci = env(&[("TERM", "xterm"), ("CI", "true")]);
+    let no_color = env(&[("TERM", "xterm"), ("NO_COLOR", "1")]);
+    #[rustfmt::skip]
+    let table: &[(&std::collections::HashMap<String, String>, [bool; 3], _, _, _)] = &[
+        // An interactive session.
+        (&xterm, [true, true, true], Some(Stdout), true, Some(Stderr)),
+        // `cmd > out.txt`.
+        (&xterm, [true, false, true], Some(Stderr), false, Some(Stderr)),
+        // `cmd 2> log.txt`.
+        (&xterm, [true, true, false], Some(Stdout), true, Some(Stdout)),
+        // `input | cmd`.
+        (&xterm, [false, true, true], None, true, Some(Stderr)),
+        // A daemon, or a job with all of its streams redirected.
+        (&xterm, [false, false, false], None, false, None),
+        (&ci, [true, true, true], Some(Stdout), true, None),
+        (&no_color, [true, true, true], Some(Stdout), false, Some(Stderr)),
+    ];
+    for &(env, [stdin, stdout, stderr], prompt, color, progress) in table {
+        let caps = interactive_caps_with(env, stdin, stdout, stderr);
+        let context = format!("{:?} {} {} {}", env, stdin, stdout, stderr);
+        assert_eq!(caps.prompt_stream, prompt, "{}", context);
+        assert_eq!(caps.can_prompt, prompt.is_some(), "{}", context);
+        assert_eq!(caps.can_color, color, "{}", context);
+        assert_eq!(caps.progress_stream, progress, "{}", context);
+        assert_eq!(caps.can_progress, progress.is_some(), "{}", context);
+    }
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn effective_color_levels() {
+    use super::{effective_color_level_with, ColorLevel};
+
+    // Terminals of ordinary size keep their level, and the size of
+    // anything else doesn't matter.
+    let table = &[
+        (
+            ColorLevel::TrueColor,
+            true,
+            Some((80, 24)),
+            ColorLevel::TrueColor,
+        ),
+        (
+            ColorLevel::TrueColor,
+            true,
+            Some((10, 24)),
+            ColorLevel::TrueColor,
+        ),
+        (ColorLevel::TrueColor, true, Some((9, 24)), ColorLevel::None),
+        (ColorLevel::Ansi16, true, Some((1, 1)), ColorLevel::None),
+        (ColorLevel::TrueColor, true, None, ColorLevel::Ansi256),
+        (ColorLevel::Ansi16, true, None, ColorLevel::Ansi16),
+        (
+            ColorLevel::TrueColor,
+            false,
+            Some((5, 5)),
+            ColorLevel::TrueColor,
+        ),
+        (ColorLevel::TrueColor, false, None, ColorLevel::TrueColor),
+    ];
+    for &(level, tty, size, expected) in table {
+        assert_eq!(
+            effective_color_level_with(level, tty, size),
+            expected,
+            "{:?} {} {:?}",
+            level,
+            tty,
+            size
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn color_precedence() {
+    use super::{color_level_with, ColorLevel::*};
+
+    let xterm = ("TERM", "xterm");
+    let dumb = ("TERM", "dumb");
+    #[rustfmt::skip]
+    let cases: &[(&[(&str, &str)], bool, _)] = &[
+        (&[xterm], true, Ansi16),
+        (&[xterm], false, None),
+        (&[dumb], true, None),
+        (&[("TERM", "xterm-256color")], true, Ansi256),
+        (&[xterm, ("COLORTERM", "truecolor")], true, TrueColor),
+        (&[xterm, ("NO_COLOR", "1")], true, None),
+        (&[xterm, ("NO_COLOR", "")], true, Ansi16),
+        (&[dumb, ("NO_COLOR", "1"), ("FORCE_COLOR", "1")], true, None),
+        (&[dumb, ("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")], false, None),
+        (&[dumb, ("FORCE_COLOR", "1")], true, Ansi16),
+        (&[dumb, ("FORCE_COLOR", "3")], false, TrueColor),
+        (&[xterm, ("FORCE_COLOR", "0")], true, None),
+        (&[xterm, ("FORCE_COLOR", "0"), ("CLICOLOR_FORCE", "1")], true, None),
+        (&[dumb, ("CLICOLOR_FORCE", "1")], true, Ansi16),
+        (&[xterm, ("CLICOLOR_FORCE", "0")], false, None),
+        (&[xterm, ("CLICOLOR", "0")], true, None),
+        (&[xterm, ("CLICOLOR", "0"), ("FORCE_COLOR", "2")], true, Ansi256),
+    ];
+    for (vars, tty, expected) in cases {
+        assert_eq!(
+            color_level_with(&env(vars), *tty),
+            *expected,
+            "{:?} tty={}",
+            vars,
+            tty
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn screen_reader_hints() {
+    use super::screen_reader_active_with;
+    use std::string::{String, ToString};
+
+    let names = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<std::vec::Vec<String>>()
+    };
+    let none: &[(&str, &str)] = &[];
+
+    assert!(!screen_reader_active_with(
+        &env(none),
+        names(&["PATH", "TERM"])
+    ));
+    assert!(screen_reader_active_with(&env(none), names(&["NVDA_PID"])));
+    assert!(screen_reader_active_with(
+        &env(&[("A11Y", "1")]),
+        names(&["A11Y"])
+    ));
+    assert!(!screen_reader_active_with(
+        &env(&[("ACCESSIBLE", "0")]),
+        names(&["ACCESSIBLE", "NVDA_PID"])
+    ));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn timed() {
+    use super::{is, is_timed, Stream};
+
+    for stream in [Stream::Stdin, Stream::Stdout, Stream::Stderr].iter() {
+        let (tty, elapsed) = is_timed(*stream);
+        assert_eq!(tty, is(*stream));
+        assert!(elapsed >= std::time::Duration::from_secs(0));
+    }
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn title_support() {
+    use super::{supports_title_with, TermProgram};
+
+    assert!(supports_title_with(true, Some("xterm-256color"), None));
+    assert!(supports_title_with(
+        true,
+        None,
+        Some(&TermProgram::WindowsTerminal)
+    ));
+    assert!(supports_title_with(
+        true,
+        Some("xterm"),
+        Some(&TermProgram::Mintty)
+    ));
+    assert!(!supports_title_with(true, Some("linux"), None));
+    assert!(!supports_title_with(true, Some("dumb"), None));
+    assert!(!supports_title_with(false, Some("xterm"), None));
+    assert!(!supports_title_with(true, Some("vt100"), None));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn title_is_sanitized() {
+    assert_eq!(
+        super::sanitize_title("build\x07\x1b]0;pwned\x1b\\ 50%\u{9c}\n"),
+        "build]0;pwned\\ 50%"
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "term"))]
+fn set_title_writes_only_to_terminals() {
+    use super::{set_title, Stream};
+
+    assert!(in_child(|| {
+        std::env::set_var("TERM", "xterm");
+        std::env::remove_var("TERM_PROGRAM");
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        drop(writer);
+        if set_title(Stream::Stdout, "title") {
+            return false;
+        }
+        // Nothing was written, so the pipe is empty once stdout closes.
+        rustix::io::dup2_stdout(&rustix::io::pipe().unwrap().1).unwrap();
+        let mut buf = [0; 16];
+        rustix::io::read(&reader, &mut buf).unwrap() == 0
+    }));
+
+    assert!(in_child(|| {
+        std::env::set_var("TERM", "xterm");
+        std::env::remove_var("TERM_PROGRAM");
+        let (master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        if !set_title(Stream::Stdout, "a\x07b") {
+            return false;
+        }
+        let mut buf = [0; 16];
+        let n = rustix::io::read(&master, &mut buf).unwrap();
+        &buf[..n] == b"\x1b]2;ab\x1b\\"
+    }));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn title_stack_support() {
+    use super::supports_title_stack_with;
+
+    assert!(supports_title_stack_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("XTERM_VERSION", "XTerm(390)")
+    ])));
+    assert!(supports_title_stack_with(&env(&[("TERM", "xterm-kitty")])));
+    assert!(supports_title_stack_with(&env(&[("TERM", "foot")])));
+    assert!(supports_title_stack_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("TERM_PROGRAM", "WezTerm")
+    ])));
+    // xterm's imitators don't set `XTERM_VERSION`.
+    assert!(!supports_title_stack_with(&env(&[(
+        "TERM",
+        "xterm-256color"
+    )])));
+    assert!(!supports_title_stack_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("TERM_PROGRAM", "Apple_Terminal")
+    ])));
+    assert!(!supports_title_stack_with(&env(&[
+        ("TERM", "tmux-256color"),
+        ("TERM_PROGRAM", "tmux")
+    ])));
+    assert!(!supports_title_stack_with(&env(&[("TERM", "linux")])));
+    assert!(!supports_title_stack_with(&env(&[("TERM", "dumb")])));
+}
+
+#[test]
+#[cfg(all(unix, feature = "term"))]
+fn title_stack_helpers() {
+    use super::{pop_title, push_title, Stream};
+
+    let clear = || {
+        for name in &[
+            "TERM_PROGRAM",
+            "KITTY_WINDOW_ID",
+            "ALACRITTY_WINDOW_ID",
+            "WEZTERM_EXECUTABLE",
+            "WT_SESSION",
+        ] {
+            std::env::remove_var(name);
+        }
+    };
+
+    // Unsupported terminals get nothing.
+    assert!(in_child(|| {
+        clear();
+        std::env::set_var("TERM", "linux");
+        std::env::remove_var("XTERM_VERSION");
+        let (master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        if push_title(Stream::Stdout) || pop_title(Stream::Stdout) {
+            return false;
+        }
+        rustix::io::write(&slave, b"end").unwrap();
+        let mut buf = [0; 16];
+        let n = rustix::io::read(&master, &mut buf).unwrap();
+        &buf[..n] == b"end"
+    }));
+
+    assert!(in_child(|| {
+        clear();
+        std::env::set_var("TERM", "xterm-256color");
+        std::env::set_var("XTERM_VERSION", "XTerm(390)");
+        let (master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        if !push_title(Stream::Stdout) || !pop_title(Stream::Stdout) || push_title(Stream::Stdin) {
+            return false;
+        }
+        let mut buf = [0; 32];
+        let n = rustix::io::read(&master, &mut buf).unwrap();
+        &buf[..n] == b"\x1b[22;0t\x1b[23;0t"
+    }));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn hyperlink_support() {
+    use super::supports_hyperlinks_with;
+
+    assert!(supports_hyperlinks_with(&env(&[("VTE_VERSION", "5002")])));
+    assert!(!supports_hyperlinks_with(&env(&[("VTE_VERSION", "4803")])));
+    assert!(supports_hyperlinks_with(&env(&[
+        ("TERM_PROGRAM", "iTerm.app"),
+        ("TERM_PROGRAM_VERSION", "3.1.0")
+    ])));
+    assert!(!supports_hyperlinks_with(&env(&[
+        ("TERM_PROGRAM", "iTerm.app"),
+        ("TERM_PROGRAM_VERSION", "3.0.15")
+    ])));
+    assert!(supports_hyperlinks_with(&env(&[("WT_SESSION", "x")])));
+    assert!(!supports_hyperlinks_with(&env(&[(
+        "TERM_PROGRAM",
+        "Apple_Terminal"
+    )])));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn styled_underline_support() {
+    use super::{supports_styled_underline_with, terminfo_has_extended};
+
+    assert!(supports_styled_underline_with(&env(&[
+        ("TERM", "xterm-kitty"),
+        ("KITTY_WINDOW_ID", "1")
+    ])));
+    assert!(supports_styled_underline_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("VTE_VERSION", "6800")
+    ])));
+    assert!(supports_styled_underline_with(&env(&[("TERM", "foot")])));
+    assert!(!supports_styled_underline_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("VTE_VERSION", "5002")
+    ])));
+    assert!(!supports_styled_underline_with(&env(&[(
+        "TERM_PROGRAM",
+        "Apple_Terminal"
+    )])));
+    assert!(!supports_styled_underline_with(&env(&[
+        ("TERM", "dumb"),
+        ("TERM_PROGRAM", "WezTerm")
+    ])));
+
+    // A legacy-format entry named `x`, with one extended string, `Smulx`.
+    let short = |value: u16| value.to_le_bytes().to_vec();
+    let mut entry = Vec::new();
+    for &value in &[0o432, 2, 0, 0, 0, 0] {
+        entry.extend(short(value));
+    }
+    entry.extend(b"x\0");
+    let table = b"\x1b[4:%p1%dm\0Smulx\0";
+    for &value in &[0, 0, 1, 2, table.len() as u16, 0, 11] {
+        entry.extend(short(value));
+    }
+    entry.extend(table);
+    assert_eq!(terminfo_has_extended(&entry, "Smulx"), Some(true));
+    assert_eq!(terminfo_has_extended(&entry, "Smol"), Some(false));
+    assert_eq!(
+        terminfo_has_extended(&entry[..entry.len() - 1], "Smulx"),
+        None
+    );
+    assert_eq!(terminfo_has_extended(b"not terminfo", "Smulx"), None);
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-terminfo-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("x")).unwrap();
+    std::fs::write(dir.join("x").join("x-undercurl"), &entry).unwrap();
+    let terminfo = dir.to_str().unwrap();
+    assert!(supports_styled_underline_with(&env(&[
+        ("TERM", "x-undercurl"),
+        ("TERMINFO", terminfo)
+    ])));
+    assert!(!supports_styled_underline_with(&env(&[
+        ("TERM", "x-missing"),
+        ("TERMINFO", terminfo)
+    ])));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn cursor_shape_support() {
+    use super::supports_cursor_shape_with;
+
+    assert!(supports_cursor_shape_with(&env(&[
+        ("TERM", "xterm-kitty"),
+        ("KITTY_WINDOW_ID", "1")
+    ])));
+    assert!(supports_cursor_shape_with(&env(&[
+        ("TERM", "xterm-256color-nonexistent"),
+        ("VTE_VERSION", "6800")
+    ])));
+    assert!(supports_cursor_shape_with(&env(&[(
+        "TERM",
+        "foot-nonexistent"
+    )])));
+    assert!(!supports_cursor_shape_with(&env(&[(
+        "TERM",
+        "linux-nonexistent"
+    )])));
+    assert!(!supports_cursor_shape_with(&env(&[
+        ("TERM", "dumb"),
+        ("TERM_PROGRAM", "WezTerm")
+    ])));
+
+    // Legacy-format entries named `x`, with one extended string, `Ss` or
+    // `Smulx`.
+    let entry = |name: &str| {
+        let short = |value: u16| value.to_le_bytes().to_vec();
+        let mut entry = Vec::new();
+        for &value in &[0o432, 2, 0, 0, 0, 0] {
+            entry.extend(short(value));
+        }
+        entry.extend(b"x\0");
+        let mut table = b"\x1b[%p1%d q\0".to_vec();
+        table.extend(name.as_bytes());
+        table.push(0);
+        for &value in &[0, 0, 1, 2, table.len() as u16, 0, 10] {
+            entry.extend(short(value));
+        }
+        entry.extend(table);
+        entry
+    };
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-decscusr-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("x")).unwrap();
+    std::fs::write(dir.join("x").join("x-shapes"), entry("Ss")).unwrap();
+    std::fs::write(dir.join("x").join("xterm-noshapes"), entry("Smulx")).unwrap();
+    let terminfo = dir.to_str().unwrap();
+    assert!(supports_cursor_shape_with(&env(&[
+        ("TERM", "x-shapes"),
+        ("TERMINFO", terminfo)
+    ])));
+    // The terminfo entry overrides the allowlist.
+    assert!(!supports_cursor_shape_with(&env(&[
+        ("TERM", "xterm-noshapes"),
+        ("TERMINFO", terminfo)
+    ])));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn eight_bit_control_support() {
+    use super::supports_8bit_controls_with;
+
+    // Modern terminals, with or without a UTF-8 locale, don't qualify.
+    assert!(!supports_8bit_controls_with(&env(&[])));
+    assert!(!supports_8bit_controls_with(&env(&[(
+        "TERM",
+        "xterm-256color"
+    )])));
+    assert!(!supports_8bit_controls_with(&env(&[
+        ("TERM", "xterm-kitty"),
+        ("KITTY_WINDOW_ID", "1"),
+        ("LANG", "en_US.UTF-8")
+    ])));
+    assert!(!supports_8bit_controls_with(&env(&[("TERM", "dumb")])));
+
+    assert!(supports_8bit_controls_with(&env(&[
+        ("TERM", "vt220"),
+        ("LANG", "C")
+    ])));
+    assert!(supports_8bit_controls_with(&env(&[
+        ("TERM", "vt420-nonexistent"),
+        ("LC_ALL", "en_US.ISO-8859-1"),
+        ("LANG", "en_US.UTF-8")
+    ])));
+    // Not under a UTF-8 locale.
+    assert!(!supports_8bit_controls_with(&env(&[
+        ("TERM", "vt220"),
+        ("LC_CTYPE", "en_US.utf8")
+    ])));
+    assert!(!supports_8bit_controls_with(&env(&[
+        ("TERM", "vt100"),
+        ("LANG", "C")
+    ])));
+
+    // A legacy-format entry named `x`, with one extended string, `S8C1T`.
+    let value = b"\x1b[62;2\"p\0";
+    let short = |value: u16| value.to_le_bytes().to_vec();
+    let mut entry = Vec::new();
+    for &value in &[0o432, 2, 0, 0, 0, 0] {
+        entry.extend(short(value));
+    }
+    entry.extend(b"x\0");
+    let mut table = value.to_vec();
+    table.extend(b"S8C1T\0");
+    for &offset in &[0, 0, 1, 2, table.len() as u16, 0, value.len() as u16] {
+        entry.extend(short(offset));
+    }
+    entry.extend(table);
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-s8c1t-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("x")).unwrap();
+    std::fs::write(dir.join("x").join("x-8bit"), entry).unwrap();
+    let terminfo = dir.to_str().unwrap();
+    assert!(supports_8bit_controls_with(&env(&[
+        ("TERM", "x-8bit"),
+        ("TERMINFO", terminfo)
+    ])));
+    assert!(!supports_8bit_controls_with(&env(&[
+        ("TERM", "x-8bit"),
+        ("TERMINFO", terminfo),
+        ("LANG", "C.UTF-8")
+    ])));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn rectangular_ops_support() {
+    use super::supports_rectangular_ops_with;
+
+    assert!(supports_rectangular_ops_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("XTERM_VERSION", "XTerm(390)")
+    ])));
+    assert!(supports_rectangular_ops_with(&env(&[("TERM", "contour")])));
+    // Terminals which only claim to be xterm don't qualify.
+    assert!(!supports_rectangular_ops_with(&env(&[
+        ("TERM", "xterm-256color"),
+        ("VTE_VERSION", "7600")
+    ])));
+    assert!(!supports_rectangular_ops_with(&env(&[
+        ("TERM", "xterm-kitty"),
+        ("KITTY_WINDOW_ID", "1")
+    ])));
+    assert!(!supports_rectangular_ops_with(&env(&[
+        ("TERM", "dumb"),
+        ("XTERM_VERSION", "XTerm(390)")
+    ])));
+    assert!(!supports_rectangular_ops_with(&env(&[])));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn acs_line_drawing_support() {
+    use super::{supports_acs_line_drawing_with, terminfo_has_string};
+
+    // Legacy-format entries with room for 147 string capabilities, where
+    // only the last, `acsc`, may be present.
+    let entry = |acsc: bool| {
+        let short = |value: u16| value.to_le_bytes().to_vec();
+        let mut entry = Vec::new();
+        for &value in &[0o432, 2, 0, 0, 147, 0] {
+            entry.extend(short(value));
+        }
+        entry.extend(b"x\0");
+        for _ in 0..146 {
+            entry.extend(short(0xffff));
+        }
+        if acsc {
+            entry.extend(short(0));
+            entry.extend(b"qqxx\0");
+        } else {
+            entry.extend(short(0xffff));
+        }
+        entry
+    };
+    assert_eq!(terminfo_has_string(&entry(true), 146), Some(true));
+    assert_eq!(terminfo_has_string(&entry(false), 146), Some(false));
+    assert_eq!(terminfo_has_string(&entry(true), 200), Some(false));
+    assert_eq!(terminfo_has_string(b"not terminfo", 146), None);
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-acsc-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("x")).unwrap();
+    std::fs::write(dir.join("x").join("xterm-acsc"), entry(true)).unwrap();
+    std::fs::write(dir.join("x").join("xterm-plain"), entry(false)).unwrap();
+    let terminfo = dir.to_str().unwrap();
+    assert!(supports_acs_line_drawing_with(&env(&[
+        ("TERM", "xterm-acsc"),
+        ("TERMINFO", terminfo)
+    ])));
+    // The terminfo entry overrides the allowlist.
+    assert!(!supports_acs_line_drawing_with(&env(&[
+        ("TERM", "xterm-plain"),
+        ("TERMINFO", terminfo)
+    ])));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Without a terminfo entry, the allowlist decides.
+    assert!(supports_acs_line_drawing_with(&env(&[(
+        "TERM",
+        "vt100-nonexistent"
+    )])));
+    assert!(!supports_acs_line_drawing_with(&env(&[(
+        "TERM",
+        "unknown-nonexistent"
+    )])));
+    assert!(!supports_acs_line_drawing_with(&env(&[("TERM", "dumb")])));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn tab_widths() {
+    use super::{tab_width_with, terminfo_number};
+
+    // Entries with two numeric capabilities, `cols` and `it`, in the
+    // legacy or extended format.
+    let entry = |extended: bool, it: i32| {
+        let short = |value: u16| value.to_le_bytes().to_vec();
+        let magic = if extended { 0o1036 } else { 0o432 };
+        let mut entry = Vec::new();
+        for &value in &[magic, 2, 1, 2, 0, 0] {
+            entry.extend(short(value));
+        }
+        // The name, one boolean, and padding to an even offset.
+        entry.extend(b"x\0\x01\0");
+        for &value in &[80, it] {
+            if extended {
+                entry.extend(value.to_le_bytes().to_vec());
+            } else {
+                entry.extend((value as i16).to_le_bytes().to_vec());
+            }
+        }
+        entry
+    };
+    assert_eq!(terminfo_number(&entry(false, 4), 0), Some(80));
+    assert_eq!(terminfo_number(&entry(false, 4), 1), Some(4));
+    assert_eq!(terminfo_number(&entry(true, 4), 1), Some(4));
+    assert_eq!(terminfo_number(&entry(false, -1), 1), None);
+    assert_eq!(terminfo_number(&entry(true, -2), 1), None);
+    assert_eq!(terminfo_number(&entry(false, 4), 2), None);
+    assert_eq!(terminfo_number(b"not terminfo", 1), None);
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-tabs-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("x")).unwrap();
+    std::fs::write(dir.join("x").join("x-tabs4"), entry(false, 4)).unwrap();
+    std::fs::write(dir.join("x").join("x-notabs"), entry(true, -1)).unwrap();
+    std::fs::write(dir.join("x").join("x-tabs1000"), entry(true, 1000)).unwrap();
+    let terminfo = dir.to_str().unwrap();
+    for &(term, expected) in &[
+        ("x-tabs4", 4),
+        ("x-notabs", 8),
+        ("x-tabs1000", 8),
+        ("x-missing", 8),
+    ] {
+        assert_eq!(
+            tab_width_with(&env(&[("TERM", term), ("TERMINFO", terminfo)])),
+            expected,
+            "{}",
+            term
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(tab_width_with(&env(&[])), 8);
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn iterm_image_support() {
+    use super::supports_iterm_images_with;
+
+    assert!(supports_iterm_images_with(&env(&[
+        ("TERM_PROGRAM", "iTerm.app"),
+        ("TERM_PROGRAM_VERSION", "3.0.0")
+    ])));
+    assert!(!supports_iterm_images_with(&env(&[
+        ("TERM_PROGRAM", "iTerm.app"),
+        ("TERM_PROGRAM_VERSION", "2.9.20160206")
+    ])));
+    assert!(supports_iterm_images_with(&env(&[(
+        "TERM_PROGRAM",
+        "WezTerm"
+    )])));
+    assert!(!supports_iterm_images_with(&env(&[(
+        "TERM_PROGRAM",
+        "Apple_Terminal"
+    )])));
+    assert!(!supports_iterm_images_with(&env(&[(
+        "KITTY_WINDOW_ID",
+        "1"
+    )])));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn versions() {
+    use super::version_at_least;
+
+    assert!(version_at_least(Some("3.1"), (3, 1)));
+    assert!(version_at_least(Some("3.4.19"), (3, 1)));
+    assert!(version_at_least(Some("4"), (3, 1)));
+    assert!(version_at_least(Some("3.5.0beta1"), (3, 5)));
+    assert!(!version_at_least(Some("3.0.15"), (3, 1)));
+    assert!(!version_at_least(Some("garbage"), (3, 1)));
+    assert!(!version_at_least(None, (0, 0)));
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn color_env_precedence() {
+    use super::color_env_preference_in;
+
+    #[rustfmt::skip]
+    let cases: &[(&[(&str, &str)], _)] = &[
+        (&[], None),
+        (&[("TERM", "dumb")], None),
+        (&[("NO_COLOR", "1")], Some(false)),
+        (&[("NO_COLOR", "")], None),
+        (&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")], Some(false)),
+        (&[("NO_COLOR", "1"), ("FORCE_COLOR", "3")], Some(false)),
+        (&[("CLICOLOR", "0")], Some(false)),
+        (&[("CLICOLOR", "1")], None),
+        (&[("CLICOLOR", "0"), ("CLICOLOR_FORCE", "1")], Some(true)),
+        (&[("CLICOLOR_FORCE", "0")], None),
+        (&[("FORCE_COLOR", "1")], Some(true)),
+        (&[("FORCE_COLOR", "")], Some(true)),
+        (&[("FORCE_COLOR", "false"), ("CLICOLOR_FORCE", "1")], Some(false)),
+    ];
+    for (vars, expected) in cases {
+        assert_eq!(color_env_preference_in(&env(vars)), *expected, "{:?}", vars);
+    }
+}
+
+#[test]
+#[cfg(all(windows, feature = "custom-fd"))]
+fn std_handle_ids() {
+    use super::{from_std_handle, is, Stream};
+    use windows_sys::Win32::System::Console::{
+        STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    assert_eq!(from_std_handle(STD_INPUT_HANDLE), Stream::Stdin);
+    assert_eq!(from_std_handle(STD_OUTPUT_HANDLE), Stream::Stdout);
+    assert_eq!(from_std_handle(STD_ERROR_HANDLE), Stream::Stderr);
+
+    let unknown = from_std_handle(0x1234);
+    assert_eq!(unknown, Stream::Custom(0x1234));
+    assert!(!is(unknown));
+}
+
+#[test]
+#[cfg(unix)]
+fn fd_streams() {
+    use super::{stream_of_fd, Stream};
+
+    assert_eq!(stream_of_fd(0), Some(Stream::Stdin));
+    assert_eq!(stream_of_fd(1), Some(Stream::Stdout));
+    assert_eq!(stream_of_fd(2), Some(Stream::Stderr));
+    assert_eq!(stream_of_fd(5), None);
+    assert_eq!(stream_of_fd(-1), None);
+}
+
+#[test]
+#[cfg(windows)]
+fn file_types() {
+    use super::file_type_of;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{FILE_TYPE_CHAR, FILE_TYPE_DISK, FILE_TYPE_PIPE};
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+
+    let null = std::fs::OpenOptions::new().write(true).open("NUL").unwrap();
+    assert_eq!(
+        file_type_of(null.as_raw_handle() as _),
+        Some(FILE_TYPE_CHAR)
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = std::fs::File::create(dir.path().join("file")).unwrap();
+    assert_eq!(
+        file_type_of(file.as_raw_handle() as _),
+        Some(FILE_TYPE_DISK)
+    );
+
+    let (mut reader, mut writer) = (0, 0);
+    assert_ne!(
+        unsafe { CreatePipe(&mut reader, &mut writer, core::ptr::null(), 0) },
+        0
+    );
+    assert_eq!(file_type_of(reader), Some(FILE_TYPE_PIPE));
+    assert_eq!(file_type_of(writer), Some(FILE_TYPE_PIPE));
+    unsafe {
+        windows_sys::Win32::Foundation::CloseHandle(reader);
+        windows_sys::Win32::Foundation::CloseHandle(writer);
+    }
+
+    // A null handle is invalid.
+    assert_eq!(file_type_of(0), None);
+}
+
+#[test]
+#[cfg(all(windows, feature = "win-minimal"))]
+fn minimal_consoles() {
+    // Character devices pass for consoles, and files don't.
+    let null = std::fs::OpenOptions::new().write(true).open("NUL").unwrap();
+    assert!(null.is_terminal());
+    assert!(!std::fs::File::open("Cargo.toml").unwrap().is_terminal());
+}
+
+#[test]
+#[cfg(windows)]
+fn handle_streams() {
+    use super::stream_of_handle;
+    use std::os::windows::io::AsRawHandle;
+
+    // The standard handles may coincide when they refer to the same
+    // console, so only check that each maps to a stream with its handle.
+    for handle in &[
+        std::io::stdin().as_raw_handle(),
+        std::io::stdout().as_raw_handle(),
+        std::io::stderr().as_raw_handle(),
+    ] {
+        if let Some(stream) = stream_of_handle(*handle) {
+            assert_eq!(stream.as_raw_handle(), *handle as _);
+        }
+    }
+    assert_eq!(stream_of_handle(core::ptr::null_mut()), None);
+    assert_eq!(
+        stream_of_handle(std::fs::File::open("Cargo.toml").unwrap().as_raw_handle()),
+        None
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn nonblocking_flag() {
+    use super::{is_nonblocking, Stream};
+    use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        if is_nonblocking(Stream::Stdout) != Some(false) {
+            return false;
+        }
+
+        let flags = fcntl_getfl(&writer).unwrap();
+        fcntl_setfl(&writer, flags | OFlags::NONBLOCK).unwrap();
+        if is_nonblocking(Stream::Stdout) != Some(true) {
+            return false;
+        }
+
+        fcntl_setfl(&writer, flags).unwrap();
+        is_nonblocking(Stream::Stdout) == Some(false)
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn toggle_blocking() {
+    use super::{is_nonblocking, set_blocking, Stream};
+    use rustix::fs::{fcntl_getfl, OFlags};
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let append = fcntl_getfl(&writer).unwrap() | OFlags::APPEND;
+        rustix::fs::fcntl_setfl(&writer, append).unwrap();
+
+        set_blocking(Stream::Stdout, false)
+            && is_nonblocking(Stream::Stdout) == Some(true)
+            && set_blocking(Stream::Stdout, true)
+            && is_nonblocking(Stream::Stdout) == Some(false)
+            && set_blocking(Stream::Stdout, true)
+            && fcntl_getfl(&writer).unwrap().contains(OFlags::APPEND)
+    }));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn virtual_terminals() {
+    use super::{active_vt, is_vt_device};
+    use rustix::fs::makedev;
+
+    assert!(is_vt_device(makedev(4, 1)));
+    assert!(is_vt_device(makedev(4, 63)));
+    assert!(!is_vt_device(makedev(4, 0)));
+    assert!(!is_vt_device(makedev(4, 64)));
+    assert!(!is_vt_device(makedev(136, 1)));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        active_vt().is_none()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn closed_streams() {
+    use super::{is, Stream};
+    use std::os::unix::io::AsRawFd;
+
+    for &(stream, fd) in &[(Stream::Stdin, 0), (Stream::Stdout, 1), (Stream::Stderr, 2)] {
+        assert!(in_child(|| {
+            let (_master, slave) = pty();
+            unsafe {
+                libc::dup2(slave.as_raw_fd(), fd);
+                if !is(stream) {
+                    return false;
+                }
+                libc::close(fd);
+            }
+            !is(stream)
+        }));
+
+        // A closed descriptor number reused by another file is reported as
+        // that file.
+        assert!(in_child(|| {
+            let (_master, slave) = pty();
+            unsafe {
+                libc::dup2(slave.as_raw_fd(), fd);
+                libc::close(fd);
+                let file = libc::open(b"/dev/null\0".as_ptr().cast(), libc::O_RDWR);
+                file == fd && !is(stream)
+            }
+        }));
+    }
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "fuchsia"), feature = "std"))]
+fn tty_names() {
+    use super::{tty_name, Stream};
+    use std::os::unix::ffi::OsStrExt;
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let expected = rustix::termios::ttyname(&slave, Vec::new()).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        tty_name(Stream::Stdout).unwrap().as_os_str().as_bytes() == expected.as_bytes()
+    }));
+
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        tty_name(Stream::Stdin).is_none() && tty_name(Stream::Stdout).is_none()
+    }));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn serial_device_names() {
+    use super::{is_serial_console, is_serial_device_name, Stream};
+    use std::path::Path;
+
+    for name in &["/dev/ttyS0", "/dev/ttyS12", "/dev/ttyAMA0", "/dev/ttyUSB3"] {
+        assert!(is_serial_device_name(Path::new(name)), "{}", name);
+    }
+    for name in &[
+        "/dev/pts/0",
+        "/dev/tty1",
+        "/dev/ttyS",
+        "/dev/ttySx",
+        "/tmp/ttyS0",
+        "/dev/console",
+    ] {
+        assert!(!is_serial_device_name(Path::new(name)), "{}", name);
+    }
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        is_serial_console(Stream::Stdout) == Some(false)
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn baud_rates() {
+    use super::{baud_rate, Stream};
+    use rustix::termios::{cfsetospeed, tcgetattr, tcsetattr, OptionalActions, B9600};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let mut termios = tcgetattr(&slave).unwrap();
+        cfsetospeed(&mut termios, B9600).unwrap();
+        tcsetattr(&slave, OptionalActions::Now, &termios).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        baud_rate(Stream::Stdout) == Some(9600)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        baud_rate(Stream::Stdout).is_none()
+    }));
+}
+
+#[test]
+fn probe_runs_only_selected_queries() {
+    use super::{is, Probe, Stream};
+    use core::cell::Cell;
+
+    let calls = Cell::new(0);
+    let size = |_| {
+        calls.set(calls.get() + 1);
+        Some((80, 24))
+    };
+
+    let info = Probe::new(Stream::Stdout).tty().run_with(&size);
+    assert_eq!(calls.get(), 0);
+    assert_eq!(info.tty, Some(is(Stream::Stdout)));
+    assert_eq!(info.size, None);
+
+    let info = Probe::new(Stream::Stdout).size().run_with(&size);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(info.tty, None);
+    assert_eq!(info.size, Some((80, 24)));
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_terminals() {
+    use super::{same_terminal, stderr_safe_for_logging_during_tui, Stream};
+
+    // stdout and stderr on one pty.
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&slave).unwrap();
+        same_terminal(Stream::Stdout, Stream::Stderr) && !stderr_safe_for_logging_during_tui()
+    }));
+
+    // On separate ptys.
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_other_master, other_slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&other_slave).unwrap();
+        !same_terminal(Stream::Stdout, Stream::Stderr) && stderr_safe_for_logging_during_tui()
+    }));
+
+    // With stderr redirected to a pipe.
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&writer).unwrap();
+        !same_terminal(Stream::Stdout, Stream::Stderr) && stderr_safe_for_logging_during_tui()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn redirection_detection() {
+    use super::{detect, verify_consistency, Stream};
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let _ = detect(Stream::Stdout);
+        let consistent = verify_consistency(Stream::Stdout);
+
+        // Something else reopens stdout, as `logrotate` might.
+        let (_other_reader, other_writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&other_writer).unwrap();
+        let redirected = !verify_consistency(Stream::Stdout);
+        let _ = detect(Stream::Stdout);
+
+        consistent && redirected && verify_consistency(Stream::Stdout)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let saved = super::snapshot(Stream::Stdout).unwrap();
+        let (_other_reader, other_writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&other_writer).unwrap();
+        let redirected = !verify_consistency(Stream::Stdout);
+        saved.restore() && redirected && verify_consistency(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn snapshots() {
+    use super::{detect, Stream, TerminalSnapshot};
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let snapshot = detect(Stream::Stdout);
+        assert_send_sync(&snapshot);
+        let shared = snapshot.clone();
+        let from_thread: TerminalSnapshot = std::thread::spawn(move || shared).join().unwrap();
+        snapshot.is_tty()
+            && !snapshot.is_pipe()
+            && snapshot.stream() == Stream::Stdout
+            && from_thread == snapshot
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let snapshot = detect(Stream::Stdout);
+        !snapshot.is_tty() && snapshot.is_pipe() && snapshot.size().is_none()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn sizes() {
+    use super::{size, Stream};
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        if size(Stream::Stdout).is_some() {
+            return false;
+        }
+        let winsize = rustix::termios::Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSWINSZ, &winsize) };
+        size(Stream::Stdout) == Some((80, 24))
+    }));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn focus_event_support() {
+    use super::{supports_focus_events_with, TermProgram};
+
+    assert!(supports_focus_events_with(
+        true,
+        Some("xterm-256color"),
+        Some(&TermProgram::Kitty)
+    ));
+    assert!(supports_focus_events_with(
+        true,
+        None,
+        Some(&TermProgram::WindowsTerminal)
+    ));
+    assert!(supports_focus_events_with(true, Some("foot"), None));
+    assert!(!supports_focus_events_with(
+        true,
+        Some("xterm-256color"),
+        Some(&TermProgram::AppleTerminal)
+    ));
+    assert!(!supports_focus_events_with(
+        false,
+        Some("xterm"),
+        Some(&TermProgram::Kitty)
+    ));
+    assert!(!supports_focus_events_with(true, Some("linux"), None));
+    assert!(!supports_focus_events_with(true, Some("vt100"), None));
+    assert!(!supports_focus_events_with(true, None, None));
+}
+
+#[test]
+fn msys_pty_names() {
+    use super::{classify_pipe_name, PtyFlavor};
+
+    for &(name, flavor) in &[
+        ("\\msys-1888ae32e00d56aa-pty0-to-master", PtyFlavor::Msys),
+        (
+            "\\Device\\NamedPipe\\msys-1888ae32e00d56aa-pty0-to-master",
+            PtyFlavor::Msys,
+        ),
+        (
+            "\\\\?\\pipe\\cygwin-e022582115c10879-pty1-from-master",
+            PtyFlavor::Cygwin,
+        ),
+        (
+            "\\\\.\\pipe\\msys-dd50a72ab4668b33-pty2-to-master",
+            PtyFlavor::Msys,
+        ),
+        // mintty's ptys are named by the MSYS or Cygwin runtime it's
+        // built against.
+        ("msys-1888ae32e00d56aa-pty3-from-master", PtyFlavor::Msys),
+        ("cygwin-e022582115c10879-pty0-to-master", PtyFlavor::Cygwin),
+    ] {
+        assert_eq!(classify_pipe_name(name), Some(flavor), "{}", name);
+    }
+    for name in &[
+        "\\msys-1888ae32e00d56aa-pipe-0x1",
+        "\\mypty-msys-1888ae32e00d56aa",
+        "\\Device\\NamedPipe\\other\\msys-1888ae32e00d56aa-pty0-to-master",
+        "\\Users\\me\\cygwin-pty.txt",
+        "\\mintty-pty0",
+        "",
+    ] {
+        assert_eq!(classify_pipe_name(name), None, "{}", name);
+    }
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn proc_fd_targets() {
+    use super::{proc_fd_target, read_fd_link, Stream};
+    use std::os::unix::fs::symlink;
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-fd-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for target in &["pipe:[4026532]", "/dev/pts/7"] {
+        let link = dir.join("link");
+        let _ = std::fs::remove_file(&link);
+        symlink(target, &link).unwrap();
+        assert_eq!(read_fd_link(&link).as_deref(), Some(*target));
+    }
+    assert_eq!(read_fd_link(&dir.join("missing")), None);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        proc_fd_target(Stream::Stdout).map_or(false, |target| target.starts_with("pipe:["))
+    }));
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        proc_fd_target(Stream::Stdout).map_or(false, |target| target.starts_with("/dev/pts/"))
+    }));
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "std"))]
+fn journald() {
+    use super::piped_to_journald_with;
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stderr(&writer).unwrap();
+        let stat = rustix::fs::fstat(&writer).unwrap();
+        let stream = format!("{}:{}", stat.st_dev, stat.st_ino);
+        let other = format!("{}:{}", stat.st_dev, stat.st_ino + 1);
+        piped_to_journald_with(&env(&[("JOURNAL_STREAM", &stream)]))
+            && !piped_to_journald_with(&env(&[("JOURNAL_STREAM", &other)]))
+            && !piped_to_journald_with(&env(&[("JOURNAL_STREAM", "garbage")]))
+            && !piped_to_journald_with(&env(&[]))
+    }));
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "std"))]
+fn systemd_sockets() {
+    use super::{is_systemd_socket_with, Stream};
+
+    assert!(in_child(|| {
+        let mut activated = [0; 2];
+        let mut other = [0; 2];
+        unsafe {
+            libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, activated.as_mut_ptr());
+            libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, other.as_mut_ptr());
+            libc::dup2(activated[0], 3);
+            libc::dup2(activated[0], 0);
+        }
+        let pid = std::process::id();
+        let listen = env(&[("LISTEN_FDS", "1"), ("LISTEN_PID", &pid.to_string())]);
+        if !is_systemd_socket_with(Stream::Stdin, &listen, pid)
+            || is_systemd_socket_with(Stream::Stdin, &listen, pid + 1)
+            || is_systemd_socket_with(Stream::Stdin, &env(&[]), pid)
+            || is_systemd_socket_with(
+                Stream::Stdin,
+                &env(&[("LISTEN_FDS", "0"), ("LISTEN_PID", &pid.to_string())]),
+                pid,
+            )
+        {
+            return false;
+        }
+        unsafe { libc::dup2(other[0], 0) };
+        !is_systemd_socket_with(Stream::Stdin, &listen, pid)
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn controlling_tty() {
+    use super::has_controlling_tty;
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        unsafe {
+            // Closing the pty would hang up its session, which is ours.
+            libc::signal(libc::SIGHUP, libc::SIG_IGN);
+            libc::setsid();
+        }
+        if has_controlling_tty() {
+            return false;
+        }
+        let (_master, slave) = pty();
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY as _, 0) };
+        has_controlling_tty()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn job_control() {
+    use super::{has_job_control, Stream};
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        unsafe {
+            // Closing the pty would hang up its session, which is ours.
+            libc::signal(libc::SIGHUP, libc::SIG_IGN);
+            libc::setsid();
+        }
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY as _, 0) };
+        // The session leader is never in a job of its own...
+        if has_job_control(Stream::Stdin) != Some(false) {
+            return false;
+        }
+        // ...but a process it puts in a new process group, as a
+        // job-control shell would, is.
+        in_child(|| {
+            unsafe { libc::setpgid(0, 0) };
+            has_job_control(Stream::Stdin) == Some(true)
+        })
+    }));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        has_job_control(Stream::Stdin).is_none()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn input_echo() {
+    use super::{echo_enabled, input_will_echo, Stream};
+    use rustix::termios::{tcgetattr, tcsetattr, OptionalActions, ECHO};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        // Ptys start out echoing.
+        if !input_will_echo() || echo_enabled(Stream::Stdin) != Some(true) {
+            return false;
+        }
+        let mut termios = tcgetattr(&slave).unwrap();
+        termios.c_lflag &= !ECHO;
+        tcsetattr(&slave, OptionalActions::Now, &termios).unwrap();
+        !input_will_echo() && echo_enabled(Stream::Stdin) == Some(false)
+    }));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        !input_will_echo() && echo_enabled(Stream::Stdin).is_none()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn terminal_sessions() {
+    use super::{terminal_session_pid, Stream};
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        let session = unsafe {
+            // Closing the pty would hang up its session, which is ours.
+            libc::signal(libc::SIGHUP, libc::SIG_IGN);
+            libc::setsid()
+        };
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        // It's not our controlling terminal yet.
+        if terminal_session_pid(Stream::Stdin).is_some() {
+            return false;
+        }
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY as _, 0) };
+        session > 0 && terminal_session_pid(Stream::Stdin) == Some(session)
+    }));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        terminal_session_pid(Stream::Stdin).is_none()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn gui_launches() {
+    use super::launched_from_gui_with;
+
+    let finder = env(&[("__CF_USER_TEXT_ENCODING", "0x1F5:0x0:0x0")]);
+    assert!(launched_from_gui_with(false, &finder, true));
+    assert!(!launched_from_gui_with(true, &finder, true));
+    assert!(!launched_from_gui_with(false, &finder, false));
+
+    let desktop = env(&[("WAYLAND_DISPLAY", "wayland-0")]);
+    assert!(launched_from_gui_with(false, &desktop, false));
+    assert!(!launched_from_gui_with(false, &env(&[]), false));
+    assert!(!launched_from_gui_with(
+        false,
+        &env(&[("DISPLAY", ":0"), ("TERM", "xterm-256color")]),
+        false
+    ));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn linux_console() {
+    use super::{is_linux_console, is_linux_console_with, Stream};
+    use std::path::Path;
+
+    assert!(is_linux_console_with(
+        Some(Path::new("/dev/tty1")),
+        Some("linux")
+    ));
+    assert!(!is_linux_console_with(
+        Some(Path::new("/dev/tty1")),
+        Some("xterm-256color")
+    ));
+    assert!(!is_linux_console_with(
+        Some(Path::new("/dev/pts/0")),
+        Some("linux")
+    ));
+    assert!(!is_linux_console_with(
+        Some(Path::new("/dev/ttyS0")),
+        Some("linux")
+    ));
+    assert!(!is_linux_console_with(None, Some("linux")));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        !is_linux_console(Stream::Stdout)
+    }));
+}
+
+#[test]
+fn pipe_to_tty() {
+    use super::reading_from_pipe_writing_to_tty_with;
+
+    assert!(reading_from_pipe_writing_to_tty_with(true, true));
+    assert!(!reading_from_pipe_writing_to_tty_with(true, false));
+    assert!(!reading_from_pipe_writing_to_tty_with(false, true));
+    assert!(!reading_from_pipe_writing_to_tty_with(false, false));
+}
+
+#[test]
+fn prompts_to_stderr() {
+    use super::prompt_should_go_to_stderr_with;
+
+    assert!(prompt_should_go_to_stderr_with(true, false, true));
+    assert!(!prompt_should_go_to_stderr_with(true, true, true));
+    assert!(!prompt_should_go_to_stderr_with(true, false, false));
+    assert!(!prompt_should_go_to_stderr_with(false, false, true));
+    assert!(!prompt_should_go_to_stderr_with(false, true, true));
+}
+
+#[test]
+#[cfg(unix)]
+fn prompt_with_redirected_stdout() {
+    use super::prompt_should_go_to_stderr;
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        rustix::io::dup2_stderr(&slave).unwrap();
+        prompt_should_go_to_stderr()
+    }));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&slave).unwrap();
+        !prompt_should_go_to_stderr()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn pipes() {
+    use super::{is_pipe, reading_from_pipe_writing_to_tty, Stream};
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        is_pipe(Stream::Stdin) && !is_pipe(Stream::Stdout) && reading_from_pipe_writing_to_tty()
+    }));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        !is_pipe(Stream::Stdin) && is_pipe(Stream::Stdout) && !reading_from_pipe_writing_to_tty()
+    }));
+}
+
+#[test]
+#[cfg(feature = "capi")]
+fn capi() {
+    use super::{atty_is, is, Stream};
+
+    let f: extern "C" fn(std::os::raw::c_int) -> std::os::raw::c_int = atty_is;
+    assert_eq!(f(0), is(Stream::Stdin) as std::os::raw::c_int);
+    assert_eq!(f(1), is(Stream::Stdout) as std::os::raw::c_int);
+    assert_eq!(f(2), is(Stream::Stderr) as std::os::raw::c_int);
+    assert_eq!(f(3), -1);
+    assert_eq!(f(-1), -1);
+}
+
+#[test]
+#[cfg(feature = "query")]
+fn reply_parser() {
+    use crate::reply::{Reply, ReplyParser};
+
+    fn csi(
+        private: Option<u8>,
+        params: &[Option<i32>],
+        intermediates: &[u8],
+        final_byte: u8,
+    ) -> Reply {
+        Reply::Csi {
+            private,
+            params: params.to_vec(),
+            intermediates: intermediates.to_vec(),
+            final_byte,
+        }
+    }
+
+    // Returns every reply in `chunks`, fed one at a time.
+    fn parse(chunks: &[&[u8]]) -> Vec<Reply> {
+        let mut parser = ReplyParser::new();
+        let mut replies = Vec::new();
+        for chunk in chunks {
+            let mut chunk = *chunk;
+            while let Some(reply) = parser.feed(chunk) {
+                replies.push(reply);
+                chunk = &[];
+            }
+        }
+        replies
+    }
+
+    let da = csi(Some(b'?'), &[Some(62), Some(4), Some(22)], b"", b'c');
+    let dsr = csi(None, &[Some(5), Some(10)], b"", b'R');
+    let decrpm = csi(Some(b'?'), &[Some(2004), Some(1)], b"$", b'y');
+    let osc11 = Reply::Osc(b"11;rgb:ffff/8080/0000".to_vec());
+
+    assert_eq!(parse(&[b"\x1b[?62;4;22c"]), std::vec![da.clone()]);
+    assert_eq!(parse(&[b"\x1b[5;10R"]), std::vec![dsr.clone()]);
+    assert_eq!(parse(&[b"\x1b[?2004;1$y"]), std::vec![decrpm.clone()]);
+    assert_eq!(
+        parse(&[b"\x1b]11;rgb:ffff/8080/0000\x07"]),
+        std::vec![osc11.clone()]
+    );
+    assert_eq!(
+        parse(&[b"\x1b]11;rgb:ffff/8080/0000\x1b\\"]),
+        std::vec![osc11.clone()]
+    );
+
+    // Several replies in one read, mixed with keystrokes, including ones
+    // sent as escape sequences.
+    assert_eq!(
+        parse(&[b"ab\x1bx\x1bOA\x1b[?2004;1$y\r\x1b]11;rgb:ffff/8080/0000\x07q\x1b[?62;4;22c"]),
+        [decrpm.clone(), osc11.clone(), da.clone()]
+    );
+
+    // Replies split across reads, at every possible point.
+    let input: &[u8] = b"x\x1b[5;10R\x1b]11;rgb:ffff/8080/0000\x1b\\\x1b[?2004;1$yy\x1b[?62;4;22c";
+    let expected = [dsr, osc11, decrpm, da];
+    for split in 0..=input.len() {
+        assert_eq!(parse(&[&input[..split], &input[split..]]), expected);
+    }
+    let bytes = input.iter().map(core::slice::from_ref).collect::<Vec<_>>();
+    assert_eq!(parse(&bytes), expected);
+
+    // Empty and negative parameters, and sequences with no parameters.
+    assert_eq!(
+        parse(&[b"\x1b[3;-1920;t\x1b[>4m\x1b[c"]),
+        [
+            csi(None, &[Some(3), Some(-1920), None], b"", b't'),
+            csi(Some(b'>'), &[Some(4)], b"", b'm'),
+            csi(None, &[], b"", b'c'),
+        ]
+    );
+    assert_eq!(
+        parse(&[b"\x1b[99999999999c"]),
+        [csi(None, &[Some(i32::MAX)], b"", b'c')]
+    );
+
+    assert_eq!(
+        parse(&[b"\x1bP>|WezTerm 20240203\x1b\\\x1bP1$r0m\x1b\\"]),
+        [
+            Reply::Dcs {
+                private: Some(b'>'),
+                params: Vec::new(),
+                intermediates: Vec::new(),
+                final_byte: b'|',
+                data: b"WezTerm 20240203".to_vec(),
+            },
+            Reply::Dcs {
+                private: None,
+                params: std::vec![Some(1)],
+                intermediates: b"$".to_vec(),
+                final_byte: b'r',
+                data: b"0m".to_vec(),
+            },
+        ]
+    );
+    assert_eq!(
+        parse(&[b"\x1b_Gi=31;OK\x1b\\"]),
+        [Reply::Apc(b"Gi=31;OK".to_vec())]
+    );
+
+    // Sequences which are malformed, cut short, or of kinds which aren't
+    // replies are skipped.
+    assert_eq!(
+        parse(&[b"\x1b[1:2c\x1b[1?c\x1b[?62\x1b[5;10\x18R\x1b]11;rgb\x1b[?62c"]),
+        [csi(Some(b'?'), &[Some(62)], b"", b'c')]
+    );
+    assert_eq!(parse(&[b"\x1bXhidden\x1b\\\x1b^private\x1b\\"]), []);
+    assert_eq!(parse(&[b"\x1b]11;rgb:ffff/8080/0000"]), []);
+    assert_eq!(parse(&[b""]), []);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn device_attribute_replies() {
+    use super::parse_device_attributes;
+
+    assert_eq!(
+        parse_device_attributes(b"\x1b[?62;4;22c"),
+        Some(std::vec![62, 4, 22])
+    );
+    assert_eq!(
+        parse_device_attributes(b"typed\x1b[?1;2c"),
+        Some(std::vec![1, 2])
+    );
+    assert_eq!(
+        parse_device_attributes(b"\x1b[?7;1$y\x1b[?62c"),
+        Some(std::vec![62])
+    );
+    assert_eq!(
+        parse_device_attributes(b"\x1b[62;4;22c"),
+        Some(std::vec![62, 4, 22])
+    );
+    assert_eq!(parse_device_attributes(b"\x1b[?62;4"), None);
+    assert_eq!(parse_device_attributes(b"\x1b[c"), None);
+    assert_eq!(parse_device_attributes(b"\x1b[?62;4R"), None);
+    assert_eq!(parse_device_attributes(b""), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn kitty_graphics_query() {
+    use super::supports_kitty_graphics_with;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    // kitty is recognized without a query.
+    let (_master, slave) = pty();
+    assert!(supports_kitty_graphics_with(
+        &env(&[("KITTY_WINDOW_ID", "1")]),
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_millis(50)
+    ));
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b_Gi=31;OK\x1b\\\x1b[?62;22c"));
+    assert!(supports_kitty_graphics_with(
+        &env(&[]),
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_secs(10)
+    ));
+    terminal.join().unwrap();
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62;22c"));
+    assert!(!supports_kitty_graphics_with(
+        &env(&[]),
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_secs(10)
+    ));
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert!(!supports_kitty_graphics_with(
+        &env(&[]),
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_millis(50)
+    ));
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn tmux_passthrough() {
+    use super::tmux_passthrough_enabled_with;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let tmux = env(&[("TMUX", "/tmp/tmux-1000/default,1234,0")]);
+    let request = b"\x1bPtmux;\x1b\x1b[c\x1b\\";
+
+    // Outside tmux, nothing is sent.
+    let (_master, slave) = pty();
+    assert_eq!(
+        tmux_passthrough_enabled_with(
+            &env(&[]),
+            slave.as_fd(),
+            slave.as_fd(),
+            Duration::from_secs(10)
+        ),
+        None
+    );
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, request, b"\x1b[?62;22c"));
+    assert_eq!(
+        tmux_passthrough_enabled_with(&tmux, slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some(true)
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        tmux_passthrough_enabled_with(
+            &tmux,
+            slave.as_fd(),
+            slave.as_fd(),
+            Duration::from_millis(50)
+        ),
+        Some(false)
+    );
+}
+
+#[test]
+#[cfg(feature = "query")]
+fn tmux_pane_visibility() {
+    use super::pane_is_visible_with;
+
+    let tmux = env(&[
+        ("TMUX", "/tmp/tmux-1000/default,1234,0"),
+        ("TMUX_PANE", "%3"),
+    ]);
+    let reply = |reply: &'static str| {
+        move |args: &[&str]| {
+            assert_eq!(&args[..4], &["display-message", "-p", "-t", "%3"]);
+            Some(std::format!("{}\n", reply))
+        }
+    };
+
+    // Outside tmux, tmux isn't asked.
+    assert_eq!(
+        pane_is_visible_with(&env(&[]), &|_| panic!("tmux was run")),
+        None
+    );
+
+    assert_eq!(pane_is_visible_with(&tmux, &reply("1 1 0 0")), Some(true));
+    // In a background window, a detached session, or behind a zoomed pane.
+    assert_eq!(pane_is_visible_with(&tmux, &reply("0 1 0 1")), Some(false));
+    assert_eq!(pane_is_visible_with(&tmux, &reply("1 0 0 1")), Some(false));
+    assert_eq!(pane_is_visible_with(&tmux, &reply("1 2 1 0")), Some(false));
+    assert_eq!(pane_is_visible_with(&tmux, &reply("1 2 1 1")), Some(true));
+    assert_eq!(pane_is_visible_with(&tmux, &reply("1 1")), None);
+    assert_eq!(pane_is_visible_with(&tmux, &|_| None), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn mode_reports() {
+    use super::{mode_report, DecModeState::*};
+
+    assert_eq!(mode_report(b"\x1b[?7;0$y", 7), Some(NotRecognized));
+    assert_eq!(mode_report(b"\x1b[?7;1$y\x1b[?62c", 7), Some(Set));
+    assert_eq!(mode_report(b"\x1b[?7;2$y", 7), Some(Reset));
+    assert_eq!(mode_report(b"\x1b[?2004;3$y", 2004), Some(PermanentlySet));
+    assert_eq!(mode_report(b"\x1b[?7;4$y", 7), Some(PermanentlyReset));
+    assert_eq!(mode_report(b"\x1b[?7;5$y", 7), None);
+    assert_eq!(mode_report(b"\x1b[?25;1$y", 7), None);
+    assert_eq!(mode_report(b"\x1b[?7;1y", 7), None);
+    assert_eq!(mode_report(b"\x1b[?62c", 7), None);
+
+    assert_eq!(Set.is_set(), Some(true));
+    assert_eq!(PermanentlyReset.is_set(), Some(false));
+    assert_eq!(NotRecognized.is_set(), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn alternate_screen_query() {
+    use super::{private_mode, DecModeState};
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    for &(reply, expected) in &[
+        (&b"\x1b[?1049;1$y\x1b[?62c"[..], Some(true)),
+        (&b"\x1b[?1049;2$y\x1b[?62c"[..], Some(false)),
+        (&b"\x1b[?62c"[..], None),
+    ] {
+        let (master, slave) = pty();
+        let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[?1049$p\x1b[c", reply));
+        assert_eq!(
+            private_mode(slave.as_fd(), slave.as_fd(), 1049, Duration::from_secs(10))
+                .and_then(DecModeState::is_set),
+            expected
+        );
+        terminal.join().unwrap();
+    }
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        private_mode(
+            slave.as_fd(),
+            slave.as_fd(),
+            1049,
+            Duration::from_millis(50)
+        ),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn dec_mode_query() {
+    use super::{private_mode, DecModeState};
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    for &(status, expected) in &[
+        (&b"1"[..], Some(DecModeState::Set)),
+        (&b"2"[..], Some(DecModeState::Reset)),
+        (&b"0"[..], Some(DecModeState::NotRecognized)),
+    ] {
+        let mut reply = b"\x1b[?7;".to_vec();
+        reply.extend_from_slice(status);
+        reply.extend_from_slice(b"$y\x1b[?62c");
+        let (master, slave) = pty();
+        let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", &reply));
+        assert_eq!(
+            private_mode(slave.as_fd(), slave.as_fd(), 7, Duration::from_secs(10)),
+            expected
+        );
+        terminal.join().unwrap();
+    }
+
+    // A terminal which doesn't know DECRQM only answers the second
+    // request.
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62c"));
+    assert_eq!(
+        private_mode(slave.as_fd(), slave.as_fd(), 7, Duration::from_secs(10)),
+        None
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        private_mode(slave.as_fd(), slave.as_fd(), 7, Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn device_attributes_query() {
+    use super::device_attributes_on;
+    use io_lifetimes::AsFd;
+    use std::os::unix::io::AsRawFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?64;1;4;22c"));
+    assert_eq!(
+        device_attributes_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some(std::vec![64, 1, 4, 22])
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        device_attributes_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+
+    // The reply is read from the input, even when the output was opened
+    // write-only, as by `cmd > /dev/tty`, and can't be read from.
+    let (master, slave) = pty();
+    let path = unsafe { std::ffi::CStr::from_ptr(libc::ttyname(slave.as_raw_fd())) };
+    let output = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path.to_str().unwrap())
+        .unwrap();
+    assert!(rustix::io::read(&output, &mut [0; 1]).is_err());
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62c"));
+    assert_eq!(
+        device_attributes_on(slave.as_fd(), output.as_fd(), Duration::from_secs(10)),
+        Some(std::vec![62])
+    );
+    terminal.join().unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn xtversion_replies() {
+    use super::parse_xtversion;
+
+    assert_eq!(
+        parse_xtversion(b"\x1bP>|WezTerm 20240203\x1b\\\x1b[?62c").as_deref(),
+        Some("WezTerm 20240203")
+    );
+    assert_eq!(
+        parse_xtversion(b"\x1bP>|tmux 3.4\x1b\\").as_deref(),
+        Some("tmux 3.4")
+    );
+    assert_eq!(parse_xtversion(b"\x1bP>|\x1b\\"), None);
+    assert_eq!(parse_xtversion(b"\x1bP>|kitty"), None);
+    assert_eq!(parse_xtversion(b"\x1b[?62c"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn xtversion_query() {
+    use super::terminal_version_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(master, b"\x1b[c", b"\x1bP>|foot(1.16.2)\x1b\\\x1b[?62;22c")
+    });
+    assert_eq!(
+        terminal_version_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)).as_deref(),
+        Some("foot(1.16.2)")
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        terminal_version_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn secondary_device_attribute_replies() {
+    use super::parse_secondary_device_attributes;
+
+    assert_eq!(
+        parse_secondary_device_attributes(b"\x1b[>41;390;0c\x1b[?64;1;2c"),
+        Some((41, 390, 0))
+    );
+    assert_eq!(
+        parse_secondary_device_attributes(b"\x1b[?62c\x1b[>1;4000;15c"),
+        Some((1, 4000, 15))
+    );
+    assert_eq!(parse_secondary_device_attributes(b"\x1b[>0;95c"), None);
+    assert_eq!(parse_secondary_device_attributes(b"\x1b[>1;2;3;4c"), None);
+    assert_eq!(parse_secondary_device_attributes(b"\x1b[>1;2;3"), None);
+    assert_eq!(parse_secondary_device_attributes(b"\x1b[?62;22c"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn secondary_device_attribute_query() {
+    use super::secondary_device_attributes_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[>1;4000;15c\x1b[?62;22c"));
+    assert_eq!(
+        secondary_device_attributes_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some((1, 4000, 15))
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        secondary_device_attributes_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn kitty_keyboard_flag_replies() {
+    use super::parse_kitty_keyboard_flags;
+
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?15u\x1b[?62c"), Some(15));
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?0u\x1b[?62c"), Some(0));
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?62c\x1b[?31u"), Some(31));
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?62c"), None);
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?u"), None);
+    assert_eq!(parse_kitty_keyboard_flags(b"\x1b[?1234u"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn kitty_keyboard_flag_query() {
+    use super::kitty_keyboard_flags_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[?u\x1b[c", b"\x1b[?5u\x1b[?62c"));
+    assert_eq!(
+        kitty_keyboard_flags_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some(5)
+    );
+    terminal.join().unwrap();
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62c"));
+    assert_eq!(
+        kitty_keyboard_flags_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        None
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        kitty_keyboard_flags_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn child_process_groups() {
+    use super::{
+        child_pgid_registered, register_child_pgid, stdin_shared_with_children,
+        unregister_child_pgid,
+    };
+
+    // Other tests don't register process groups, so the slots are free.
+    assert!(!child_pgid_registered(424_242));
+    assert!(register_child_pgid(424_242));
+    assert!(register_child_pgid(424_242));
+    assert!(child_pgid_registered(424_242));
+    assert!(!child_pgid_registered(424_243));
+    unregister_child_pgid(424_242);
+    assert!(!child_pgid_registered(424_242));
+    assert!(!register_child_pgid(0));
+    assert!(!register_child_pgid(-1));
+
+    let pgids = 500_000..500_008;
+    assert!(pgids.clone().all(register_child_pgid));
+    assert!(!register_child_pgid(500_008));
+    pgids.clone().for_each(unregister_child_pgid);
+    assert!(pgids.clone().all(|pgid| !child_pgid_registered(pgid)));
+
+    // Threads registering the same process group at once take one slot
+    // between them, leaving the other seven free.
+    for _ in 0..100 {
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(8));
+        let threads: std::vec::Vec<_> = (0..8)
+            .map(|_| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    register_child_pgid(600_000)
+                })
+            })
+            .collect();
+        assert!(threads.into_iter().all(|thread| thread.join().unwrap()));
+        assert!(child_pgid_registered(600_000));
+        let others = 600_001..600_008;
+        assert!(others.clone().all(register_child_pgid));
+        assert!(!register_child_pgid(600_008));
+        others.for_each(unregister_child_pgid);
+        unregister_child_pgid(600_000);
+        assert!(!child_pgid_registered(600_000));
+    }
+
+    // A pipe has no foreground process group.
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        register_child_pgid(unsafe { libc::getpgrp() });
+        !stdin_shared_with_children()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn modify_other_keys_replies() {
+    use super::parse_modify_other_keys_level;
+
+    assert_eq!(
+        parse_modify_other_keys_level(b"\x1b[>4;2m\x1b[?62c"),
+        Some(2)
+    );
+    assert_eq!(
+        parse_modify_other_keys_level(b"\x1b[?62c\x1b[>4;1m"),
+        Some(1)
+    );
+    assert_eq!(parse_modify_other_keys_level(b"\x1b[>4m\x1b[?62c"), Some(0));
+    assert_eq!(parse_modify_other_keys_level(b"\x1b[?62c"), None);
+    assert_eq!(parse_modify_other_keys_level(b"\x1b[>4;m"), None);
+    assert_eq!(parse_modify_other_keys_level(b"\x1b[>41;2m"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn modify_other_keys_query() {
+    use super::modify_other_keys_level_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[?4m\x1b[c", b"\x1b[>4;2m\x1b[?62c"));
+    assert_eq!(
+        modify_other_keys_level_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some(2)
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        modify_other_keys_level_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn query_safety() {
+    use super::{
+        query_is_safe, tmux_passthrough_enabled, try_cursor_position, Stream, TerminalError,
+    };
+
+    // With stdin piped, the reply would never be read, so nothing is sent.
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        !query_is_safe(Stream::Stdout)
+            && !query_is_safe(Stream::Stdin)
+            && matches!(
+                try_cursor_position(Stream::Stdout),
+                Err(TerminalError::Unsupported)
+            )
+    }));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        std::env::set_var("TMUX", "/tmp/tmux-1000/default,1234,0");
+        tmux_passthrough_enabled().is_none()
+    }));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let (_other_master, other) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        rustix::io::dup2_stderr(&other).unwrap();
+        query_is_safe(Stream::Stdin)
+            && query_is_safe(Stream::Stdout)
+            && !query_is_safe(Stream::Stderr)
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn queries_survive_signals() {
+    use super::device_attributes_on;
+    use io_lifetimes::AsFd;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static DELIVERED: AtomicUsize = AtomicUsize::new(0);
+    extern "C" fn count(_: libc::c_int) {
+        DELIVERED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // A signal arriving while a query waits for its reply interrupts the
+    // wait, which continues afterwards.
+    for &signal in &[libc::SIGWINCH, libc::SIGCHLD] {
+        assert!(in_child(|| unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = count as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            assert_eq!(libc::sigaction(signal, &action, std::ptr::null_mut()), 0);
+
+            let (master, slave) = pty();
+            // `pthread_t` is a pointer on some platforms, which isn't `Send`.
+            let prober = libc::pthread_self() as usize;
+            let terminal = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                libc::pthread_kill(prober as libc::pthread_t, signal);
+                std::thread::sleep(Duration::from_millis(50));
+                reply_to(master, b"\x1b[c", b"\x1b[?62;4c")
+            });
+            let attributes =
+                device_attributes_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10));
+            let _master = terminal.join().unwrap();
+            attributes == Some(vec![62, 4]) && DELIVERED.load(Ordering::SeqCst) == 1
+        }));
+    }
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn window_position_replies() {
+    use super::parse_window_report;
+
+    let parse_window_position = |reply: &[u8]| parse_window_report::<i16>(reply, 3);
+
+    assert_eq!(
+        parse_window_position(b"\x1b[3;120;45t\x1b[?62c"),
+        Some((120, 45))
+    );
+    assert_eq!(
+        parse_window_position(b"\x1b[?62c\x1b[3;-1920;0t"),
+        Some((-1920, 0))
+    );
+    assert_eq!(parse_window_position(b"\x1b[?62c"), None);
+    assert_eq!(parse_window_position(b"\x1b[3;120t"), None);
+    assert_eq!(parse_window_position(b"\x1b[3;1;2;3t"), None);
+    assert_eq!(parse_window_position(b"\x1b[3;x;0t"), None);
+    assert_eq!(parse_window_position(b"\x1b[3;100000;0t"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn window_position_query() {
+    use super::window_position_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[13t\x1b[c", b"\x1b[3;64;32t\x1b[?62c"));
+    assert_eq!(
+        window_position_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some((64, 32))
+    );
+    terminal.join().unwrap();
+
+    // Terminals which refuse the request only reply to the second one.
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62c"));
+    assert_eq!(
+        window_position_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        None
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        window_position_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn window_report_replies() {
+    use super::parse_window_report;
+
+    let reply = b"\x1b[4;600;800t\x1b[6;20;10t\x1b[?62c";
+    assert_eq!(parse_window_report(reply, 4), Some((600u16, 800)));
+    assert_eq!(parse_window_report(reply, 6), Some((20u16, 10)));
+    assert_eq!(parse_window_report::<u16>(reply, 3), None);
+    assert_eq!(parse_window_report::<u16>(b"\x1b[4;600t", 4), None);
+    assert_eq!(parse_window_report::<u16>(b"\x1b[4;-1;800t", 4), None);
+    assert_eq!(parse_window_report::<u16>(b"\x1b[?62c", 4), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn text_area_pixel_query() {
+    use super::text_area_pixels_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(
+            master,
+            b"\x1b[14t\x1b[16t\x1b[c",
+            b"\x1b[4;600;800t\x1b[6;20;10t\x1b[?62c",
+        )
+    });
+    assert_eq!(
+        text_area_pixels_on(
+            slave.as_fd(),
+            slave.as_fd(),
+            Some((80, 24)),
+            Duration::from_secs(10)
+        ),
+        Some((800, 600))
+    );
+    terminal.join().unwrap();
+
+    // Without the text area's size, the cell size is scaled up.
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[6;20;10t\x1b[?62c"));
+    assert_eq!(
+        text_area_pixels_on(
+            slave.as_fd(),
+            slave.as_fd(),
+            Some((80, 24)),
+            Duration::from_secs(10)
+        ),
+        Some((800, 480))
+    );
+    terminal.join().unwrap();
+
+    let (master, slave) = pty();
+    let terminal =
+        std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[6;20;10t\x1b[?62c"));
+    assert_eq!(
+        text_area_pixels_on(slave.as_fd(), slave.as_fd(), None, Duration::from_secs(10)),
+        None
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        text_area_pixels_on(
+            slave.as_fd(),
+            slave.as_fd(),
+            Some((80, 24)),
+            Duration::from_millis(50)
+        ),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn sixel_color_register_replies() {
+    use super::parse_sixel_color_registers;
+
+    assert_eq!(parse_sixel_color_registers(b"\x1b[?1;0;256S"), Some(256));
+    assert_eq!(
+        parse_sixel_color_registers(b"\x1b[?1;0;1024S\x1b[?62;4c"),
+        Some(1024)
+    );
+    assert_eq!(
+        parse_sixel_color_registers(b"\x1b[?62;4c\x1b[?1;0;16S"),
+        Some(16)
+    );
+    // Failure statuses, and replies about other items.
+    assert_eq!(parse_sixel_color_registers(b"\x1b[?1;3;0S"), None);
+    assert_eq!(parse_sixel_color_registers(b"\x1b[?2;0;640;480S"), None);
+    assert_eq!(parse_sixel_color_registers(b"\x1b[?1;0S"), None);
+    assert_eq!(parse_sixel_color_registers(b"\x1b[?62c"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn sixel_color_register_query() {
+    use super::sixel_color_registers_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(master, b"\x1b[?1;1;0S\x1b[c", b"\x1b[?1;0;256S\x1b[?62;4c")
+    });
+    assert_eq!(
+        sixel_color_registers_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some(256)
+    );
+    terminal.join().unwrap();
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62c"));
+    assert_eq!(
+        sixel_color_registers_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        None
+    );
+    terminal.join().unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn osc_color_replies() {
+    use super::parse_osc_color;
+
+    assert_eq!(
+        parse_osc_color(b"\x1b]10;rgb:ffff/8080/0000\x07\x1b[?62c", "10"),
+        Some((255, 128, 0))
+    );
+    assert_eq!(
+        parse_osc_color(b"\x1b]10;rgb:d0d0/cfcf/caca\x1b\\", "10"),
+        Some((0xd0, 0xcf, 0xca))
+    );
+    assert_eq!(
+        parse_osc_color(b"\x1b]10;rgb:f/8/0\x07", "10"),
+        Some((255, 136, 0))
+    );
+    assert_eq!(
+        parse_osc_color(b"\x1b]10;rgb:ff/80/00\x07", "10"),
+        Some((255, 128, 0))
+    );
+    assert_eq!(
+        parse_osc_color(b"\x1b]11;rgb:ffff/ffff/ffff\x07", "10"),
+        None
+    );
+    assert_eq!(parse_osc_color(b"\x1b]10;rgb:ffff/ffff\x07", "10"), None);
+    assert_eq!(parse_osc_color(b"\x1b]10;rgb:fffff/0/0\x07", "10"), None);
+    assert_eq!(parse_osc_color(b"\x1b]10;rgb:ffff/ffff/ffff", "10"), None);
+    assert_eq!(parse_osc_color(b"\x1b[?62c", "10"), None);
+    assert_eq!(
+        parse_osc_color(b"\x1b]4;1;rgb:cdcd/0000/0000\x07", "4;1"),
+        Some((0xcd, 0, 0))
+    );
+    assert_eq!(
+        parse_osc_color(b"\x1b]4;11;rgb:ffff/ffff/0000\x07", "4;1"),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn foreground_color_query() {
+    use super::foreground_color_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(
+            master,
+            b"\x1b]10;?\x07\x1b[c",
+            b"\x1b]10;rgb:1e1e/2020/2828\x1b\\\x1b[?62c",
+        )
+    });
+    assert_eq!(
+        foreground_color_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some((0x1e, 0x20, 0x28))
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        foreground_color_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn palette_color_query() {
+    use super::palette_color_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(
+            master,
+            b"\x1b]4;1;?\x07\x1b[c",
+            b"\x1b]4;1;rgb:cccc/2424/1d1d\x07\x1b[?62c",
+        )
+    });
+    assert_eq!(
+        palette_color_on(slave.as_fd(), slave.as_fd(), 1, Duration::from_secs(10)),
+        Some((0xcc, 0x24, 0x1d))
+    );
+    terminal.join().unwrap();
+
+    let (_master, slave) = pty();
+    assert_eq!(
+        palette_color_on(slave.as_fd(), slave.as_fd(), 1, Duration::from_millis(50)),
+        None
+    );
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn query_sessions() {
+    use super::{with_query_session, with_query_session_on, Stream};
+    use io_lifetimes::AsFd;
+    use rustix::termios::{tcgetattr, ECHO, ICANON};
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let before = tcgetattr(&slave).unwrap();
+    assert_ne!(before.c_lflag & ICANON, 0);
+    let terminal = std::thread::spawn(move || {
+        let master = reply_to(master, b"\x1b[c", b"\x1b[?62;4c");
+        let master = reply_to(master, b"\x1b[6n", b"\x1b[3;7R");
+        reply_to(
+            master,
+            b"\x1b[>0q\x1b[c",
+            b"\x1bP>|Foo 1.0\x1b\\\x1b[?62;4c",
+        )
+    });
+    let results = with_query_session_on(
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_secs(10),
+        |session| {
+            let sixel = session.supports_sixel();
+            // The mode isn't restored between queries.
+            let raw = tcgetattr(&slave).unwrap().c_lflag & (ICANON | ECHO) == 0;
+            let position = session.cursor_position();
+            let version = session.terminal_version();
+            (
+                sixel,
+                raw,
+                position,
+                version,
+                tcgetattr(&slave).unwrap().c_lflag & (ICANON | ECHO) == 0,
+            )
+        },
+    );
+    let _master = terminal.join().unwrap();
+    assert_eq!(
+        results,
+        Some((true, true, Some((6, 2)), Some("Foo 1.0".to_owned()), true))
+    );
+    // It's restored once the session ends.
+    assert_eq!(tcgetattr(&slave).unwrap().c_lflag, before.c_lflag);
+
+    // And after a panic.
+    let (_master, slave) = pty();
+    let panicked = std::panic::catch_unwind(|| {
+        with_query_session_on(
+            slave.as_fd(),
+            slave.as_fd(),
+            Duration::from_millis(50),
+            |_| panic!(),
+        )
+    });
+    assert!(panicked.is_err());
+    assert_eq!(tcgetattr(&slave).unwrap().c_lflag, before.c_lflag);
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        with_query_session(Stream::Stdin, |_| ()).is_none()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn size_source_gathering() {
+    use super::{size_sources_with, Stream};
+
+    // The ioctl is set, but the environment isn't.
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let winsize = rustix::termios::Winsize {
+            ws_row: 40,
+            ws_col: 100,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        rustix::termios::tcsetwinsize(&slave, winsize).unwrap();
+        let sources = size_sources_with(Stream::Stdout, &env(&[("COLUMNS", "100")]));
+        sources.ioctl == Some((100, 40)) && sources.env.is_none()
+    }));
+
+    // The environment is set, but there's no terminal.
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let sources =
+            size_sources_with(Stream::Stdout, &env(&[("COLUMNS", "80"), ("LINES", "24")]));
+        #[cfg(feature = "query")]
+        if sources.cursor_probe.is_some() {
+            return false;
+        }
+        sources.ioctl.is_none() && sources.env == Some((80, 24))
+    }));
+
+    let sources = size_sources_with(Stream::Stdout, &env(&[("COLUMNS", "0"), ("LINES", "24")]));
+    assert_eq!(sources.env, None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn size_probe() {
+    use super::probed_size_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || {
+        reply_to(master, b"\x1b7\x1b[9999;9999H\x1b[6n\x1b8", b"\x1b[40;100R")
+    });
+    assert_eq!(
+        probed_size_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)),
+        Some((100, 40))
+    );
+    terminal.join().unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn cursor_position_replies() {
+    use super::parse_cursor_position;
+
+    assert_eq!(parse_cursor_position(b"\x1b[5;10R"), Some((9, 4)));
+    assert_eq!(parse_cursor_position(b"typed\x1b[1;1R"), Some((0, 0)));
+    assert_eq!(
+        parse_cursor_position(b"\x1b[?62c\x1b[24;80R"),
+        Some((79, 23))
+    );
+    assert_eq!(parse_cursor_position(b"\x1b[0;0R"), None);
+    assert_eq!(parse_cursor_position(b"\x1b[5R"), None);
+    assert_eq!(parse_cursor_position(b"\x1b[5;10"), None);
+    assert_eq!(parse_cursor_position(b"\x1b[?62c"), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn cursor_position_query() {
+    use super::{cursor_position_on, try_cursor_position, Stream, TerminalError};
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[6n", b"\x1b[3;7R"));
+    assert_eq!(
+        cursor_position_on(slave.as_fd(), slave.as_fd(), Duration::from_secs(10)).ok(),
+        Some((6, 2))
+    );
+    terminal.join().unwrap();
+
+    // A terminal which never replies times out.
+    let (_master, slave) = pty();
+    assert!(matches!(
+        cursor_position_on(slave.as_fd(), slave.as_fd(), Duration::from_millis(50)),
+        Err(TerminalError::Timeout)
+    ));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        matches!(
+            try_cursor_position(Stream::Stdin),
+            Err(TerminalError::NotATerminal)
+        )
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "query"))]
+fn sixel_query() {
+    use super::supports_sixel_on;
+    use io_lifetimes::AsFd;
+    use std::time::Duration;
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62;4;22c"));
+    assert!(supports_sixel_on(
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_secs(10)
+    ));
+    terminal.join().unwrap();
+
+    let (master, slave) = pty();
+    let terminal = std::thread::spawn(move || reply_to(master, b"\x1b[c", b"\x1b[?62;22c"));
+    assert!(!supports_sixel_on(
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_secs(10)
+    ));
+    terminal.join().unwrap();
+
+    // A terminal which never replies.
+    let (_master, slave) = pty();
+    assert!(!supports_sixel_on(
+        slave.as_fd(),
+        slave.as_fd(),
+        Duration::from_millis(50)
+    ));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn bracketed_paste_support() {
+    use super::supports_bracketed_paste_with;
+
+    assert!(supports_bracketed_paste_with(true, Some("xterm-256color")));
+    assert!(!supports_bracketed_paste_with(true, Some("linux")));
+    assert!(!supports_bracketed_paste_with(true, Some("dumb")));
+    assert!(!supports_bracketed_paste_with(false, Some("xterm")));
+}
+
+#[test]
+#[cfg(all(unix, feature = "term"))]
+fn dangerous_pastes() {
+    use super::{paste_is_dangerous, Stream};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        std::env::set_var("TERM", "dumb");
+        let dumb = paste_is_dangerous(Stream::Stdin);
+        std::env::set_var("TERM", "linux");
+        let linux = paste_is_dangerous(Stream::Stdin);
+        std::env::set_var("TERM", "xterm-256color");
+        dumb && linux && !paste_is_dangerous(Stream::Stdin)
+    }));
+
+    // Input from a pipe isn't pasted.
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        std::env::set_var("TERM", "dumb");
+        !paste_is_dangerous(Stream::Stdin)
+    }));
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn safe_charsets() {
+    use super::{safe_charset_with, Charset};
+
+    assert_eq!(
+        safe_charset_with(&env(&[("LANG", "en_US.UTF-8")])),
+        Charset::Unicode
+    );
+    assert_eq!(
+        safe_charset_with(&env(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")])),
+        Charset::Ascii
+    );
+    assert_eq!(
+        safe_charset_with(&env(&[("LANG", "POSIX")])),
+        Charset::Ascii
+    );
+    assert_eq!(safe_charset_with(&env(&[])), Charset::Ascii);
+
+    #[cfg(unix)]
+    {
+        use super::{safe_charset, Stream};
+
+        // A `C` locale over a terminal still gets ASCII, and a UTF-8 one
+        // gets Unicode.
+        assert!(in_child(|| {
+            let (_master, slave) = pty();
+            rustix::io::dup2_stdout(&slave).unwrap();
+            std::env::remove_var("LC_CTYPE");
+            std::env::remove_var("LANG");
+            std::env::set_var("LC_ALL", "C");
+            let c = safe_charset(Stream::Stdout);
+            std::env::set_var("LC_ALL", "C.UTF-8");
+            c == Charset::Ascii && safe_charset(Stream::Stdout) == Charset::Unicode
+        }));
+
+        // A pipe gets ASCII whatever the locale.
+        assert!(in_child(|| {
+            let (_reader, writer) = rustix::io::pipe().unwrap();
+            rustix::io::dup2_stdout(&writer).unwrap();
+            std::env::set_var("LC_ALL", "C.UTF-8");
+            safe_charset(Stream::Stdout) == Charset::Ascii
+        }));
+    }
+}
+
+#[test]
+#[cfg(feature = "term")]
+fn reset_safety() {
+    use super::safe_to_reset_with;
+
+    let xterm = env(&[("TERM", "xterm-256color")]);
+    assert!(safe_to_reset_with(&xterm, true, true));
+    assert!(!safe_to_reset_with(&xterm, false, true));
+    // A background job.
+    assert!(!safe_to_reset_with(&xterm, true, false));
+    // Multiplexers.
+    assert!(!safe_to_reset_with(
+        &env(&[
+            ("TERM", "tmux-256color"),
+            ("TMUX", "/tmp/tmux-1000/default,1,0")
+        ]),
+        true,
+        true
+    ));
+    assert!(!safe_to_reset_with(
+        &env(&[
+            ("TERM", "xterm-256color"),
+            ("TMUX", "/tmp/tmux-1000/default,1,0")
+        ]),
+        true,
+        true
+    ));
+    assert!(!safe_to_reset_with(&env(&[("TERM", "screen")]), true, true));
+    assert!(!safe_to_reset_with(
+        &env(&[("TERM", "xterm"), ("ZELLIJ", "0")]),
+        true,
+        true
+    ));
+
+    #[cfg(unix)]
+    assert!(in_child(|| {
+        use super::{safe_to_reset, Stream};
+
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        for name in &["STY", "ZELLIJ", "TMUX"] {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("TERM", "xterm-256color");
+        let standalone = safe_to_reset(Stream::Stdout);
+        std::env::set_var("TMUX", "/tmp/tmux-1000/default,1,0");
+        standalone && !safe_to_reset(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "color"))]
+fn controlling_tty_color() {
+    use super::color_via_controlling_tty;
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        for (name, _) in std::env::vars() {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("TERM", "xterm");
+        unsafe {
+            // Closing the pty would hang up its session, which is ours.
+            libc::signal(libc::SIGHUP, libc::SIG_IGN);
+            libc::setsid();
+        }
+        // With no controlling terminal, there's no `/dev/tty`.
+        if color_via_controlling_tty() {
+            return false;
+        }
+        let (_master, slave) = pty();
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY as _, 0) };
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        rustix::io::dup2_stderr(&writer).unwrap();
+        if !color_via_controlling_tty() {
+            return false;
+        }
+        std::env::set_var("NO_COLOR", "1");
+        !color_via_controlling_tty()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn stdin_controlling_tty() {
+    use super::stdin_is_controlling_tty;
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        unsafe {
+            libc::signal(libc::SIGHUP, libc::SIG_IGN);
+            libc::setsid();
+        }
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        // With no controlling terminal, there's nothing to compare with.
+        if stdin_is_controlling_tty().is_some() {
+            return false;
+        }
+        unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY as _, 0) };
+        if stdin_is_controlling_tty() != Some(true) {
+            return false;
+        }
+        let (_other_master, other) = pty();
+        rustix::io::dup2_stdin(&other).unwrap();
+        if stdin_is_controlling_tty() != Some(false) {
+            return false;
+        }
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        stdin_is_controlling_tty().is_none()
+    }));
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn escape_stripping() {
+    use super::strip_escapes;
+
+    assert_eq!(strip_escapes("plain"), "plain");
+    assert_eq!(strip_escapes("\x1b[1;31merror\x1b[0m: oops"), "error: oops");
+    assert_eq!(
+        strip_escapes("see \x1b]8;;https://example.com\x1b\\docs\x1b]8;;\x07"),
+        "see docs"
+    );
+    assert_eq!(strip_escapes("\x1b(0qqq\x1b(B\x1b7!"), "qqq!");
+    assert_eq!(strip_escapes("cut off \x1b[31"), "cut off ");
+}
+
+#[test]
+#[cfg(all(unix, feature = "color"))]
+fn panic_color_filter() {
+    use super::install_panic_color_filter;
+
+    assert!(in_child(|| {
+        for (name, _) in std::env::vars() {
+            std::env::remove_var(name);
+        }
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stderr(&writer).unwrap();
+        drop(writer);
+        install_panic_color_filter();
+        let result = std::thread::Builder::new()
+            .name("filtered".to_owned())
+            .spawn(|| panic!("\x1b[31mred\x1b[0m {}", 42))
+            .unwrap()
+            .join();
+        if result.is_ok() {
+            return false;
+        }
+        let _ = std::panic::take_hook();
+        let null = rustix::fs::openat(
+            rustix::fs::cwd(),
+            "/dev/null",
+            rustix::fs::OFlags::WRONLY,
+            rustix::fs::Mode::empty(),
+        )
+        .unwrap();
+        rustix::io::dup2_stderr(&null).unwrap();
+
+        let mut output = Vec::new();
+        std::io::Read::read_to_end(&mut std::fs::File::from(reader), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        output.starts_with("thread 'filtered' panicked at ") && output.ends_with(":\nred 42\n")
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_sockets() {
+    use super::{is_file, is_pipe, is_unix_socket, Stream};
+
+    assert!(in_child(|| {
+        let mut fds = [0; 2];
+        unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) };
+        unsafe { libc::dup2(fds[0], 1) };
+        is_unix_socket(Stream::Stdout) && !is_pipe(Stream::Stdout) && !is_file(Stream::Stdout)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        !is_unix_socket(Stream::Stdout)
+    }));
+
+    assert!(in_child(|| {
+        use std::os::unix::io::AsRawFd;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        unsafe { libc::dup2(listener.as_raw_fd(), 1) };
+        !is_unix_socket(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn debugger_detection() {
+    use super::{traced_in_status_file, under_debugger};
+
+    let dir = std::env::temp_dir().join(format!("is-terminal-status-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let status = |name: &str, tracer: &str| {
+        let path = dir.join(name);
+        let contents = format!(
+            "Name:\tcargo\nState:\tR (running)\nTracerPid:\t{}\nUid:\t0\n",
+            tracer
+        );
+        std::fs::write(&path, contents).unwrap();
+        path
+    };
+    assert!(traced_in_status_file(&status("traced", "4242")));
+    assert!(!traced_in_status_file(&status("untraced", "0")));
+    assert!(!traced_in_status_file(&status("garbled", "gdb")));
+    assert!(!traced_in_status_file(&dir.join("missing")));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The answer is remembered, and matches the real status file.
+    let traced = traced_in_status_file(std::path::Path::new("/proc/self/status"));
+    assert_eq!(under_debugger(), traced);
+    assert_eq!(under_debugger(), traced);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn shell_classification() {
+    use super::{shell_with, Shell};
+
+    // Windows PowerShell adds the user's module directory on startup.
+    assert_eq!(
+        shell_with(
+            None,
+            &env(&[(
+                "PSModulePath",
+                "C:\\Users\\me\\Documents\\WindowsPowerShell\\Modules;C:\\Program Files\\WindowsPowerShell\\Modules"
+            )])
+        ),
+        Shell::PowerShell
+    );
+    assert_eq!(
+        shell_with(
+            None,
+            &env(&[
+                (
+                    "PSModulePath",
+                    "/home/me/.local/share/powershell/Modules:/usr/local/share/powershell/Modules"
+                ),
+                ("SHELL", "/bin/bash")
+            ])
+        ),
+        Shell::Pwsh
+    );
+    // Under cmd, only the system directories are listed.
+    assert_eq!(
+        shell_with(
+            Some("cmd.exe"),
+            &env(&[(
+                "PSModulePath",
+                "C:\\Program Files\\WindowsPowerShell\\Modules"
+            )])
+        ),
+        Shell::Cmd
+    );
+    assert_eq!(
+        shell_with(None, &env(&[("SHELL", "/bin/bash")])),
+        Shell::Bash
+    );
+    // The parent process beats the login shell.
+    assert_eq!(
+        shell_with(Some("-zsh"), &env(&[("SHELL", "/bin/bash")])),
+        Shell::Zsh
+    );
+    assert_eq!(
+        shell_with(Some("cargo"), &env(&[("SHELL", "/usr/bin/fish")])),
+        Shell::Fish
+    );
+    assert_eq!(shell_with(Some("make"), &env(&[])), Shell::Unknown);
+    assert_eq!(shell_with(Some("nu"), &env(&[])), Shell::Nushell);
+    assert_eq!(
+        shell_with(
+            Some("cargo"),
+            &env(&[("NU_VERSION", "0.92.1"), ("SHELL", "/bin/bash")])
+        ),
+        Shell::Nushell
+    );
+    assert_eq!(
+        shell_with(None, &env(&[("SHELL", "/bin/tcsh")])),
+        Shell::Unknown
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn structured_output_preference() {
+    use super::prefers_structured_output_with;
+
+    let nu = env(&[("NU_VERSION", "0.92.1")]);
+    assert!(prefers_structured_output_with(&nu, Some("cargo"), false));
+    assert!(prefers_structured_output_with(&env(&[]), Some("nu"), false));
+    // Output to a terminal is displayed.
+    assert!(!prefers_structured_output_with(&nu, Some("cargo"), true));
+    assert!(!prefers_structured_output_with(
+        &env(&[("SHELL", "/bin/bash")]),
+        Some("bash"),
+        false
+    ));
+    assert!(!prefers_structured_output_with(&env(&[]), None, false));
+
+    // `STRUCTURED_OUTPUT` decides it either way.
+    assert!(!prefers_structured_output_with(
+        &env(&[("NU_VERSION", "0.92.1"), ("STRUCTURED_OUTPUT", "0")]),
+        Some("nu"),
+        false
+    ));
+    assert!(prefers_structured_output_with(
+        &env(&[("STRUCTURED_OUTPUT", "yes")]),
+        Some("bash"),
+        true
+    ));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn keyboard_hints() {
+    use super::keyboard_hint_in;
+
+    let hint = |vars: &[(&str, &str)]| keyboard_hint_in(&env(vars));
+    assert_eq!(hint(&[("XKB_DEFAULT_LAYOUT", "de")]), Some("de".to_owned()));
+    assert_eq!(
+        hint(&[("XKB_DEFAULT_LAYOUT", "us,ru"), ("LANG", "de_DE.UTF-8")]),
+        Some("us".to_owned())
+    );
+    assert_eq!(hint(&[("LANG", "fr_CA.UTF-8")]), Some("ca".to_owned()));
+    assert_eq!(hint(&[("LANG", "sr_RS@latin")]), Some("rs".to_owned()));
+    assert_eq!(
+        hint(&[("XKB_DEFAULT_LAYOUT", ""), ("LANG", "C.UTF-8")]),
+        None
+    );
+    assert_eq!(hint(&[("LANG", "POSIX")]), None);
+    assert_eq!(hint(&[]), None);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn mosh_detection() {
+    use super::is_mosh_in;
+
+    assert!(is_mosh_in(&env(&[("MOSH_KEY", "abcdefghijklmnopqrstuv")])));
+    assert!(is_mosh_in(&env(&[("MOSH_CONNECTION", "192.0.2.1 60001")])));
+    assert!(!is_mosh_in(&env(&[("SSH_CONNECTION", "192.0.2.1 22")])));
+    assert!(!is_mosh_in(&env(&[])));
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use super::proc_stat_command_and_parent;
+
+        assert_eq!(
+            proc_stat_command_and_parent("4242 (mosh-server) S 1 4242 4242 0 -1"),
+            Some(("mosh-server", 1))
+        );
+        assert_eq!(
+            proc_stat_command_and_parent("77 (a) b (c)) R 76 77"),
+            Some(("a) b (c)", 76))
+        );
+        assert_eq!(proc_stat_command_and_parent("77 (bash"), None);
+        assert_eq!(proc_stat_command_and_parent("77 (bash) S"), None);
+    }
+}
+
+#[test]
+#[cfg(feature = "anstyle")]
+fn anstream_color_choices() {
+    use super::{color_choice_for, color_level_with};
+    use colorchoice::ColorChoice;
+
+    let capable = color_level_with(&env(&[("TERM", "xterm-256color")]), true);
+    assert_eq!(color_choice_for(capable), ColorChoice::Always);
+    let truecolor = color_level_with(
+        &env(&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")]),
+        true,
+    );
+    assert_eq!(color_choice_for(truecolor), ColorChoice::Always);
+
+    let redirected = color_level_with(&env(&[("TERM", "xterm-256color")]), false);
+    assert_eq!(color_choice_for(redirected), ColorChoice::Never);
+    let no_color = color_level_with(&env(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")]), true);
+    assert_eq!(color_choice_for(no_color), ColorChoice::Never);
+}
+
+#[test]
+#[cfg(all(unix, feature = "color"))]
+fn conditional_ansi_writes() {
+    use super::{supports_ansi, write_ansi_if_supported, Stream};
+
+    assert!(in_child(|| {
+        std::env::set_var("TERM", "xterm");
+        std::env::set_var("NO_COLOR", "1");
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let mut written = Vec::new();
+        // NO_COLOR doesn't forbid other escape sequences.
+        if !supports_ansi(Stream::Stdout)
+            || !write_ansi_if_supported(Stream::Stdout, &mut written, "\x1b[2K").unwrap()
+            || written != b"\x1b[2K"
+        {
+            return false;
+        }
+
+        std::env::set_var("TERM", "dumb");
+        let mut written = Vec::new();
+        !write_ansi_if_supported(Stream::Stdout, &mut written, "\x1b[2K").unwrap()
+            && written.is_empty()
+    }));
+
+    assert!(in_child(|| {
+        std::env::set_var("TERM", "xterm");
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let mut written = Vec::new();
+        !supports_ansi(Stream::Stdout)
+            && !write_ansi_if_supported(Stream::Stdout, &mut written, "\x1b[1m").unwrap()
+            && written.is_empty()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "term"))]
+fn xterm_capabilities() {
+    use super::{capabilities, Capabilities, Stream};
+
+    assert!(in_child(|| {
+        for (name, _) in std::env::vars() {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("TERM", "xterm-256color");
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+
+        let mut expected = Capabilities::ANSI
+            | Capabilities::MOUSE
+            | Capabilities::BRACKETED_PASTE
+            | Capabilities::TITLE;
+        if cfg!(feature = "color") {
+            expected |= Capabilities::COLOR_16 | Capabilities::COLOR_256;
+        }
+        // With nobody to answer the sixel query, it times out.
+        capabilities(Stream::Stdout) == expected
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        std::env::remove_var("FORCE_COLOR");
+        std::env::remove_var("CLICOLOR_FORCE");
+        capabilities(Stream::Stdout).is_empty()
+    }));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn recorded_output() {
+    use super::{output_is_recorded, Stream};
+
+    // Fork a process named `name` which holds `held` open, and run `f`
+    // while it's there.
+    fn with_peer(name: &[u8], held: io_lifetimes::OwnedFd, f: impl FnOnce() -> bool) -> bool {
+        let (ready_reader, ready_writer) = rustix::io::pipe().unwrap();
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            unsafe {
+                libc::prctl(libc::PR_SET_NAME, name.as_ptr());
+                rustix::io::write(&ready_writer, b"x").ok();
+                loop {
+                    libc::pause();
+                }
+            }
+        }
+        drop(held);
+        drop(ready_writer);
+        let mut buf = [0];
+        rustix::io::read(&ready_reader, &mut buf).unwrap();
+        let result = f();
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+        result
+    }
+
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        drop(writer);
+        with_peer(b"tee\0", reader, || output_is_recorded(Stream::Stdout))
+    }));
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        drop(writer);
+        with_peer(b"cat\0", reader, || !output_is_recorded(Stream::Stdout))
+    }));
+    assert!(in_child(|| {
+        let (master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        with_peer(b"script\0", master, || output_is_recorded(Stream::Stdout))
+    }));
+}
+
+#[test]
+fn stream_directions() {
+    use super::Stream;
+
+    assert!(Stream::Stdin.is_input());
+    assert!(!Stream::Stdin.is_output());
+    assert!(!Stream::Stdout.is_input());
+    assert!(Stream::Stdout.is_output());
+    assert!(!Stream::Stderr.is_input());
+    assert!(Stream::Stderr.is_output());
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn env_sources() {
+    use super::{
+        color_env_preference_in, color_level_in, supports_color_in, ColorLevel, EnvSource, Stream,
+        SystemEnv,
+    };
+
+    let forced = env(&[("FORCE_COLOR", "3")]);
+    assert_eq!(EnvSource::get(&forced, "FORCE_COLOR").as_deref(), Some("3"));
+    assert!(supports_color_in(Stream::Stdout, &forced));
+    assert_eq!(
+        color_level_in(Stream::Stdout, &forced),
+        ColorLevel::TrueColor
+    );
+
+    let disabled = env(&[("NO_COLOR", "1"), ("FORCE_COLOR", "3")]);
+    assert!(!supports_color_in(Stream::Stdout, &disabled));
+    assert_eq!(color_env_preference_in(&disabled), Some(false));
+
+    // `ATTY_FORCE` is read from `env` too, and not from the process.
+    #[cfg(unix)]
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let forced_tty = env(&[("ATTY_FORCE", "1"), ("TERM", "xterm-256color")]);
+        let injected = color_level_in(Stream::Stdout, &forced_tty);
+        std::env::set_var("ATTY_FORCE", "1");
+        let leaked = color_level_in(Stream::Stdout, &env(&[("TERM", "xterm-256color")]));
+        injected == ColorLevel::Ansi256 && leaked == ColorLevel::None
+    }));
+
+    assert_eq!(SystemEnv.get("PATH"), std::env::var("PATH").ok());
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn color_targets() {
+    use super::{color_target_with, Stream};
+
+    let plain = env(&[]);
+    assert_eq!(color_target_with(&plain, true, true), Some(Stream::Stdout));
+    assert_eq!(color_target_with(&plain, false, true), Some(Stream::Stderr));
+    assert_eq!(color_target_with(&plain, false, false), None);
+    assert_eq!(
+        color_target_with(&env(&[("NO_COLOR", "1")]), true, true),
+        None
+    );
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn log_targets() {
+    use super::{log_target_with, LogTarget};
+
+    let xterm = env(&[("TERM", "xterm-256color")]);
+    assert_eq!(log_target_with(&xterm, true), LogTarget::TtyColor);
+    assert_eq!(
+        log_target_with(&env(&[("TERM", "xterm"), ("NO_COLOR", "1")]), true),
+        LogTarget::TtyPlain
+    );
+    assert_eq!(
+        log_target_with(&env(&[("TERM", "dumb")]), true),
+        LogTarget::TtyPlain
+    );
+    assert_eq!(log_target_with(&xterm, false), LogTarget::FileOrPipe);
+    assert_eq!(
+        log_target_with(&env(&[("FORCE_COLOR", "1")]), false),
+        LogTarget::FileOrPipe
+    );
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn ci_detection() {
+    use super::is_ci_with;
+
+    assert!(is_ci_with(&env(&[("CI", "true")])));
+    assert!(is_ci_with(&env(&[("CI", "1")])));
+    assert!(!is_ci_with(&env(&[("CI", "false")])));
+    assert!(!is_ci_with(&env(&[("CI", "0"), ("TF_BUILD", "True")])));
+    assert!(is_ci_with(&env(&[("TF_BUILD", "True")])));
+    assert!(!is_ci_with(&env(&[])));
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn fancy_output_policy() {
+    use super::fancy_output_with;
+
+    let xterm = env(&[("TERM", "xterm-256color")]);
+    let ci = env(&[("TERM", "xterm-256color"), ("CI", "true")]);
+    let forced_ci = env(&[
+        ("TERM", "xterm-256color"),
+        ("CI", "true"),
+        ("FORCE_COLOR", "1"),
+    ]);
+    let clicolor_force = env(&[("CLICOLOR_FORCE", "1")]);
+    let no_color = env(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")]);
+    let no_color_forced = env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")]);
+    let clicolor = env(&[("TERM", "xterm-256color"), ("CLICOLOR", "0")]);
+    let dumb = env(&[("TERM", "dumb")]);
+    // (environment, [tty, screen reader, journald], expected)
+    #[rustfmt::skip]
+    let table: &[(&std::collections::HashMap<String, String>, [bool; 3], bool)] = &[
+        (&xterm, [true, false, false], true),
+        (&xterm, [false, false, false], false),
+        // Plain output under CI, unless forced.
+        (&ci, [true, false, false], false),
+        (&ci, [false, false, false], false),
+        (&forced_ci, [false, false, false], true),
+        (&clicolor_force, [false, true, true], true),
+        // Disabling overrides.
+        (&no_color, [true, false, false], false),
+        (&no_color_forced, [true, false, false], false),
+        (&clicolor, [true, false, false], false),
+        (&dumb, [true, false, false], false),
+        // Known-plain environments.
+        (&xterm, [true, true, false], false),
+        (&xterm, [true, false, true], false),
+    ];
+    for &(env, [tty, screen_reader, journald], expected) in table {
+        assert_eq!(
+            fancy_output_with(env, tty, screen_reader, journald),
+            expected,
+            "{:?} {} {} {}",
+            env,
+            tty,
+            screen_reader,
+            journald
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn non_interactive_explanations() {
+    use super::{why_not_interactive_with, FileKind};
+
+    let xterm = env(&[("TERM", "xterm")]);
+    let tty = (true, FileKind::Tty);
+    assert_eq!(why_not_interactive_with(&xterm, tty, tty), None);
+    assert_eq!(
+        why_not_interactive_with(&xterm, (false, FileKind::Pipe), tty).as_deref(),
+        Some("stdin is a pipe")
+    );
+    assert_eq!(
+        why_not_interactive_with(&xterm, tty, (false, FileKind::File)).as_deref(),
+        Some("stdout is redirected to a file")
+    );
+    assert_eq!(
+        why_not_interactive_with(
+            &env(&[
+                ("TERM", "xterm"),
+                ("CI", "true"),
+                ("GITHUB_ACTIONS", "true")
+            ]),
+            (false, FileKind::Pipe),
+            tty
+        )
+        .as_deref(),
+        Some("stdin is a pipe; running under CI (GITHUB_ACTIONS)")
+    );
+    assert_eq!(
+        why_not_interactive_with(&env(&[("TERM", "xterm"), ("CI", "1")]), tty, tty).as_deref(),
+        Some("running under CI (CI)")
+    );
+    assert_eq!(
+        why_not_interactive_with(&env(&[("TERM", "xterm"), ("NO_COLOR", "1")]), tty, tty)
+            .as_deref(),
+        Some("NO_COLOR is set")
+    );
+    assert_eq!(
+        why_not_interactive_with(&env(&[("TERM", "dumb")]), tty, tty).as_deref(),
+        Some("TERM is dumb")
+    );
+
+    #[cfg(unix)]
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        super::why_not_interactive().map_or(false, |why| why.starts_with("stdin is a pipe"))
+    }));
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn trailing_whitespace_preservation() {
+    use super::preserves_trailing_whitespace_with;
+
+    let plain = env(&[]);
+    let ci = env(&[("CI", "true")]);
+    // (environment, [tty, file, pipe], expected)
+    #[rustfmt::skip]
+    let table: &[(&std::collections::HashMap<String, String>, [bool; 3], bool)] = &[
+        (&plain, [true, false, false], true),
+        (&plain, [false, true, false], true),
+        (&plain, [false, false, true], true),
+        (&plain, [false, false, false], false),
+        (&ci, [true, false, false], true),
+        (&ci, [false, true, false], true),
+        (&ci, [false, false, true], false),
+    ];
+    for &(env, [tty, file, pipe], expected) in table {
+        assert_eq!(
+            preserves_trailing_whitespace_with(env, tty, file, pipe),
+            expected,
+            "{:?} {} {} {}",
+            env,
+            tty,
+            file,
+            pipe
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use super::{preserves_trailing_whitespace, Stream};
+
+        assert!(in_child(|| {
+            let path =
+                std::env::temp_dir().join(format!("is-terminal-whitespace-{}", std::process::id()));
+            let file = std::fs::File::create(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            rustix::io::dup2_stdout(&file).unwrap();
+            preserves_trailing_whitespace(Stream::Stdout)
+        }));
+        assert!(in_child(|| {
+            let (_master, slave) = pty();
+            rustix::io::dup2_stdout(&slave).unwrap();
+            preserves_trailing_whitespace(Stream::Stdout)
+        }));
+    }
+}
+
+#[test]
+#[cfg(feature = "color")]
+fn literal_escapes() {
+    use super::escapes_would_be_literal_with;
+
+    let plain = env(&[]);
+    let ci = env(&[("CI", "true")]);
+    let no_color = env(&[("NO_COLOR", "1")]);
+    // (environment, [tty, pager, file or pipe], expected)
+    #[rustfmt::skip]
+    let table: &[(&std::collections::HashMap<String, String>, [bool; 3], bool)] = &[
+        (&plain,    [true,  false, false], false),
+        (&plain,    [false, false, true],  true),
+        (&plain,    [false, true,  true],  false),
+        (&plain,    [false, false, false], false),
+        (&ci,       [true,  false, false], false),
+        (&ci,       [false, true,  true],  false),
+        (&ci,       [false, false, true],  true),
+        (&ci,       [false, false, false], true),
+        (&no_color, [true,  false, false], true),
+        (&no_color, [false, true,  true],  true),
+    ];
+    for &(env, [tty, pager, recorded], expected) in table {
+        assert_eq!(
+            escapes_would_be_literal_with(env, tty, pager, recorded),
+            expected,
+            "{:?} {} {} {}",
+            env,
+            tty,
+            pager,
+            recorded
+        );
+    }
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn inspections() {
+    use super::{inspect, FileKind, Stream};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        let expected = rustix::termios::ttyname(&slave, Vec::new()).unwrap();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let inspection = inspect(Stream::Stdout);
+        inspection.is_tty
+            && inspection.file_kind == FileKind::Tty
+            && inspection.name.as_deref().map(str::as_bytes) == Some(expected.as_bytes())
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let inspection = inspect(Stream::Stdout);
+        !inspection.is_tty
+            && inspection.file_kind == FileKind::Pipe
+            && (cfg!(not(any(target_os = "linux", target_os = "android")))
+                || inspection
+                    .name
+                    .as_deref()
+                    .map_or(false, |name| name.starts_with("pipe:[")))
+    }));
+
+    // A pipe reported as a terminal is listed as one, as `is` says.
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        std::env::set_var("ATTY_FORCE", "1");
+        let inspection = inspect(Stream::Stdout);
+        inspection.is_tty && inspection.file_kind == FileKind::Tty
+    }));
+
+    #[cfg(feature = "testutil")]
+    assert!(in_child(|| {
+        use super::testutil::{set_override, with_fake_tty};
+
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        let faked = with_fake_tty(Stream::Stdout, || inspect(Stream::Stdout));
+        set_override(Stream::Stdout, Some(true));
+        let overridden = inspect(Stream::Stdout);
+        faked.is_tty
+            && faked.file_kind == FileKind::Tty
+            && overridden.is_tty
+            && overridden.file_kind == FileKind::Tty
+    }));
+
+    assert!(in_child(|| {
+        let file = rustix::fs::openat(
+            rustix::fs::cwd(),
+            "Cargo.toml",
+            rustix::fs::OFlags::RDONLY,
+            rustix::fs::Mode::empty(),
+        )
+        .unwrap();
+        rustix::io::dup2_stdout(&file).unwrap();
+        inspect(Stream::Stdout)
+            == super::Inspection {
+                is_tty: false,
+                file_kind: FileKind::File,
+                name: None,
+            }
+    }));
+}
+
+#[test]
+#[cfg(all(windows, feature = "std"))]
+fn inspections() {
+    use super::{inspect_handle, FileKind};
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+
+    let null = std::fs::OpenOptions::new().write(true).open("NUL").unwrap();
+    let inspection = inspect_handle(null.as_raw_handle() as _, false);
+    assert_eq!(inspection.file_kind, FileKind::CharDevice);
+    assert_eq!(inspection.name, None);
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = std::fs::File::create(dir.path().join("file")).unwrap();
+    let inspection = inspect_handle(file.as_raw_handle() as _, false);
+    assert_eq!(inspection.file_kind, FileKind::File);
+    assert_eq!(inspection.name, None);
+
+    let (mut reader, mut writer) = (0, 0);
+    assert_ne!(
+        unsafe { CreatePipe(&mut reader, &mut writer, core::ptr::null(), 0) },
+        0
+    );
+    let inspection = inspect_handle(writer, false);
+    assert_eq!(inspection.file_kind, FileKind::Pipe);
+    assert!(inspection.name.is_some());
+    unsafe {
+        windows_sys::Win32::Foundation::CloseHandle(reader);
+        windows_sys::Win32::Foundation::CloseHandle(writer);
+    }
+
+    assert_eq!(inspect_handle(0, false).file_kind, FileKind::Unknown);
+    assert_eq!(inspect_handle(0, true).file_kind, FileKind::Tty);
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn terminal_errors() {
+    use super::{try_size, Stream, TerminalError};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        // A new pty's size is unknown until someone sets it.
+        if !matches!(try_size(Stream::Stdout), Err(TerminalError::Unsupported)) {
+            return false;
+        }
+        let winsize = rustix::termios::Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        rustix::termios::tcsetwinsize(&slave, winsize).unwrap();
+        try_size(Stream::Stdout).ok() == Some((80, 24))
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        matches!(try_size(Stream::Stdout), Err(TerminalError::NotATerminal))
+    }));
+
+    assert!(in_child(|| {
+        unsafe { libc::close(1) };
+        matches!(try_size(Stream::Stdout), Err(TerminalError::BadDescriptor))
+    }));
+
+    #[cfg(feature = "color")]
+    assert!(in_child(|| {
+        unsafe { libc::close(1) };
+        matches!(
+            super::try_color_level(Stream::Stdout),
+            Err(TerminalError::BadDescriptor)
+        ) && super::try_color_level(Stream::Stdin).is_ok()
+    }));
+
+    assert_eq!(
+        TerminalError::Timeout.to_string(),
+        "the terminal didn't reply in time"
+    );
+    let io = TerminalError::from(std::io::Error::from(std::io::ErrorKind::Other));
+    assert!(std::error::Error::source(&io).is_some());
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn sigpipe() {
+    use super::{restore_default_sigpipe, sigpipe_ignored};
+
+    // The test harness runs under Rust's runtime, which ignores SIGPIPE.
+    assert!(sigpipe_ignored());
+    assert!(in_child(|| {
+        restore_default_sigpipe();
+        if sigpipe_ignored() {
+            return false;
+        }
+        // With the default disposition, a write to a closed pipe kills
+        // the writer.
+        !in_child(|| {
+            let (reader, writer) = rustix::io::pipe().unwrap();
+            drop(reader);
+            let _ = rustix::io::write(&writer, b"x");
+            true
+        })
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn unbuffered() {
+    use super::{is_unbuffered, Stream};
+    use rustix::fs::{cwd, openat, Mode, OFlags};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        is_unbuffered(Stream::Stdout) == Some(true)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        is_unbuffered(Stream::Stdout) == Some(true)
+    }));
+
+    assert!(in_child(|| {
+        let file = openat(cwd(), "Cargo.toml", OFlags::RDONLY, Mode::empty()).unwrap();
+        rustix::io::dup2_stdout(&file).unwrap();
+        is_unbuffered(Stream::Stdout) == Some(false)
+    }));
+
+    assert!(in_child(|| {
+        let file = openat(
+            cwd(),
+            "Cargo.toml",
+            OFlags::RDONLY | OFlags::SYNC,
+            Mode::empty(),
+        )
+        .unwrap();
+        rustix::io::dup2_stdout(&file).unwrap();
+        is_unbuffered(Stream::Stdout) == Some(true)
+    }));
+
+    assert!(in_child(|| {
+        unsafe { libc::close(1) };
+        is_unbuffered(Stream::Stdout).is_none()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn files() {
+    use super::{is_file, Stream};
+
+    assert!(in_child(|| {
+        let file = rustix::fs::openat(
+            rustix::fs::cwd(),
+            "Cargo.toml",
+            rustix::fs::OFlags::RDONLY,
+            rustix::fs::Mode::empty(),
+        )
+        .unwrap();
+        rustix::io::dup2_stdout(&file).unwrap();
+        is_file(Stream::Stdout)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        !is_file(Stream::Stdout)
+    }));
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn pipe_capacities() {
+    use super::{pipe_capacity, Stream};
+    use std::os::unix::io::AsRawFd;
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        unsafe { libc::fcntl(writer.as_raw_fd(), libc::F_SETPIPE_SZ, 65536 * 2) };
+        rustix::io::dup2_stdout(&writer).unwrap();
+        pipe_capacity(Stream::Stdout) == Some(65536 * 2)
+    }));
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        pipe_capacity(Stream::Stdout).is_none()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "std"))]
+fn stdin_eof() {
+    use super::stdin_at_eof;
+
+    // All writers have exited.
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        drop(writer);
+        stdin_at_eof()
+    }));
+    // A writer is still there, or has left unread input behind.
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        !stdin_at_eof()
+    }));
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::write(&writer, b"input").unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        drop(writer);
+        !stdin_at_eof()
+    }));
+    // Regular files, at and before their end.
+    assert!(in_child(|| {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let path = std::env::temp_dir().join(format!("is-terminal-eof-{}", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"input").unwrap();
+        let mut file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        rustix::io::dup2_stdin(&file).unwrap();
+        if stdin_at_eof() {
+            return false;
+        }
+        file.seek(SeekFrom::End(0)).unwrap();
+        stdin_at_eof()
+    }));
+}
+
+#[test]
+fn negated_checks() {
+    use super::{is, isnt, isnt_tty, not_interactive, Stream};
+
+    for &stream in &[Stream::Stdin, Stream::Stdout, Stream::Stderr] {
+        assert_eq!(isnt(stream), !is(stream));
+        assert_eq!(isnt_tty(stream), !is(stream));
+    }
+    assert_eq!(not_interactive(), !is(Stream::Stdin) || !is(Stream::Stdout));
+}
+
+#[test]
+#[cfg(all(windows, feature = "std"))]
+fn msys_like() {
+    use super::msys_like_with;
+
+    assert!(msys_like_with(true, Some("xterm-256color")));
+    assert!(msys_like_with(true, Some("xterm")));
+    assert!(!msys_like_with(false, Some("xterm-256color")));
+    assert!(!msys_like_with(true, Some("dumb")));
+    assert!(!msys_like_with(true, None));
+}
+
+#[test]
+#[cfg(all(windows, feature = "std"))]
+fn conemu() {
+    use super::is_conemu_with;
+
+    assert!(is_conemu_with(&env(&[
+        ("ConEmuANSI", "ON"),
+        ("ConEmuPID", "1234")
+    ])));
+    assert!(!is_conemu_with(&env(&[
+        ("ConEmuANSI", "OFF"),
+        ("ConEmuPID", "1234")
+    ])));
+    assert!(!is_conemu_with(&env(&[])));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn colorfgbg_values() {
+    use super::colorfgbg_in;
+
+    let parse = |value| colorfgbg_in(&env(&[("COLORFGBG", value)]));
+    assert_eq!(parse("15;0"), Some((15, 0)));
+    assert_eq!(parse("0;15"), Some((0, 15)));
+    assert_eq!(parse("15;default;0"), Some((15, 0)));
+    assert_eq!(parse("garbage"), None);
+    assert_eq!(parse("15;"), None);
+    assert_eq!(parse("15;0;0;0"), None);
+    assert_eq!(parse("300;0"), None);
+    assert_eq!(colorfgbg_in(&env(&[])), None);
+}
+
+#[test]
+#[cfg(feature = "cache")]
+fn tty_cache() {
+    use super::{invalidate_tty_cache, tty_cached_with, Stream};
+    use core::cell::Cell;
+
+    let calls = Cell::new(0);
+    let tty = |_| {
+        calls.set(calls.get() + 1);
+        true
+    };
+
+    invalidate_tty_cache();
+    assert!(tty_cached_with(Stream::Stderr, &tty));
+    assert!(tty_cached_with(Stream::Stderr, &|_| false));
+    assert_eq!(calls.get(), 1);
+
+    invalidate_tty_cache();
+    assert!(!tty_cached_with(Stream::Stderr, &|_| false));
+    assert!(!tty_cached_with(Stream::Stderr, &tty));
+    assert_eq!(calls.get(), 1);
+
+    // An answer from before an invalidation, as when the stream is
+    // redirected during the query, isn't cached.
+    invalidate_tty_cache();
+    let stale = |_| {
+        invalidate_tty_cache();
+        true
+    };
+    assert!(tty_cached_with(Stream::Stderr, &stale));
+    assert!(!tty_cached_with(Stream::Stderr, &|_| false));
+    assert!(!tty_cached_with(Stream::Stderr, &tty));
+    assert_eq!(calls.get(), 1);
+
+    // Threads overriding stdin, caching whether it's a terminal, and
+    // invalidating the cache at once each see their own override, and
+    // otherwise the real answer. This is part of the same test since the
+    // cache is process-wide.
+    #[cfg(feature = "testutil")]
+    {
+        use super::testutil::set_override;
+        use super::{is, is_cached};
+
+        invalidate_tty_cache();
+        let real = is(Stream::Stdin);
+        let threads: std::vec::Vec<_> = (0..8)
+            .map(|thread| {
+                std::thread::spawn(move || {
+                    let value = thread % 2 == 0;
+                    for i in 0..1000 {
+                        if i % 10 == 0 {
+                            invalidate_tty_cache();
+                        }
+                        set_override(Stream::Stdin, Some(value));
+                        assert_eq!(is(Stream::Stdin), value);
+                        assert_eq!(is_cached(Stream::Stdin), value);
+                        set_override(Stream::Stdin, None);
+                        assert_eq!(is(Stream::Stdin), real);
+                        assert_eq!(is_cached(Stream::Stdin), real);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+    invalidate_tty_cache();
+}
+
+#[test]
+#[cfg(feature = "cache")]
+fn size_cache() {
+    use super::{invalidate_size_cache, size_cached_with, Stream};
+    use core::cell::Cell;
+    use core::sync::atomic::Ordering;
+
+    let calls = Cell::new(0);
+    let size = |_| {
+        calls.set(calls.get() + 1);
+        Some((80, 24))
+    };
+
+    invalidate_size_cache();
+    assert_eq!(size_cached_with(Stream::Stderr, &size), Some((80, 24)));
+    assert_eq!(size_cached_with(Stream::Stderr, &size), Some((80, 24)));
+    assert_eq!(calls.get(), 1);
+
+    invalidate_size_cache();
+    assert_eq!(size_cached_with(Stream::Stderr, &size), Some((80, 24)));
+    assert_eq!(calls.get(), 2);
+
+    // An unknown size is remembered too.
+    invalidate_size_cache();
+    assert_eq!(size_cached_with(Stream::Stderr, &|_| None), None);
+    assert_eq!(size_cached_with(Stream::Stderr, &size), None);
+    assert_eq!(calls.get(), 2);
+
+    // A size queried before an invalidation, as by a `SIGWINCH` handler
+    // which runs during the query, isn't cached.
+    invalidate_size_cache();
+    let stale = |_| {
+        invalidate_size_cache();
+        Some((80, 24))
+    };
+    assert_eq!(size_cached_with(Stream::Stderr, &stale), Some((80, 24)));
+    assert_eq!(
+        size_cached_with(Stream::Stderr, &|_| Some((100, 30))),
+        Some((100, 30))
+    );
+    assert_eq!(size_cached_with(Stream::Stderr, &size), Some((100, 30)));
+    assert_eq!(calls.get(), 2);
+
+    // Many threads resizing and querying concurrently end up caching the
+    // final size. This is part of the same test since the cache is
+    // process-wide.
+    let width = std::sync::Arc::new(core::sync::atomic::AtomicU32::new(80));
+    let threads: std::vec::Vec<_> = (0..8)
+        .map(|_| {
+            let width = width.clone();
+            std::thread::spawn(move || {
+                let size = |_| Some((width.load(Ordering::SeqCst) as u16, 24));
+                for i in 0..1000 {
+                    if i % 10 == 0 {
+                        width.fetch_add(1, Ordering::SeqCst);
+                        invalidate_size_cache();
+                    }
+                    assert!(size_cached_with(Stream::Stderr, &size).is_some());
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let last = width.load(Ordering::SeqCst) as u16;
+    assert_eq!(last, 80 + 8 * 100);
+    assert_eq!(
+        size_cached_with(Stream::Stderr, &|_| Some((0, 1))),
+        Some((last, 24))
+    );
+    invalidate_size_cache();
+}
+
+// Models of the caches' state machines, checked under every interleaving
+// loom finds; see `Cargo.toml` for how to run them. Nothing else in this
+// file runs under loom, since the caches are then made of loom's atomics.
+#[test]
+#[cfg(all(feature = "cache", is_terminal_loom))]
+fn loom_size_cache() {
+    use super::{invalidate_size_cache, size_cached_with, Stream};
+    use loom::sync::atomic::{AtomicU16, Ordering};
+    use loom::sync::Arc;
+
+    // However a resize races with a query caching the size, the size
+    // cached afterwards is the new one.
+    loom::model(|| {
+        let width = Arc::new(AtomicU16::new(80));
+        let resizer = {
+            let width = width.clone();
+            loom::thread::spawn(move || {
+                width.store(100, Ordering::Relaxed);
+                invalidate_size_cache();
+            })
+        };
+        let size = |_| Some((width.load(Ordering::Relaxed), 24));
+        assert!(size_cached_with(Stream::Stderr, &size).is_some());
+        resizer.join().unwrap();
+        assert_eq!(size_cached_with(Stream::Stderr, &size), Some((100, 24)));
+    });
+
+    // Two threads querying at once both get an answer, and leave one
+    // cached.
+    loom::model(|| {
+        let other = loom::thread::spawn(|| size_cached_with(Stream::Stderr, &|_| Some((80, 24))));
+        assert_eq!(
+            size_cached_with(Stream::Stderr, &|_| Some((80, 24))),
+            Some((80, 24))
+        );
+        assert_eq!(other.join().unwrap(), Some((80, 24)));
+        assert_eq!(size_cached_with(Stream::Stderr, &|_| None), Some((80, 24)));
+    });
+}
+
+#[test]
+#[cfg(all(feature = "cache", is_terminal_loom))]
+fn loom_tty_cache() {
+    use super::{invalidate_tty_cache, tty_cached_with, Stream};
+    use loom::sync::atomic::{AtomicBool, Ordering};
+    use loom::sync::Arc;
+
+    // However a redirection races with a query caching the answer, the
+    // answer cached afterwards is the new one.
+    loom::model(|| {
+        let tty = Arc::new(AtomicBool::new(true));
+        let redirector = {
+            let tty = tty.clone();
+            loom::thread::spawn(move || {
+                tty.store(false, Ordering::Relaxed);
+                invalidate_tty_cache();
+            })
+        };
+        let query = |_| tty.load(Ordering::Relaxed);
+        tty_cached_with(Stream::Stderr, &query);
+        redirector.join().unwrap();
+        assert!(!tty_cached_with(Stream::Stderr, &query));
+    });
+}
+
+#[test]
+fn concurrent_detection() {
+    use super::{detect, is, is_pipe, Stream};
+
+    let expected = [Stream::Stdin, Stream::Stdout, Stream::Stderr]
+        .iter()
+        .map(|&stream| detect(stream))
+        .collect::<std::vec::Vec<_>>();
+    let threads: std::vec::Vec<_> = (0..8)
+        .map(|_| {
+            let expected = expected.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    for snapshot in &expected {
+                        assert_eq!(is(snapshot.stream()), snapshot.is_tty());
+                        assert_eq!(is_pipe(snapshot.stream()), snapshot.is_pipe());
+                    }
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn crlf_console_modes() {
+    use super::needs_crlf_with;
+    use windows_sys::Win32::System::Console::{
+        DISABLE_NEWLINE_AUTO_RETURN, ENABLE_PROCESSED_OUTPUT, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+        ENABLE_WRAP_AT_EOL_OUTPUT,
+    };
+
+    // A legacy console with output processing turned off.
+    assert!(needs_crlf_with(Some(ENABLE_WRAP_AT_EOL_OUTPUT)));
+    assert!(!needs_crlf_with(Some(
+        ENABLE_PROCESSED_OUTPUT | ENABLE_WRAP_AT_EOL_OUTPUT
+    )));
+    assert!(!needs_crlf_with(Some(
+        ENABLE_PROCESSED_OUTPUT | ENABLE_VIRTUAL_TERMINAL_PROCESSING
+    )));
+    assert!(needs_crlf_with(Some(
+        ENABLE_PROCESSED_OUTPUT | ENABLE_VIRTUAL_TERMINAL_PROCESSING | DISABLE_NEWLINE_AUTO_RETURN
+    )));
+    assert!(!needs_crlf_with(None));
+}
+
+#[test]
+#[cfg(unix)]
+fn crlf_on_unix() {
+    use super::{needs_crlf, Stream};
+
+    assert!(!needs_crlf(Stream::Stdout));
+}
+
+#[test]
+#[cfg(windows)]
+fn processed_output() {
+    use super::{console_mode, processed_output_enabled, Stream};
+    use windows_sys::Win32::System::Console::ENABLE_PROCESSED_OUTPUT;
+
+    // Whether these are consoles depends on how the tests are run; a
+    // redirected handle has no console mode and yields `None`.
+    for &stream in &[Stream::Stdout, Stream::Stderr] {
+        let expected =
+            console_mode(stream.as_raw_handle()).map(|mode| mode & ENABLE_PROCESSED_OUTPUT != 0);
+        assert_eq!(processed_output_enabled(stream), expected);
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn alloc_console() {
+    use super::{can_alloc_console, can_alloc_console_with};
+    use windows_sys::Win32::System::Console::GetConsoleCP;
+
+    assert!(can_alloc_console_with(false, Some(1)));
+    assert!(!can_alloc_console_with(true, Some(1)));
+    assert!(!can_alloc_console_with(false, Some(0)));
+    assert!(!can_alloc_console_with(false, None));
+
+    if unsafe { GetConsoleCP() } != 0 {
+        assert!(!can_alloc_console());
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn attach_console_fails_when_attached() {
+    use super::attach_parent_console;
+    use windows_sys::Win32::System::Console::GetConsoleCP;
+
+    // Only try this when it's bound to fail, since otherwise it would
+    // attach the test process to its parent's console.
+    if unsafe { GetConsoleCP() } != 0 {
+        assert!(!attach_parent_console());
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn processed_output_on_unix() {
+    use super::{processed_output_enabled, Stream};
+
+    assert_eq!(processed_output_enabled(Stream::Stdout), None);
+}
+
+#[test]
+#[cfg(unix)]
+fn downstream() {
+    use super::{downstream_connected, Stream};
+
+    assert!(in_child(|| {
+        let (reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        drop(writer);
+        let connected = downstream_connected(Stream::Stdout);
+        drop(reader);
+        connected == Some(true) && downstream_connected(Stream::Stdout) == Some(false)
+    }));
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        downstream_connected(Stream::Stdout).is_none()
+            && downstream_connected(Stream::Stdin).is_none()
+    }));
+}
+
+#[test]
+#[cfg(all(unix, feature = "raw"))]
+fn raw_mode_is_restored() {
+    use super::{enter_raw_mode, Stream};
+    use rustix::termios::{tcgetattr, ECHO, ICANON};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdin(&slave).unwrap();
+        let cooked = tcgetattr(&slave).unwrap().c_lflag;
+        let guard = enter_raw_mode(Stream::Stdin).unwrap();
+        let raw = tcgetattr(&slave).unwrap().c_lflag;
+        drop(guard);
+        let restored = tcgetattr(&slave).unwrap().c_lflag;
+        cooked & (ICANON | ECHO) != 0 && raw & (ICANON | ECHO) == 0 && restored == cooked
+    }));
+
+    assert!(in_child(|| {
+        let (reader, _writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdin(&reader).unwrap();
+        enter_raw_mode(Stream::Stdin).is_err()
+    }));
+}
+
+#[test]
+#[cfg(unix)]
+fn snapshot_restores_redirected_stdout() {
+    use super::{is, snapshot, Stream};
+
+    let path =
+        std::env::temp_dir().join(std::format!("is-terminal-snapshot-{}", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        assert!(is(Stream::Stdout));
+
+        let saved = snapshot(Stream::Stdout).unwrap();
+        assert_eq!(saved.stream(), Stream::Stdout);
+        rustix::io::dup2_stdout(&file).unwrap();
+        assert!(!is(Stream::Stdout));
+
+        saved.restore() && is(Stream::Stdout)
+    }));
+
+    #[cfg(feature = "cache")]
+    assert!(in_child(|| {
+        use super::{invalidate_tty_cache, is_cached};
+
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        let saved = snapshot(Stream::Stdout).unwrap();
+        rustix::io::dup2_stdout(&file).unwrap();
+        invalidate_tty_cache();
+        let redirected = !is_cached(Stream::Stdout);
+
+        saved.restore() && redirected && is_cached(Stream::Stdout)
+    }));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn unix_confidence() {
+    use super::{is_confident, Confidence, Stream};
+
+    assert!(in_child(|| {
+        let (_master, slave) = pty();
+        rustix::io::dup2_stdout(&slave).unwrap();
+        is_confident(Stream::Stdout) == Confidence::Certain(true)
+    }));
+
+    assert!(in_child(|| {
+        let (_reader, writer) = rustix::io::pipe().unwrap();
+        rustix::io::dup2_stdout(&writer).unwrap();
+        is_confident(Stream::Stdout) == Confidence::Certain(false)
+    }));
+}
+
+#[test]
+#[cfg(all(windows, not(feature = "win-minimal")))]
+fn windows_confidence() {
+    use super::{handle_console_confidence, Confidence};
+    use io_lifetimes::AsHandle;
+    use windows_sys::Win32::System::Console::{GetConsoleMode, GetStdHandle, STD_OUTPUT_HANDLE};
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+
+    let (mut reader, mut writer) = (0, 0);
+    assert_ne!(
+        unsafe { CreatePipe(&mut reader, &mut writer, core::ptr::null(), 0) },
+        0
+    );
+    let confidence =
+        handle_console_confidence(unsafe { io_lifetimes::BorrowedHandle::borrow_raw(writer as _) });
+    // An anonymous pipe isn't an MSYS pty, but that can only be known
+    // for certain when one of the standard handles is a console.
+    let mut mode = 0;
+    let console = [
+        windows_sys::Win32::System::Console::STD_INPUT_HANDLE,
+        STD_OUTPUT_HANDLE,
+        windows_sys::Win32::System::Console::STD_ERROR_HANDLE,
+    ]
+    .iter()
+    .any(|&std_handle| unsafe { GetConsoleMode(GetStdHandle(std_handle), &mut mode) != 0 });
+    if console {
+        assert_eq!(confidence, Confidence::Certain(false));
+    } else {
+        assert_eq!(confidence, Confidence::Uncertain(false));
+    }
+    unsafe {
+        windows_sys::Win32::Foundation::CloseHandle(reader);
+        windows_sys::Win32::Foundation::CloseHandle(writer);
+    }
+
+    // A console is certain.
+    if std::io::stdout().is_terminal() {
+        assert_eq!(
+            handle_console_confidence(std::io::stdout().as_handle()),
+            Confidence::Certain(true)
+        );
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn quick_edit_mode() {
+    use super::{quick_edit_enabled, set_quick_edit, Stream};
+
+    // Only input handles have a quick-edit mode.
+    assert_eq!(quick_edit_enabled(Stream::Stdout), None);
+
+    match quick_edit_enabled(Stream::Stdin) {
+        // Toggle it, when stdin is a console, and put it back.
+        Some(enabled) => {
+            assert!(set_quick_edit(!enabled));
+            assert_eq!(quick_edit_enabled(Stream::Stdin), Some(!enabled));
+            assert!(set_quick_edit(enabled));
+            assert_eq!(quick_edit_enabled(Stream::Stdin), Some(enabled));
+        }
+        None => assert!(!set_quick_edit(false)),
+    }
+}
+
+#[test]
+#[cfg(windows)]
+fn own_anonymous_pipe_names() {
+    use super::is_own_anonymous_pipe_name;
+
+    assert!(is_own_anonymous_pipe_name(
+        "\\Win32Pipes.000012a4.00000002",
+        0x12a4
+    ));
+    assert!(is_own_anonymous_pipe_name(
+        "\\Device\\NamedPipe\\Win32Pipes.000012a4.00000002",
+        0x12a4
+    ));
+    assert!(!is_own_anonymous_pipe_name(
+        "\\Win32Pipes.000012a4.00000002",
+        0x12a5
+    ));
+    assert!(!is_own_anonymous_pipe_name(
+        "\\msys-1888ae32e00d56aa-pty0-to-master",
+        0x12a4
+    ));
+    assert!(!is_own_anonymous_pipe_name("\\Win32Pipes.12a4", 0x12a4));
+    assert!(!is_own_anonymous_pipe_name("", 0));
+}
+
+#[test]
+#[cfg(windows)]
+fn pseudoconsole_registration() {
+    use super::{owns_pseudoconsole, register_pseudoconsole, unregister_pseudoconsole};
+
+    let before = owns_pseudoconsole();
+    register_pseudoconsole(1);
+    assert!(owns_pseudoconsole());
+    unregister_pseudoconsole(1);
+    assert_eq!(owns_pseudoconsole(), before);
+}
+
+// Verify that the msys_tty_on function works with long path.
+#[test]
+#[cfg(windows)]
+fn msys_tty_on_path_length() {
+    use std::{fs::File, os::windows::io::AsRawHandle};
+    use windows_sys::Win32::Foundation::MAX_PATH;
+
+    let dir = tempfile::tempdir().expect("Unable to create temporary directory");
+    let file_path = dir.path().join("ten_chars_".repeat(25));
+    // Ensure that the path is longer than MAX_PATH.
+    assert!(file_path.to_string_lossy().len() > MAX_PATH as usize);
+    let file = File::create(file_path).expect("Unable to create file");
+
+    assert!(!unsafe { crate::msys_tty_on(file.as_raw_handle() as isize) });
+}
+
+#[test]
+fn public_paths() {
+    use super::Stream;
+
+    // Everything is defined in private modules and re-exported from the
+    // crate root, so these paths are the ones users see.
+    let _: fn(Stream) -> bool = super::is;
+    let _: fn(Stream) -> bool = super::isnt;
+    let _: fn(&std::fs::File) -> bool = super::is_terminal::<std::fs::File>;
+    let _: fn(Stream) -> Option<(u16, u16)> = super::size;
+    let _: fn(Stream) -> bool = super::is_pipe;
+    let _: fn(Stream) -> super::TerminalSnapshot = super::detect;
+    #[cfg(unix)]
+    let _: fn(i32) -> Option<Stream> = super::stream_of_fd;
+    #[cfg(windows)]
+    let _: fn(std::os::windows::io::RawHandle) -> Option<Stream> = super::stream_of_handle;
+    #[cfg(feature = "std")]
+    let _: fn() -> bool = super::is_ci;
+    #[cfg(feature = "std")]
+    let _: fn(Stream) -> Result<(u16, u16), super::TerminalError> = super::try_size;
+    #[cfg(feature = "term")]
+    let _: fn(Stream) -> super::Capabilities = super::capabilities;
+    #[cfg(feature = "color")]
+    let _: fn(Stream) -> super::ColorLevel = super::color_level;
+    #[cfg(feature = "query")]
+    let _: fn(Stream) -> Option<(u16, u16)> = super::cursor_position;
+    #[cfg(feature = "raw")]
+    let _: fn(Stream) -> std::io::Result<super::RawModeGuard> = super::enter_raw_mode;
+}
@@ -0,0 +1,127 @@
+//! Fixtures for testing code which uses this crate.
+//!
+//! The overrides installed by [`with_fake_tty`], [`set_override`], and
+//! [`with_env`] are per-thread, so tests running in parallel don't see each
+//! other's. Those of `with_fake_tty` and `with_env` last only until the
+//! closure they're given returns. They're meant for tests, and the
+//! `testutil` feature shouldn't be enabled outside of `dev-dependencies`.
+
+use crate::Stream;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+std::thread_local! {
+    static FAKE_TTYS: RefCell<Vec<Stream>> = RefCell::new(Vec::new());
+    static OVERRIDES: RefCell<Vec<(Stream, bool)>> = RefCell::new(Vec::new());
+    static FAKE_ENV: RefCell<Option<HashMap<String, String>>> = RefCell::new(None);
+}
+
+/// Calls `f` with `stream` reported as a terminal by [`is`](crate::is)
+/// and [`Stream`]'s [`IsTerminal`](crate::IsTerminal) impl, and by
+/// everything in this crate which checks a `Stream` through them, and
+/// returns what it returns.
+///
+/// Only the calling thread sees the override. Functions which inspect
+/// the stream's file descriptor or handle directly, such as `size`,
+/// still see the real stream.
+pub fn with_fake_tty<R>(stream: Stream, f: impl FnOnce() -> R) -> R {
+    struct Restore;
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            FAKE_TTYS.with(|ttys| ttys.borrow_mut().pop());
+        }
+    }
+
+    FAKE_TTYS.with(|ttys| ttys.borrow_mut().push(stream));
+    let _restore = Restore;
+    f()
+}
+
+/// Makes [`is`](crate::is), and everything in this crate which checks a
+/// `Stream` through it, report whether `stream` is a terminal as `value`
+/// says, or stops overriding it if `value` is `None`.
+///
+/// Only the calling thread sees the override. It lasts until it's replaced
+/// or cleared, and takes precedence over `ATTY_FORCE` and the real stream,
+/// but not over [`with_fake_tty`].
+pub fn set_override(stream: Stream, value: Option<bool>) {
+    OVERRIDES.with(|overrides| {
+        let mut overrides = overrides.borrow_mut();
+        overrides.retain(|&(overridden, _)| overridden != stream);
+        if let Some(value) = value {
+            overrides.push((stream, value));
+        }
+    })
+}
+
+/// Calls `f` with the process environment, as this crate's functions
+/// read it through [`SystemEnv`](crate::SystemEnv), replaced by `vars`,
+/// and returns what it returns.
+///
+/// Variables not in `vars` appear unset. Only the calling thread sees
+/// the replacement, and `std::env` itself is left alone.
+pub fn with_env<R>(vars: &[(&str, &str)], f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<HashMap<String, String>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            FAKE_ENV.with(|env| *env.borrow_mut() = previous);
+        }
+    }
+
+    let vars = vars
+        .iter()
+        .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+        .collect();
+    let _restore = Restore(FAKE_ENV.with(|env| env.borrow_mut().replace(vars)));
+    f()
+}
+
+/// Opens a new pseudoterminal with `posix_openpt`, returning its master
+/// and slave ends.
+///
+/// The slave end is opened with `O_NOCTTY`, so it doesn't become the
+/// controlling terminal.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "freebsd"
+))]
+pub fn make_pty() -> std::io::Result<(rustix::fd::OwnedFd, rustix::fd::OwnedFd)> {
+    use rustix::fs::{cwd, openat, Mode, OFlags};
+    use rustix::pty::{grantpt, openpt, ptsname, unlockpt, OpenptFlags};
+
+    let master = openpt(OpenptFlags::RDWR | OpenptFlags::NOCTTY)?;
+    grantpt(&master)?;
+    unlockpt(&master)?;
+    let name = ptsname(&master, Vec::new())?;
+    let slave = openat(
+        cwd(),
+        name.as_c_str(),
+        OFlags::RDWR | OFlags::NOCTTY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    Ok((master, slave))
+}
+
+/// Returns whether `stream` is being faked as a terminal, or `None` if it
+/// isn't being faked.
+pub(crate) fn fake_tty(stream: Stream) -> Option<bool> {
+    if FAKE_TTYS.with(|ttys| ttys.borrow().contains(&stream)) {
+        return Some(true);
+    }
+    OVERRIDES.with(|overrides| {
+        overrides
+            .borrow()
+            .iter()
+            .find(|&&(overridden, _)| overridden == stream)
+            .map(|&(_, value)| value)
+    })
+}
+
+/// Returns the faked value of `key`, or `None` if the environment isn't
+/// being faked.
+pub(crate) fn fake_env(key: &str) -> Option<Option<String>> {
+    FAKE_ENV.with(|env| env.borrow().as_ref().map(|env| env.get(key).cloned()))
+}
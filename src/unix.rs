@@ -0,0 +1,875 @@
+//! APIs specific to Unix-family platforms.
+
+use crate::*;
+
+/// Waits until `stream` is readable, using tokio's reactor, so that async
+/// programs can wait for interactive input without tying up a thread.
+///
+/// The stream's file descriptor is registered with the reactor for the
+/// duration of the call; it isn't taken ownership of or closed. Registration
+/// fails if the descriptor is a regular file, or is already registered with
+/// the reactor elsewhere, such as by `tokio::io::stdin()`.
+///
+/// This is only available on Unix-family platforms. On Windows, console
+/// handles can't be registered with tokio's reactor, so a blocking thread is
+/// needed instead.
+#[cfg(feature = "tokio")]
+pub async fn readable(stream: Stream) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    readable_fd(stream.as_fd().as_raw_fd()).await
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) async fn readable_fd(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    use tokio::io::unix::AsyncFd;
+    use tokio::io::Interest;
+
+    // `AsyncFd` deregisters a bare `RawFd` on drop, but doesn't close it.
+    let fd = AsyncFd::with_interest(fd, Interest::READABLE)?;
+    let _guard = fd.readable().await?;
+    Ok(())
+}
+
+/// Waits until `stream` is readable, using async-std's reactor, in the same
+/// way as [`readable`] does for tokio.
+///
+/// The stream's file descriptor is registered with the reactor for the
+/// duration of the call, without changing it to non-blocking mode; it isn't
+/// taken ownership of or closed. Registration fails if the descriptor is a
+/// regular file.
+///
+/// This is only available on Unix-family platforms. On Windows, async-std's
+/// reactor only accepts sockets, so console handles and pipes can't be
+/// awaited this way.
+#[cfg(feature = "async-std")]
+pub async fn readable_async_std(stream: Stream) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    readable_async_std_fd(stream.as_fd().as_raw_fd()).await
+}
+
+#[cfg(feature = "async-std")]
+pub(crate) async fn readable_async_std_fd(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    use async_io::Async;
+    use std::os::unix::io::BorrowedFd;
+
+    // SAFETY: `fd` outlives the registration, which is dropped, and thus
+    // deregistered, before we return. Dropping a `BorrowedFd` doesn't close
+    // it.
+    let fd = Async::new_nonblocking(unsafe { BorrowedFd::borrow_raw(fd) })?;
+    fd.readable().await
+}
+
+/// Calls `f` until it returns anything but `EINTR`, as system calls do when
+/// a signal handler runs while they're blocked, such as for `SIGWINCH` when
+/// the terminal is resized or `SIGCHLD` when a child exits.
+pub(crate) fn retry_on_eintr<T>(
+    mut f: impl FnMut() -> rustix::io::Result<T>,
+) -> rustix::io::Result<T> {
+    loop {
+        match f() {
+            Err(rustix::io::Errno::INTR) => {}
+            result => return result,
+        }
+    }
+}
+
+/// A saved duplicate of one of the standard streams, made by [`snapshot`].
+///
+/// Dropping an `FdSnapshot` without calling [`FdSnapshot::restore`] closes
+/// the duplicate and leaves the stream as it is.
+#[derive(Debug)]
+pub struct FdSnapshot {
+    stream: Stream,
+    saved: OwnedFd,
+}
+
+impl FdSnapshot {
+    /// Returns the stream this snapshot was taken of.
+    #[inline]
+    pub fn stream(&self) -> Stream {
+        self.stream
+    }
+
+    /// Points the stream back at what it referred to when the snapshot was
+    /// taken, returning `true` on success.
+    ///
+    /// Afterward, [`is`] and the other queries report on the restored stream
+    /// again, and with the `cache` feature, the cached answers are
+    /// invalidated so that they do too.
+    pub fn restore(self) -> bool {
+        let restored = match self.stream {
+            Stream::Stdin => rustix::io::dup2_stdin(&self.saved),
+            Stream::Stdout => rustix::io::dup2_stdout(&self.saved),
+            Stream::Stderr => rustix::io::dup2_stderr(&self.saved),
+        }
+        .is_ok();
+
+        #[cfg(feature = "cache")]
+        if restored {
+            invalidate_tty_cache();
+            invalidate_size_cache();
+        }
+
+        restored
+    }
+}
+
+/// Saves a duplicate of what `stream` currently refers to, so that it can be
+/// put back with [`FdSnapshot::restore`] after temporarily redirecting the
+/// stream.
+///
+/// Returns `None` if `stream` is closed or can't be duplicated. This also
+/// records what the stream refers to, so that [`verify_consistency`] can
+/// tell whether it's been redirected since.
+///
+/// # Example
+///
+/// ```no_run
+/// use is_terminal::Stream;
+///
+/// let snapshot = is_terminal::snapshot(Stream::Stdout).unwrap();
+/// // ... redirect stdout, run a subprocess, and so on ...
+/// snapshot.restore();
+/// ```
+pub fn snapshot(stream: Stream) -> Option<FdSnapshot> {
+    // Keep the duplicate out of the standard descriptor range, even if one of
+    // them is currently closed, and out of any child processes.
+    let saved = rustix::io::fcntl_dupfd_cloexec(stream.as_fd(), 3).ok()?;
+    record_identity(stream);
+    Some(FdSnapshot { stream, saved })
+}
+
+/// Returns `true` if standard output is a pipe being read by a pager, such
+/// as `less`, `more`, `most`, `bat`, or the program named by `PAGER`.
+///
+/// Tools often keep color enabled when piped specifically to a pager. This
+/// is a best-effort heuristic: it finds the processes holding the read end of
+/// the pipe by scanning `/proc`, so it only works on Linux, and only sees
+/// processes the current user may inspect. It returns `false` whenever the
+/// reader can't be determined.
+#[cfg(feature = "std")]
+pub fn piped_to_pager() -> bool {
+    let pager = env_var("PAGER");
+    pipe_readers(Stream::Stdout.as_fd())
+        .map(|names| names.iter().any(|name| is_pager(name, pager.as_deref())))
+        .unwrap_or(false)
+}
+
+/// Returns `true` if `name`, a process's command name, is a known pager or
+/// the program named by `pager`, the value of `PAGER`.
+#[cfg(feature = "std")]
+pub(crate) fn is_pager(name: &str, pager: Option<&str>) -> bool {
+    let named = pager
+        .and_then(|pager| pager.split_whitespace().next())
+        .and_then(|program| program.rsplit('/').next())
+        == Some(name);
+    named || matches!(name, "less" | "more" | "most" | "bat")
+}
+
+/// Returns the command names of the other processes which hold the read end
+/// of the pipe `fd`, or `None` if `fd` isn't a pipe or `/proc` can't be read.
+#[cfg(feature = "std")]
+pub(crate) fn pipe_readers(fd: BorrowedFd<'_>) -> Option<Vec<String>> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::fs::{read_link, read_to_string};
+        use std::os::unix::io::AsRawFd;
+
+        let target = read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()?;
+        if !target.to_str()?.starts_with("pipe:") {
+            return None;
+        }
+
+        fd_holders(&|link, info| {
+            link == target && read_to_string(info).map_or(false, |info| fdinfo_is_read_only(&info))
+        })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = fd;
+        None
+    }
+}
+
+/// Returns the command names of the other processes which hold the master
+/// side of the pty `fd` is the slave side of, or `None` if `fd` isn't a pty
+/// slave or `/proc` can't be read.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn pty_masters(fd: BorrowedFd<'_>) -> Option<Vec<String>> {
+    use std::fs::{read_link, read_to_string};
+    use std::os::unix::io::AsRawFd;
+
+    let target = read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).ok()?;
+    let index = target.to_str()?.strip_prefix("/dev/pts/")?.to_owned();
+    fd_holders(&|link, info| {
+        link.file_name().map_or(false, |name| name == "ptmx")
+            && read_to_string(info).map_or(false, |info| {
+                info.lines()
+                    .find_map(|line| line.strip_prefix("tty-index:"))
+                    .map_or(false, |tty| tty.trim() == index)
+            })
+    })
+}
+
+/// Returns the command names of the other processes with a file descriptor
+/// for which `matches`, given the descriptor's `/proc/<pid>/fd` link target
+/// and `/proc/<pid>/fdinfo` path, returns `true`.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn fd_holders(matches: &dyn Fn(&std::path::Path, &std::path::Path) -> bool) -> Option<Vec<String>> {
+    use std::fs::{read_dir, read_link, read_to_string};
+
+    let me = std::process::id().to_string();
+    let mut holders = Vec::new();
+    for process in read_dir("/proc").ok()?.flatten() {
+        let pid = process.file_name();
+        let pid = match pid.to_str() {
+            Some(pid) if pid != me && pid.bytes().all(|b| b.is_ascii_digit()) => pid,
+            _ => continue,
+        };
+
+        // Other users' processes can't be inspected; skip them.
+        let fds = match read_dir(process.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let holds = fds.flatten().any(|entry| {
+            read_link(entry.path()).map_or(false, |link| {
+                matches(
+                    &link,
+                    &process.path().join("fdinfo").join(entry.file_name()),
+                )
+            })
+        });
+        if holds {
+            if let Ok(comm) = read_to_string(format!("/proc/{}/comm", pid)) {
+                holders.push(comm.trim_end().to_owned());
+            }
+        }
+    }
+    Some(holders)
+}
+
+/// Returns `true` if output to `stream` is likely being recorded, because
+/// it's a pipe read by `tee` or a pty driven by `script`.
+///
+/// Programs may want to tone down animations so that recordings stay
+/// readable. Like [`piped_to_pager`], this finds the other end of the pipe or
+/// pty by scanning `/proc`, so it only works on Linux, and only sees
+/// processes the current user may inspect. It returns `false` whenever the
+/// other end can't be determined.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn output_is_recorded(stream: Stream) -> bool {
+    let fd = stream.as_fd();
+    let tee = pipe_readers(fd).map_or(false, |names| names.iter().any(|name| name == "tee"));
+    tee || pty_masters(fd).map_or(false, |names| names.iter().any(|name| name == "script"))
+}
+
+/// Returns `true` if `info`, the contents of a `/proc/<pid>/fdinfo/<fd>`
+/// file, describes a descriptor opened for reading only.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn fdinfo_is_read_only(info: &str) -> bool {
+    info.lines()
+        .find_map(|line| line.strip_prefix("flags:"))
+        .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+        .map_or(false, |flags| flags & 0o3 == 0)
+}
+
+/// Returns `true` if standard output or standard error is connected to the
+/// systemd journal, as it is for services whose output systemd collects.
+///
+/// systemd sets `JOURNAL_STREAM` to the device and inode numbers of the
+/// journal stream it connects a service's output to, as `device:inode`, so
+/// this compares that against `fstat` of each stream. That isn't a
+/// heuristic, and a value inherited from a parent whose output went to the
+/// journal won't match once the output is redirected elsewhere. Services
+/// can use this to write plain lines with level prefixes, such as `<3>` for
+/// errors, rather than escape sequences. This is only available on Linux.
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub fn piped_to_journald() -> bool {
+    piped_to_journald_with(&SystemEnv)
+}
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub(crate) fn piped_to_journald_with(env: &dyn EnvSource) -> bool {
+    let value = match env.get("JOURNAL_STREAM") {
+        Some(value) => value,
+        None => return false,
+    };
+    let mut fields = value.split(':');
+    let (device, inode) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(device), Some(inode), None) => (device, inode),
+        _ => return false,
+    };
+    [Stream::Stdout, Stream::Stderr].iter().any(|stream| {
+        rustix::fs::fstat(stream.as_fd()).map_or(false, |stat| {
+            device.parse().ok() == Some(stat.st_dev) && inode.parse().ok() == Some(stat.st_ino)
+        })
+    })
+}
+
+/// Returns `true` if `stream` is one of the sockets systemd passed to the
+/// process through socket activation.
+///
+/// systemd passes activated sockets as consecutive descriptors starting at
+/// `SD_LISTEN_FDS_START`, which is 3, setting `LISTEN_FDS` to their number
+/// and `LISTEN_PID` to the process they're meant for. This requires
+/// `LISTEN_PID` to be the current process, as `sd_listen_fds` does, and
+/// then compares the device and inode numbers of `stream` with those of the
+/// passed descriptors, since services configured with `StandardInput=socket`
+/// or `StandardOutput=socket` get one of them on a standard stream. Like
+/// [`piped_to_journald`], this is only available on Linux.
+#[cfg(all(target_os = "linux", feature = "std"))]
+pub fn is_systemd_socket(stream: Stream) -> bool {
+    is_systemd_socket_with(stream, &SystemEnv, std::process::id())
+}
+
+#[cfg(all(target_os = "linux", feature = "std"))]
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn is_systemd_socket_with(stream: Stream, env: &dyn EnvSource, pid: u32) -> bool {
+    use rustix::fs::{fstat, FileType};
+
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    if env.get("LISTEN_PID").and_then(|listen| listen.parse().ok()) != Some(pid) {
+        return false;
+    }
+    let count = match env
+        .get("LISTEN_FDS")
+        .and_then(|count| count.parse::<i32>().ok())
+    {
+        Some(count) if count > 0 => count,
+        _ => return false,
+    };
+    let stat = match fstat(stream.as_fd()) {
+        Ok(stat) if FileType::from_raw_mode(stat.st_mode) == FileType::Socket => stat,
+        _ => return false,
+    };
+
+    // The passed descriptors were never given to this crate, so rather than
+    // borrow them, `fstat` their numbers, as `sd_listen_fds` does. Stop at
+    // the first closed one, in case `LISTEN_FDS` is bogus.
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START.saturating_add(count))
+        .map(|fd| {
+            let mut listen = core::mem::MaybeUninit::<libc::stat>::uninit();
+            if unsafe { libc::fstat(fd, listen.as_mut_ptr()) } == 0 {
+                Some(unsafe { listen.assume_init() })
+            } else {
+                None
+            }
+        })
+        .take_while(Option::is_some)
+        .flatten()
+        .any(|listen| {
+            listen.st_dev as u64 == stat.st_dev as u64 && listen.st_ino as u64 == stat.st_ino as u64
+        })
+}
+
+/// Returns whether `O_NONBLOCK` is set on `stream`'s file descriptor, or
+/// `None` if its flags can't be read.
+///
+/// TUIs which put the terminal in non-blocking mode can use this to detect
+/// that, and [`set_blocking`] to undo it.
+pub fn is_nonblocking(stream: Stream) -> Option<bool> {
+    rustix::fs::fcntl_getfl(stream.as_fd())
+        .ok()
+        .map(|flags| flags.contains(rustix::fs::OFlags::NONBLOCK))
+}
+
+/// Sets or clears `O_NONBLOCK` on `stream`'s file descriptor, leaving its
+/// other flags alone, and returns whether it succeeded.
+///
+/// Programs which crash while a terminal is in non-blocking mode can leave
+/// it that way, breaking the shell and other programs which share it, so
+/// this is useful for defensively resetting it at startup. It works on any
+/// kind of file descriptor, not just terminals.
+pub fn set_blocking(stream: Stream, blocking: bool) -> bool {
+    use rustix::fs::{fcntl_getfl, fcntl_setfl, OFlags};
+
+    let fd = stream.as_fd();
+    let flags = match fcntl_getfl(fd) {
+        Ok(flags) => flags,
+        Err(_) => return false,
+    };
+    let mut new_flags = flags;
+    new_flags.set(OFlags::NONBLOCK, !blocking);
+    new_flags == flags || fcntl_setfl(fd, new_flags).is_ok()
+}
+
+/// Returns whether writes to `stream` reach their destination immediately,
+/// as far as the operating system is concerned, or `None` if that can't be
+/// determined.
+///
+/// Terminals and other character devices are unbuffered, as are pipes and
+/// sockets, where written data is available to the reader right away. Writes
+/// to regular files and block devices sit in the page cache unless the file
+/// was opened with `O_SYNC`, `O_DSYNC`, or, where available, `O_DIRECT`, so
+/// they're reported as buffered otherwise. This is only about the kernel;
+/// buffering in the program itself, such as by `std::io::Stdout`'s line
+/// buffering or C's stdio, is not considered. This is only available on
+/// Unix-family platforms.
+pub fn is_unbuffered(stream: Stream) -> Option<bool> {
+    use rustix::fs::{fcntl_getfl, fstat, FileType, OFlags};
+
+    let fd = stream.as_fd();
+    let stat = fstat(fd).ok()?;
+    let flags = fcntl_getfl(fd).ok()?;
+    Some(match FileType::from_raw_mode(stat.st_mode) {
+        FileType::RegularFile | FileType::BlockDevice => {
+            #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+            let synchronous = OFlags::SYNC | OFlags::DSYNC;
+            #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+            let synchronous = OFlags::SYNC | OFlags::DSYNC | OFlags::DIRECT;
+            flags.intersects(synchronous)
+        }
+        _ => true,
+    })
+}
+
+/// Returns the output baud rate of the terminal `stream` refers to, such as
+/// `9600` for a slow serial line, or `None` if it isn't a terminal or its
+/// speed isn't one of the standard rates.
+///
+/// Programs can use this to throttle redraws on slow links. Ptys typically
+/// report `38400`, which doesn't reflect their actual throughput.
+pub fn baud_rate(stream: Stream) -> Option<u32> {
+    let termios = rustix::termios::tcgetattr(stream.as_fd()).ok()?;
+    rustix::termios::speed_value(rustix::termios::cfgetospeed(&termios))
+}
+
+/// Returns whether the terminal `stream` refers to echoes what's typed, that
+/// is, whether `ECHO` is set in its local modes, or `None` if it isn't a
+/// terminal.
+pub fn echo_enabled(stream: Stream) -> Option<bool> {
+    let termios = rustix::termios::tcgetattr(stream.as_fd()).ok()?;
+    Some(termios.c_lflag & rustix::termios::ECHO != 0)
+}
+
+/// Returns `true` if what's read from standard input will be echoed back to
+/// the user, that is, if standard input is a terminal with
+/// [`echo_enabled`].
+///
+/// Programs reading passwords use this to decide whether they need to turn
+/// echo off first. Piped or redirected input isn't echoed, so this is
+/// `false` for it.
+pub fn input_will_echo() -> bool {
+    is(Stream::Stdin) && echo_enabled(Stream::Stdin) == Some(true)
+}
+
+/// Returns the number of the active virtual terminal, when standard input or
+/// output is one of the Linux kernel's text consoles (`/dev/ttyN`).
+///
+/// Installers and other tools which run on the bare console use this. It
+/// returns `None` when neither stream is a virtual terminal, such as in a
+/// terminal emulator under X11 or Wayland, or over SSH, where the streams are
+/// ptys. This is only available on Linux.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn active_vt() -> Option<u32> {
+    // From <linux/vt.h>.
+    const VT_GETSTATE: u32 = 0x5603;
+    #[repr(C)]
+    struct VtStat {
+        v_active: u16,
+        v_signal: u16,
+        v_state: u16,
+    }
+
+    use std::os::unix::io::AsRawFd;
+
+    [Stream::Stdin, Stream::Stdout]
+        .iter()
+        .map(|stream| stream.as_fd())
+        .find(|fd| rustix::fs::fstat(fd).map_or(false, |stat| is_vt_device(stat.st_rdev)))
+        .and_then(|fd| {
+            let mut state = VtStat {
+                v_active: 0,
+                v_signal: 0,
+                v_state: 0,
+            };
+            match unsafe { libc::ioctl(fd.as_raw_fd(), VT_GETSTATE as _, &mut state) } {
+                0 => Some(u32::from(state.v_active)),
+                _ => None,
+            }
+        })
+}
+
+/// Returns `true` if `rdev` is the device number of a Linux virtual
+/// terminal, `/dev/tty1` through `/dev/tty63`.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub(crate) fn is_vt_device(rdev: rustix::fs::Dev) -> bool {
+    // TTY_MAJOR, from <linux/major.h>; minor 0 is `/dev/tty0`, which refers
+    // to whichever terminal is current, and 64 and up are serial ports.
+    rustix::fs::major(rdev) == 4 && (1..64).contains(&rustix::fs::minor(rdev))
+}
+
+/// Returns the path of the terminal device `stream` refers to, such as
+/// `/dev/pts/3`, or `None` if it isn't a terminal.
+#[cfg(all(not(target_os = "fuchsia"), feature = "std"))]
+pub fn tty_name(stream: Stream) -> Option<std::path::PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let name = rustix::termios::ttyname(stream.as_fd(), Vec::new()).ok()?;
+    Some(std::ffi::OsString::from_vec(name.into_bytes()).into())
+}
+
+/// Returns whether `stream` is a serial line, such as `/dev/ttyS0`,
+/// `/dev/ttyAMA0`, or `/dev/ttyUSB0`, rather than a pty or the video console,
+/// or `None` if it isn't a terminal.
+///
+/// Serial links can be slow, so programs may want to avoid heavy redraws on
+/// them. This is only available on Linux.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn is_serial_console(stream: Stream) -> Option<bool> {
+    tty_name(stream).map(|name| is_serial_device_name(&name))
+}
+
+/// Returns `true` if `name` is the path of a Linux serial device.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub(crate) fn is_serial_device_name(name: &std::path::Path) -> bool {
+    ["ttyS", "ttyAMA", "ttyUSB"]
+        .iter()
+        .any(|prefix| is_numbered_device(name, prefix))
+}
+
+/// Returns `true` if `name` is `/dev/` followed by `prefix` and a number.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+fn is_numbered_device(name: &std::path::Path, prefix: &str) -> bool {
+    name.strip_prefix("/dev")
+        .ok()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(prefix))
+        .map_or(false, |number| {
+            !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+/// Returns `true` if `stream` is the Linux kernel's text console, a virtual
+/// terminal such as `/dev/tty1` with `TERM=linux`, rather than a graphical
+/// terminal emulator.
+///
+/// The console only has a 16-color palette, so programs may want to limit
+/// their colors on it regardless of what `color_level` reports. This is
+/// only available on Linux.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn is_linux_console(stream: Stream) -> bool {
+    is_linux_console_with(tty_name(stream).as_deref(), env_var("TERM").as_deref())
+}
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub(crate) fn is_linux_console_with(name: Option<&std::path::Path>, term: Option<&str>) -> bool {
+    term == Some("linux") && name.map_or(false, |name| is_numbered_device(name, "tty"))
+}
+
+/// Returns what `/proc/self/fd` says `stream`'s file descriptor refers to,
+/// such as `/dev/pts/3`, `pipe:[12345]`, or `/var/log/app.log (deleted)`.
+///
+/// This is meant for diagnosing unusual setups, such as containers and
+/// sandboxes, where [`is`] gives a surprising answer. It returns `None` if
+/// the stream is closed or `/proc` isn't mounted. This is only available on
+/// Linux.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn proc_fd_target(stream: Stream) -> Option<String> {
+    use std::os::unix::io::AsRawFd;
+
+    read_fd_link(std::path::Path::new(&format!(
+        "/proc/self/fd/{}",
+        stream.as_fd().as_raw_fd()
+    )))
+}
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub(crate) fn read_fd_link(link: &std::path::Path) -> Option<String> {
+    let target = std::fs::read_link(link).ok()?;
+    Some(target.to_string_lossy().into_owned())
+}
+
+/// Returns `true` if the process has a controlling terminal, that is, if
+/// `/dev/tty` can be opened.
+///
+/// A process can have a controlling terminal even if none of its standard
+/// streams are terminals, such as in `cmd < input > output`, and programs
+/// started by daemons, cron jobs, and graphical launchers typically have
+/// none.
+pub fn has_controlling_tty() -> bool {
+    use rustix::fs::{cwd, openat, Mode, OFlags};
+
+    openat(
+        cwd(),
+        "/dev/tty",
+        OFlags::RDWR | OFlags::NOCTTY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )
+    .is_ok()
+}
+
+/// Returns whether standard input is the controlling terminal, so that
+/// reading it reads what the user types, or `None` if standard input isn't
+/// a terminal or the process has no controlling terminal.
+///
+/// It's `Some(false)` when standard input is some other terminal, such as a
+/// pty a program has redirected it from. This is decided with `tcgetsid`,
+/// which only succeeds on the controlling terminal; device numbers can't be
+/// compared, since `/dev/tty` is a device of its own which the kernel
+/// redirects to the controlling terminal.
+pub fn stdin_is_controlling_tty() -> Option<bool> {
+    if !is(Stream::Stdin) || !has_controlling_tty() {
+        return None;
+    }
+    Some(rustix::termios::tcgetsid(Stream::Stdin.as_fd()).is_ok())
+}
+
+/// Returns whether suspending with Ctrl-Z is likely to work for programs on
+/// the terminal `stream` refers to, or `None` if it isn't a terminal.
+///
+/// This is a heuristic based on the relationship between the process, its
+/// session, and the terminal: the terminal must be the controlling terminal
+/// of the process's session (`tcgetsid` matches `getsid`), it must have a
+/// foreground process group (`tcgetpgrp` succeeds), and the process must
+/// not be in its session leader's process group. Job-control shells put each
+/// job in a process group of its own, so the last condition fails for
+/// processes started by programs such as `sshd` or `setsid` without a shell
+/// in between. Whether the shell actually resumes stopped jobs can't be
+/// determined.
+pub fn has_job_control(stream: Stream) -> Option<bool> {
+    use rustix::process::{getpgrp, getsid};
+    use rustix::termios::{tcgetpgrp, tcgetsid};
+
+    if !is(stream) {
+        return None;
+    }
+    let fd = stream.as_fd();
+    let session = match getsid(None) {
+        Ok(session) => session,
+        Err(_) => return Some(false),
+    };
+    Some(match (tcgetsid(fd), tcgetpgrp(fd)) {
+        (Ok(tty_session), Ok(_)) => tty_session == session && getpgrp() != session,
+        _ => false,
+    })
+}
+
+/// Returns `true` unless `stream` is the controlling terminal and the
+/// process isn't in its foreground process group, so that reading from it
+/// would stop the process with `SIGTTIN`.
+#[cfg(any(feature = "query", feature = "term"))]
+pub(crate) fn in_foreground(stream: Stream) -> bool {
+    rustix::termios::tcgetpgrp(stream.as_fd())
+        .map_or(true, |pgrp| pgrp == rustix::process::getpgrp())
+}
+
+/// Returns the ID of the session the terminal `stream` refers to belongs to,
+/// which is the process ID of its session leader, typically a login shell.
+///
+/// This is `tcgetsid`, which only succeeds on the controlling terminal of
+/// the process's own session, so this returns `None` if `stream` isn't a
+/// terminal or isn't the controlling terminal.
+pub fn terminal_session_pid(stream: Stream) -> Option<i32> {
+    if !is(stream) {
+        return None;
+    }
+    let session = rustix::termios::tcgetsid(stream.as_fd()).ok()?;
+    // rustix's raw pids are unsigned, but they're always positive.
+    Some(rustix::process::Pid::as_raw(Some(session)) as i32)
+}
+
+/// The process groups registered with [`register_child_pgid`], with `0` for
+/// unused slots.
+static CHILD_PGIDS: [core::sync::atomic::AtomicI32; 8] = [
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+    core::sync::atomic::AtomicI32::new(0),
+];
+
+/// Registers `pgid` as the process group of a child the process spawned,
+/// for [`stdin_shared_with_children`].
+///
+/// Up to eight process groups can be registered at once; this returns
+/// `false` if that many already are, or if `pgid` isn't positive.
+/// Registering a process group which already is has no effect, even from
+/// several threads at once.
+pub fn register_child_pgid(pgid: i32) -> bool {
+    use core::sync::atomic::Ordering;
+
+    if pgid <= 0 {
+        return false;
+    }
+    if child_pgid_registered(pgid) {
+        return true;
+    }
+    for (index, slot) in CHILD_PGIDS.iter().enumerate() {
+        if slot
+            .compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            // Another thread may have registered `pgid` in an earlier slot
+            // at the same time. Only the earliest is kept, so that one of
+            // them always is.
+            if CHILD_PGIDS[..index]
+                .iter()
+                .any(|earlier| earlier.load(Ordering::SeqCst) == pgid)
+            {
+                let _ = slot.compare_exchange(pgid, 0, Ordering::SeqCst, Ordering::SeqCst);
+            }
+            return true;
+        }
+    }
+    false
+}
+
+/// Forgets `pgid`, registered with [`register_child_pgid`], as when the
+/// child has exited.
+///
+/// Every slot holding `pgid` is cleared, in case a registration racing
+/// with another thread's unregistration left it in more than one.
+pub fn unregister_child_pgid(pgid: i32) {
+    use core::sync::atomic::Ordering;
+
+    for slot in &CHILD_PGIDS {
+        let _ = slot.compare_exchange(pgid, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn child_pgid_registered(pgid: i32) -> bool {
+    use core::sync::atomic::Ordering;
+
+    pgid > 0
+        && CHILD_PGIDS
+            .iter()
+            .any(|slot| slot.load(Ordering::SeqCst) == pgid)
+}
+
+/// Returns `true` if the foreground process group of the terminal on
+/// standard input is that of a child registered with
+/// [`register_child_pgid`], so that what the user types goes to the child
+/// as well as, or instead of, this process.
+///
+/// Supervisors can use this to decide whether forwarding terminal input to
+/// children would be ambiguous. This is `false` if standard input isn't a
+/// terminal, or isn't the process's controlling terminal, since only that
+/// has a foreground process group which can be asked for.
+#[allow(clippy::unnecessary_cast)]
+pub fn stdin_shared_with_children() -> bool {
+    if !is(Stream::Stdin) {
+        return false;
+    }
+    rustix::termios::tcgetpgrp(Stream::Stdin.as_fd()).map_or(false, |pgrp| {
+        // rustix's raw pids are unsigned on some platforms, but they're
+        // always positive.
+        child_pgid_registered(rustix::process::Pid::as_raw(Some(pgrp)) as i32)
+    })
+}
+
+/// Returns `true` if the process looks like it was launched from a graphical
+/// desktop, such as by double-clicking it in Finder or through a `.desktop`
+/// file, rather than from a terminal.
+///
+/// Programs can use this to report errors with a dialog instead of to
+/// stderr, which nobody will see. It's a heuristic: it requires that the
+/// process has no [controlling terminal](has_controlling_tty) and that `TERM`
+/// is unset, as terminals always set it. To rule out daemons and cron jobs,
+/// it also requires a sign of a graphical session: on macOS,
+/// `__CF_USER_TEXT_ENCODING`, which is set for processes in a login session,
+/// and elsewhere `DISPLAY` or `WAYLAND_DISPLAY`.
+#[cfg(feature = "std")]
+pub fn launched_from_gui() -> bool {
+    launched_from_gui_with(
+        has_controlling_tty(),
+        &SystemEnv,
+        cfg!(any(target_os = "macos", target_os = "ios")),
+    )
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn launched_from_gui_with(
+    controlling_tty: bool,
+    env: &dyn EnvSource,
+    macos: bool,
+) -> bool {
+    let graphical_session = if macos {
+        env.get("__CF_USER_TEXT_ENCODING").is_some()
+    } else {
+        env.get("DISPLAY").is_some() || env.get("WAYLAND_DISPLAY").is_some()
+    };
+    !controlling_tty && env.get("TERM").is_none() && graphical_session
+}
+
+/// Returns whether anything still has the read end of the pipe on `stream`
+/// open, so that writing to it wouldn't fail with `EPIPE` or raise `SIGPIPE`.
+///
+/// Long-running producers can use this to stop early when their consumer,
+/// such as `head`, has exited without waiting to be killed by their next
+/// write. This polls the pipe for `POLLERR` and `POLLHUP`, which report a
+/// closed read end. It returns `None` if `stream` isn't an output stream or
+/// isn't a pipe.
+pub fn downstream_connected(stream: Stream) -> Option<bool> {
+    use rustix::io::{poll, PollFd, PollFlags};
+
+    if !stream.is_output() || !is_pipe(stream) {
+        return None;
+    }
+    let fd = stream.as_fd();
+    let mut fds = [PollFd::new(&fd, PollFlags::empty())];
+    retry_on_eintr(|| poll(&mut fds, 0)).ok()?;
+    Some(!fds[0].revents().intersects(PollFlags::ERR | PollFlags::HUP))
+}
+
+/// Returns whether `SIGPIPE` is ignored, so that writing to a pipe whose
+/// read end is closed fails with `EPIPE` instead of killing the process.
+///
+/// Rust programs ignore `SIGPIPE` by default: the standard library sets it
+/// to `SIG_IGN` before `main` runs, so a broken pipe shows up as an error
+/// from `write`, which `println!` turns into a panic. Programs which don't go
+/// through Rust's `main`, or which called [`restore_default_sigpipe`], see
+/// the disposition they were started with.
+#[cfg(feature = "std")]
+pub fn sigpipe_ignored() -> bool {
+    let mut action = std::mem::MaybeUninit::<libc::sigaction>::uninit();
+    let action = unsafe {
+        if libc::sigaction(libc::SIGPIPE, std::ptr::null(), action.as_mut_ptr()) != 0 {
+            return false;
+        }
+        action.assume_init()
+    };
+    action.sa_sigaction == libc::SIG_IGN
+}
+
+/// Restores the default `SIGPIPE` disposition, so that writing to a closed
+/// pipe ends the process quietly, as it does for C programs.
+///
+/// This is the conventional behavior for command-line filters: `cmd | head`
+/// should stop `cmd` without an error message once `head` has read enough.
+/// It undoes the `SIG_IGN` that Rust's runtime installs before `main`; call
+/// it early, before any threads are spawned. Programs which want to finish
+/// cleanly on a broken pipe can instead check [`downstream_connected`] or
+/// handle `EPIPE` themselves.
+#[cfg(feature = "std")]
+pub fn restore_default_sigpipe() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+/// Returns the capacity of the pipe `stream` writes to or reads from, in
+/// bytes, or `None` if it isn't a pipe.
+///
+/// Programs writing large amounts of output to a pipe can size their writes
+/// to match. This is only available on Linux.
+#[cfg(all(any(target_os = "linux", target_os = "android"), feature = "std"))]
+pub fn pipe_capacity(stream: Stream) -> Option<usize> {
+    use std::os::unix::io::AsRawFd;
+
+    if !is_pipe(stream) {
+        return None;
+    }
+    match unsafe { libc::fcntl(stream.as_fd().as_raw_fd(), libc::F_GETPIPE_SZ) } {
+        -1 => None,
+        size => Some(size as usize),
+    }
+}
@@ -0,0 +1,439 @@
+//! APIs specific to Windows.
+
+use crate::*;
+
+/// Returns what `GetFileType` reports for `stream`'s handle:
+/// `FILE_TYPE_CHAR` for consoles and character devices such as `NUL`,
+/// `FILE_TYPE_DISK` for files, `FILE_TYPE_PIPE` for pipes and sockets, or
+/// `FILE_TYPE_UNKNOWN`.
+///
+/// This returns `None` instead of `FILE_TYPE_UNKNOWN` if `GetFileType`
+/// failed, as it does for invalid or unset handles. MSYS ptys are pipes, so
+/// they're reported as `FILE_TYPE_PIPE`. This is only available on Windows.
+pub fn file_type(stream: Stream) -> Option<u32> {
+    file_type_of(stream.as_raw_handle())
+}
+
+pub(crate) fn file_type_of(handle: HANDLE) -> Option<u32> {
+    use windows_sys::Win32::Foundation::{GetLastError, SetLastError, NO_ERROR};
+    use windows_sys::Win32::Storage::FileSystem::{GetFileType, FILE_TYPE_UNKNOWN};
+
+    unsafe {
+        SetLastError(NO_ERROR);
+        match GetFileType(handle) {
+            FILE_TYPE_UNKNOWN if GetLastError() != NO_ERROR => None,
+            file_type => Some(file_type),
+        }
+    }
+}
+
+/// Returns `true` if `stream` is a terminal, or looks like an MSYS-style
+/// pty that [`is`] didn't recognize: a pipe, while `TERM` names the xterm
+/// family, as MSYS2 and Git Bash set it.
+///
+/// [`is`] recognizes MSYS ptys by their pipe names, which some shells and
+/// versions name differently. This looser check catches those, at the cost
+/// of also matching genuine pipes inside such shells, as in `cmd | less`, so
+/// `is` doesn't use it. Disk files never match. This is only available on
+/// Windows.
+#[cfg(feature = "std")]
+pub fn is_msys_like(stream: Stream) -> bool {
+    use windows_sys::Win32::Storage::FileSystem::FILE_TYPE_PIPE;
+
+    is(stream)
+        || msys_like_with(
+            file_type(stream) == Some(FILE_TYPE_PIPE),
+            env_var("TERM").as_deref(),
+        )
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn msys_like_with(pipe: bool, term: Option<&str>) -> bool {
+    pipe && term.map_or(false, |term| term.starts_with("xterm"))
+}
+
+/// Returns `true` if the process is running in ConEmu, or Cmder, which is
+/// built on it, with its ANSI escape sequence processing turned on.
+///
+/// ConEmu predates Windows Terminal and interprets escape sequences itself
+/// rather than relying on the console's virtual-terminal processing, so it
+/// may support them where [`is_msys_like`] or a console-mode check wouldn't
+/// say so. It sets `ConEmuPID` to identify itself, and `ConEmuANSI` to `ON`
+/// or `OFF` according to its settings; this returns whether the latter is
+/// `ON`. It's only available on Windows.
+#[cfg(feature = "std")]
+pub fn is_conemu() -> bool {
+    is_conemu_with(&SystemEnv)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn is_conemu_with(env: &dyn EnvSource) -> bool {
+    env.get("ConEmuANSI").as_deref() == Some("ON")
+}
+
+/// The number of pseudoconsoles currently registered with
+/// [`register_pseudoconsole`].
+static PSEUDOCONSOLES: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// Records that the process has created the pseudoconsole `hpcon` with
+/// `CreatePseudoConsole`, so that [`owns_pseudoconsole`] reports it.
+///
+/// Call [`unregister_pseudoconsole`] when closing it with
+/// `ClosePseudoConsole`. Registrations are counted rather than remembered
+/// individually, so each registration needs exactly one matching
+/// unregistration. This is only available on Windows.
+pub fn register_pseudoconsole(hpcon: windows_sys::Win32::System::Console::HPCON) {
+    let _ = hpcon;
+    PSEUDOCONSOLES.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+}
+
+/// Undoes a call to [`register_pseudoconsole`].
+pub fn unregister_pseudoconsole(hpcon: windows_sys::Win32::System::Console::HPCON) {
+    use core::sync::atomic::Ordering;
+
+    let _ = hpcon;
+    let _ = PSEUDOCONSOLES.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+        count.checked_sub(1)
+    });
+}
+
+/// Returns `true` if the process is hosting a pseudoconsole of its own, as
+/// terminal emulators and multiplexers do, rather than only running in one.
+///
+/// This is `true` if a pseudoconsole is currently registered with
+/// [`register_pseudoconsole`], or if one of the standard handles is an
+/// anonymous pipe this process created, which is how a ConPTY host's own
+/// plumbing shows up when it's wired to its standard handles. Such a host
+/// shouldn't treat its own pseudoconsole as an interactive terminal to talk
+/// to. This is only available on Windows.
+pub fn owns_pseudoconsole() -> bool {
+    use windows_sys::Win32::Storage::FileSystem::FILE_TYPE_PIPE;
+    use windows_sys::Win32::System::Threading::GetCurrentProcessId;
+
+    if PSEUDOCONSOLES.load(core::sync::atomic::Ordering::Relaxed) != 0 {
+        return true;
+    }
+    let pid = unsafe { GetCurrentProcessId() };
+    [Stream::Stdin, Stream::Stdout, Stream::Stderr]
+        .iter()
+        .map(|stream| stream.as_raw_handle())
+        .filter(|&handle| file_type_of(handle) == Some(FILE_TYPE_PIPE))
+        .filter_map(|handle| unsafe { pipe_name_of(handle) })
+        .any(|name| is_own_anonymous_pipe_name(&name, pid))
+}
+
+/// Returns `true` if `name` is the name of an anonymous pipe created by
+/// `CreatePipe` in the process `pid`, such as
+/// `\Win32Pipes.000012a4.00000002`, which embeds the creator's process ID
+/// in hex.
+pub(crate) fn is_own_anonymous_pipe_name(name: &str, pid: u32) -> bool {
+    let name = name.trim_start_matches('\\');
+    let name = name.strip_prefix("Device\\NamedPipe\\").unwrap_or(name);
+    name.strip_prefix("Win32Pipes.")
+        .and_then(|rest| rest.get(..9))
+        .and_then(|rest| rest.strip_suffix('.'))
+        .and_then(|creator| u32::from_str_radix(creator, 16).ok())
+        == Some(pid)
+}
+
+pub(crate) fn needs_crlf_with(mode: Option<u32>) -> bool {
+    use windows_sys::Win32::System::Console::{
+        DISABLE_NEWLINE_AUTO_RETURN, ENABLE_PROCESSED_OUTPUT,
+    };
+
+    mode.map_or(false, |mode| {
+        mode & ENABLE_PROCESSED_OUTPUT == 0 || mode & DISABLE_NEWLINE_AUTO_RETURN != 0
+    })
+}
+
+/// Returns `true` if `AllocConsole` would likely succeed, that is, if the
+/// process has no console yet and isn't running in session 0.
+///
+/// GUI programs which want to open a console for verbose output can check
+/// this first. Services run in session 0, which has no interactive desktop
+/// for a console window to appear on. This doesn't allocate anything. It
+/// returns `false` if the process already has a console, including a hidden
+/// one, and is only available on Windows.
+pub fn can_alloc_console() -> bool {
+    use windows_sys::Win32::System::Console::{GetConsoleCP, GetConsoleWindow};
+    use windows_sys::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    use windows_sys::Win32::System::Threading::GetCurrentProcessId;
+
+    // `GetConsoleWindow` is null for consoles created without a window, but
+    // `GetConsoleCP` still succeeds for them.
+    let has_console = unsafe { GetConsoleWindow() != 0 || GetConsoleCP() != 0 };
+    let mut session = 0;
+    let session = if unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) } != 0 {
+        Some(session)
+    } else {
+        None
+    };
+    can_alloc_console_with(has_console, session)
+}
+
+pub(crate) fn can_alloc_console_with(has_console: bool, session: Option<u32>) -> bool {
+    !has_console && session.map_or(false, |session| session != 0)
+}
+
+/// Attaches the process to its parent's console, returning whether it did,
+/// so that a GUI-subsystem program launched from a terminal can write to it.
+///
+/// This calls `AttachConsole(ATTACH_PARENT_PROCESS)`, and then opens
+/// `CONIN$` and `CONOUT$` for any standard handles the process started
+/// without, so that [`is`] and printing work against the inherited console.
+/// Standard handles which were already redirected are left alone. Sizes
+/// remembered by `size_cached`, and answers remembered by `is_cached`, are
+/// forgotten, since they describe whatever the handles referred to before.
+/// This returns `false` if the parent has no console or the process already
+/// has one. It's only available on Windows.
+pub fn attach_parent_console() -> bool {
+    use windows_sys::Win32::System::Console::{
+        AttachConsole, ATTACH_PARENT_PROCESS, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    if unsafe { AttachConsole(ATTACH_PARENT_PROCESS) } == 0 {
+        return false;
+    }
+    unsafe {
+        reopen_std_handle(STD_INPUT_HANDLE, "CONIN$");
+        reopen_std_handle(STD_OUTPUT_HANDLE, "CONOUT$");
+        reopen_std_handle(STD_ERROR_HANDLE, "CONOUT$");
+    }
+
+    #[cfg(feature = "cache")]
+    {
+        invalidate_size_cache();
+        invalidate_tty_cache();
+    }
+
+    true
+}
+
+/// Sets the standard handle `id` to a new handle for the console device
+/// `device`, unless it's already set.
+unsafe fn reopen_std_handle(id: windows_sys::Win32::System::Console::STD_HANDLE, device: &str) {
+    use windows_sys::Win32::Foundation::{GENERIC_READ, GENERIC_WRITE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Console::{GetStdHandle, SetStdHandle};
+
+    let handle = GetStdHandle(id);
+    if handle != 0 && handle != INVALID_HANDLE_VALUE {
+        return;
+    }
+    let name: Vec<u16> = device.encode_utf16().chain(Some(0)).collect();
+    let handle = CreateFileW(
+        name.as_ptr(),
+        GENERIC_READ | GENERIC_WRITE,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        core::ptr::null(),
+        OPEN_EXISTING,
+        0,
+        0,
+    );
+    if handle != INVALID_HANDLE_VALUE {
+        SetStdHandle(id, handle);
+    }
+}
+
+pub(crate) fn handle_is_console(handle: BorrowedHandle<'_>) -> bool {
+    handle_console_confidence(handle).value()
+}
+
+// This is copied from `handle_is_console` in std/src/sys/windows/io.rs in
+// Rust at revision d7b0bcb20f2f7d5f3ea3489d56ece630147e98f5.
+#[cfg(not(feature = "win-minimal"))]
+pub(crate) fn handle_console_confidence(handle: BorrowedHandle<'_>) -> Confidence {
+    use windows_sys::Win32::System::Console::{
+        GetConsoleMode, GetStdHandle, STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    let handle = handle.as_raw_handle();
+
+    unsafe {
+        // A null handle means the process has no console.
+        if handle.is_null() {
+            return Confidence::Certain(false);
+        }
+
+        let mut out = 0;
+        if GetConsoleMode(handle as HANDLE, &mut out) != 0 {
+            // False positives aren't possible. If we got a console then we definitely have a console.
+            return Confidence::Certain(true);
+        }
+
+        // At this point, we *could* have a false negative. We can determine that this is a true
+        // negative if we can detect the presence of a console on any of the standard I/O streams. If
+        // another stream has a console, then we know we're in a Windows console and can therefore
+        // trust the negative.
+        for std_handle in [STD_INPUT_HANDLE, STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            let std_handle = GetStdHandle(std_handle);
+            if std_handle != 0
+                && std_handle != handle as HANDLE
+                && GetConsoleMode(std_handle, &mut out) != 0
+            {
+                return Confidence::Certain(false);
+            }
+        }
+
+        // Otherwise, we fall back to an msys hack to see if we can detect the presence of a pty.
+        Confidence::Uncertain(msys_tty_on(handle as HANDLE))
+    }
+}
+
+/// With the `win-minimal` feature, consoles are recognized by their file type
+/// alone, which needs nothing from the console API set. This can't tell a
+/// console from other character devices, such as `NUL`, and doesn't
+/// recognize MSYS ptys.
+#[cfg(feature = "win-minimal")]
+pub(crate) fn handle_console_confidence(handle: BorrowedHandle<'_>) -> Confidence {
+    use windows_sys::Win32::Storage::FileSystem::{FILE_TYPE_CHAR, FILE_TYPE_PIPE};
+
+    let handle = handle.as_raw_handle();
+    if handle.is_null() {
+        return Confidence::Certain(false);
+    }
+    match file_type_of(handle as HANDLE) {
+        Some(FILE_TYPE_CHAR) => Confidence::Uncertain(true),
+        // This may be an MSYS pty.
+        Some(FILE_TYPE_PIPE) => Confidence::Uncertain(false),
+        _ => Confidence::Certain(false),
+    }
+}
+
+/// Returns the console mode of `handle`, or `None` if it isn't a console.
+pub(crate) fn console_mode(handle: HANDLE) -> Option<u32> {
+    use windows_sys::Win32::System::Console::GetConsoleMode;
+
+    let mut mode = 0;
+    if handle != 0 && unsafe { GetConsoleMode(handle, &mut mode) } != 0 {
+        Some(mode)
+    } else {
+        None
+    }
+}
+
+/// Returns true if the console attached to standard input accepts
+/// `ENABLE_VIRTUAL_TERMINAL_INPUT`.
+#[cfg(feature = "term")]
+pub(crate) fn console_supports_vt_input() -> bool {
+    use windows_sys::Win32::System::Console::{SetConsoleMode, ENABLE_VIRTUAL_TERMINAL_INPUT};
+
+    let handle = Stream::Stdin.as_raw_handle();
+    let mode = match console_mode(handle) {
+        Some(mode) => mode,
+        None => return false,
+    };
+    if mode & ENABLE_VIRTUAL_TERMINAL_INPUT != 0 {
+        return true;
+    }
+
+    // Consoles which predate VT support reject the flag, so try setting it,
+    // and put the original mode back if that worked.
+    unsafe {
+        if SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_INPUT) == 0 {
+            return false;
+        }
+        SetConsoleMode(handle, mode);
+    }
+    true
+}
+
+/// Returns whether the console's quick-edit mode is on, so that dragging
+/// the mouse selects text, or `None` if `stream` isn't a console input
+/// handle, which it can only be for stdin.
+///
+/// Quick-edit mode consumes mouse input, so a console program which turns
+/// on mouse reporting, such as with `\x1b[?1000h`, receives no mouse events
+/// while it's on. TUIs which handle the mouse can turn it off with
+/// [`set_quick_edit`], and turn it back on when they exit if this reported
+/// it on beforehand.
+pub fn quick_edit_enabled(stream: Stream) -> Option<bool> {
+    use windows_sys::Win32::System::Console::ENABLE_QUICK_EDIT_MODE;
+
+    if !stream.is_input() {
+        return None;
+    }
+    console_mode(stream.as_raw_handle()).map(|mode| mode & ENABLE_QUICK_EDIT_MODE != 0)
+}
+
+/// Turns the quick-edit mode of the console attached to stdin on or off,
+/// as reported by [`quick_edit_enabled`], returning `true` on success.
+///
+/// This fails if stdin isn't a console. The rest of the console's input
+/// mode is left alone.
+pub fn set_quick_edit(enabled: bool) -> bool {
+    use windows_sys::Win32::System::Console::{
+        SetConsoleMode, ENABLE_EXTENDED_FLAGS, ENABLE_QUICK_EDIT_MODE,
+    };
+
+    let handle = Stream::Stdin.as_raw_handle();
+    let mode = match console_mode(handle) {
+        Some(mode) => mode,
+        None => return false,
+    };
+    // Quick-edit mode is only changed along with `ENABLE_EXTENDED_FLAGS`.
+    let mode = if enabled {
+        mode | ENABLE_EXTENDED_FLAGS | ENABLE_QUICK_EDIT_MODE
+    } else {
+        (mode | ENABLE_EXTENDED_FLAGS) & !ENABLE_QUICK_EDIT_MODE
+    };
+    unsafe { SetConsoleMode(handle, mode) != 0 }
+}
+
+/// Returns true if there is an MSYS tty on the given handle.
+///
+/// This incoproates d7b0bcb20f2f7d5f3ea3489d56ece630147e98f5
+pub(crate) unsafe fn msys_tty_on(handle: HANDLE) -> bool {
+    use windows_sys::Win32::Storage::FileSystem::{GetFileType, FILE_TYPE_PIPE};
+
+    // Early return if the handle is not a pipe.
+    if GetFileType(handle) != FILE_TYPE_PIPE {
+        return false;
+    }
+
+    match pipe_name_of(handle) {
+        Some(name) => classify_pipe_name(&name).is_some(),
+        None => false,
+    }
+}
+
+/// Returns the name of the file `handle` refers to, as reported by
+/// `GetFileInformationByHandleEx`, or `None` if it can't be determined.
+pub(crate) unsafe fn pipe_name_of(handle: HANDLE) -> Option<String> {
+    use std::ffi::c_void;
+    use windows_sys::Win32::{
+        Foundation::MAX_PATH,
+        Storage::FileSystem::{FileNameInfo, GetFileInformationByHandleEx},
+    };
+
+    /// Mirrors windows_sys::Win32::Storage::FileSystem::FILE_NAME_INFO, giving
+    /// it a fixed length that we can stack allocate
+    #[repr(C)]
+    #[allow(non_snake_case)]
+    struct FILE_NAME_INFO {
+        FileNameLength: u32,
+        FileName: [u16; MAX_PATH as usize],
+    }
+    let mut name_info = FILE_NAME_INFO {
+        FileNameLength: 0,
+        FileName: [0; MAX_PATH as usize],
+    };
+    // Safety: buffer length is fixed.
+    let res = GetFileInformationByHandleEx(
+        handle,
+        FileNameInfo,
+        &mut name_info as *mut _ as *mut c_void,
+        std::mem::size_of::<FILE_NAME_INFO>() as u32,
+    );
+    if res == 0 {
+        return None;
+    }
+
+    // Use `get` because `FileNameLength` can be out of range.
+    let s = name_info
+        .FileName
+        .get(..name_info.FileNameLength as usize / 2)?;
+    Some(String::from_utf16_lossy(s))
+}